@@ -27,7 +27,7 @@ fn main() {
         .expect("Invalid value for baudrate");
 
     let s = SerialPortSettings {
-        baud_rate: baud_rate,
+        baud_rate,
         data_bits: DataBits::Eight,
         flow_control: FlowControl::None,
         parity: Parity::None,
@@ -42,7 +42,7 @@ fn main() {
         .write_data_terminal_ready(false)
         .expect("failed to clear DTR");
 
-    let channel = Channel::<RplidarHostProtocol, serialport::SerialPort>::new(
+    let channel = Channel::<RplidarHostProtocol, dyn serialport::SerialPort>::new(
         RplidarHostProtocol::new(),
         serial_port,
     );
@@ -105,7 +105,7 @@ fn main() {
     println!("Typical scan mode: {}", typical_scan_mode);
 
     match rplidar.check_motor_ctrl_support() {
-        Ok(support) if support == true => {
+        Ok(true) => {
             println!("Accessory board is detected and support motor control, starting motor...");
             rplidar.set_motor_pwm(600).expect("failed to start motor");
         },
@@ -1,8 +1,8 @@
 use super::ring_byte_buffer::RingByteBuffer;
+use super::throughput::ThroughputMeter;
 use super::prelude::*;
-use std::io;
+use std::io::{ self, Write };
 use std::time::{ Instant, Duration };
-use failure::{Error, Fail};
 
 const DEFAULT_CHANNEL_READ_BUFFER_SIZE: usize = 1024;
 
@@ -22,6 +22,8 @@ pub struct Channel<P, T: ?Sized> {
     protocol: P,
     stream: Box<T>,
     read_buffer: RingByteBuffer,
+    read_throughput: ThroughputMeter,
+    read_buffer_high_water: usize,
 }
 
 impl<P, T: ?Sized> Channel<P, T>
@@ -61,6 +63,8 @@ where
             protocol: protocol,
             stream: stream,
             read_buffer: RingByteBuffer::with_capacity(read_buffer_size),
+            read_throughput: ThroughputMeter::new(),
+            read_buffer_high_water: 0,
         };
 
         chn.reset();
@@ -83,6 +87,28 @@ where
         self.protocol.reset_decoder();
     }
 
+    /// Discard bytes read from the stream but not decoded yet, returns count of discarded bytes
+    ///
+    /// Call it with `reset` to resync with the stream after the data is corrupted
+    pub fn discard_pending_bytes(&mut self) -> usize {
+        let pending = self.read_buffer.len();
+        self.read_buffer.skip_bytes(pending);
+        return pending;
+    }
+
+    /// Borrow the underlying stream
+    pub fn stream(&self) -> &T {
+        return &self.stream;
+    }
+
+    /// Mutably borrow the underlying stream
+    ///
+    /// Reading from or writing to the stream directly will corrupt the protocol state,
+    /// `reset` may be required afterwards
+    pub fn stream_mut(&mut self) -> &mut T {
+        return &mut self.stream;
+    }
+
     /// Read message from channel
     /// 
     /// # Example
@@ -93,7 +119,9 @@ where
     /// ```
     pub fn read(&mut self) -> Result<Option<Message>> {
         loop {
-            self.read_buffer.read_from(&mut self.stream)?;
+            let read = self.read_buffer.read_from(&mut self.stream)?;
+            self.read_throughput.record(read, Instant::now());
+            self.read_buffer_high_water = self.read_buffer_high_water.max(self.read_buffer.len());
 
             let (decoded, msg) = self
                 .protocol
@@ -104,12 +132,88 @@ where
                 return Ok(Option::None);
             }
 
-            if let Some(_) = msg {
+            if msg.is_some() {
                 return Ok(msg);
             }
         }
     }
 
+    /// Read from the stream once without decoding, returns count of bytes read
+    ///
+    /// A single read call is made on the stream, so it never waits longer than the read timeout of the stream.
+    /// Reads timed out or would block read nothing instead of failing
+    ///
+    /// # Example
+    /// ```ignore
+    /// channel.read_once()?;
+    /// while let Some(msg) = channel.decode_buffered()? {
+    ///     println!("{:?}", msg);
+    /// }
+    /// ```
+    pub fn read_once(&mut self) -> Result<usize> {
+        let read = self.read_buffer.read_once_from(&mut self.stream)?;
+        self.read_throughput.record(read, Instant::now());
+        self.read_buffer_high_water = self.read_buffer_high_water.max(self.read_buffer.len());
+        return Ok(read);
+    }
+
+    /// Decode message from the bytes already read from the stream, without reading the stream
+    pub fn decode_buffered(&mut self) -> Result<Option<Message>> {
+        loop {
+            let (decoded, msg) = self
+                .protocol
+                .decode(self.read_buffer.current_read_slice())?;
+            self.read_buffer.skip_bytes(decoded);
+
+            if msg.is_some() {
+                return Ok(msg);
+            }
+
+            if decoded == 0 {
+                return Ok(Option::None);
+            }
+        }
+    }
+
+    /// Decode bytes fed by caller instead of read from the stream
+    ///
+    /// The bytes are decoded after the bytes already buffered, and trailing incomplete message
+    /// will be completed by future reads or feeds
+    ///
+    /// # Example
+    /// ```ignore
+    /// for msg in channel.feed(&captured_bytes)? {
+    ///     println!("{:?}", msg);
+    /// }
+    /// ```
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Message>> {
+        let mut msgs = Vec::new();
+        let mut remaining = data;
+
+        loop {
+            let written = self.read_buffer.write(remaining)?;
+            remaining = &remaining[written..];
+            self.read_buffer_high_water = self.read_buffer_high_water.max(self.read_buffer.len());
+
+            loop {
+                let (decoded, msg) = self
+                    .protocol
+                    .decode(self.read_buffer.current_read_slice())?;
+                self.read_buffer.skip_bytes(decoded);
+
+                if let Some(msg) = msg {
+                    msgs.push(msg);
+                } else if decoded == 0 {
+                    break;
+                }
+            }
+
+            if remaining.is_empty() {
+                return Ok(msgs);
+            }
+        }
+    }
+
     /// Read message until timeout
     /// 
     /// # Example
@@ -128,6 +232,31 @@ where
         return Err(RposError::OperationTimeout.into());
     }
 
+    /// Bytes per second read from the stream recently, or `None` if not enough data was read yet
+    pub fn measured_throughput_bps(&self) -> Option<f32> {
+        return self.read_throughput.bytes_per_second();
+    }
+
+    /// Bytes read from the stream but not decoded yet
+    pub fn pending_bytes(&self) -> usize {
+        return self.read_buffer.len();
+    }
+
+    /// Max bytes buffered for decoding since the channel is created or `reset_read_buffer_high_water` is called
+    pub fn read_buffer_high_water(&self) -> usize {
+        return self.read_buffer_high_water;
+    }
+
+    /// Restart tracking the max bytes buffered for decoding from the bytes currently buffered
+    pub fn reset_read_buffer_high_water(&mut self) {
+        self.read_buffer_high_water = self.read_buffer.len();
+    }
+
+    /// Capacity of the buffer of bytes read from the stream but not decoded yet
+    pub fn read_buffer_capacity(&self) -> usize {
+        return self.read_buffer.capacity();
+    }
+
     /// Write message to channel
     /// 
     /// # Example
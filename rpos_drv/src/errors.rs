@@ -1,4 +1,5 @@
-use std::error;
+#![allow(non_local_definitions)]
+
 pub use failure::{ Fail, Error };
 
 #[derive(Fail, Debug)]
@@ -25,7 +26,12 @@ pub enum RposError {
 
     /// The buffer is too small for message encoding
     #[fail(display="buffer is too small for message encoding")]
-    BufferTooSmall
+    BufferTooSmall,
+
+    /// The device is occupied by another operation (e.g. still streaming for another session),
+    /// the operation may succeed after waiting or stopping the device
+    #[fail(display="device busy")]
+    DeviceBusy
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
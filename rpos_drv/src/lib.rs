@@ -4,13 +4,17 @@
 //! 
 //! `rpos_drv` is a collection of structs and traits to build drivers for RPOS.
 
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 extern crate failure;
 
 mod channel;
 mod prelude;
 mod ring_byte_buffer;
+mod throughput;
 mod errors;
 
 pub use self::prelude::*;
 pub use self::channel::*;
 pub use self::ring_byte_buffer::RingByteBuffer;
+pub use self::throughput::ThroughputMeter;
@@ -33,6 +33,11 @@ impl RingByteBuffer {
         return self.size;
     }
 
+    /// whether the ring buffer is empty
+    pub fn is_empty(&self) -> bool {
+        return self.size == 0;
+    }
+
     /// the capacity of the ring buffer
     pub fn capacity(&self) -> usize {
         return self.buf.len();
@@ -86,7 +91,7 @@ impl RingByteBuffer {
     }
 
     fn partial_read_from(&mut self, upstream: &mut impl Read) -> std::io::Result<usize> {
-        if self.current_write_slice().len() == 0 {
+        if self.current_write_slice().is_empty() {
             return Ok(0);
         }
 
@@ -96,7 +101,7 @@ impl RingByteBuffer {
                 Ok(read)
             },
             Err(err) => {
-                if err.kind() == std::io::ErrorKind::TimedOut {
+                if err.kind() == std::io::ErrorKind::TimedOut || err.kind() == std::io::ErrorKind::WouldBlock {
                     Ok(0)
                 } else {
                     Err(err)
@@ -105,6 +110,13 @@ impl RingByteBuffer {
         }
     }
 
+    /// read data from upstream with a single read call, so it never waits longer than the read timeout of upstream
+    ///
+    /// Reads timed out or would block read nothing instead of failing
+    pub fn read_once_from(&mut self, upstream: &mut impl Read) -> std::io::Result<usize> {
+        return self.partial_read_from(upstream);
+    }
+
     /// read data from upstream to fill the ring buffer
     /// 
     /// # Example
@@ -193,7 +205,7 @@ mod tests {
     fn read_and_write() {
         let mut ring_buf = super::RingByteBuffer::with_capacity(6);
 
-        let test_data = vec![1,2,3,4];
+        let test_data = [1,2,3,4];
 
         assert_eq!(ring_buf.write(&test_data[..]).unwrap(), 4);
         assert_eq!(ring_buf.len(), 4);
@@ -216,4 +228,21 @@ mod tests {
         assert_eq!(ring_buf.len(), 0);
         assert_eq!(ring_buf.free_space(), 6);
     }
+
+    struct WouldBlockStream;
+
+    impl Read for WouldBlockStream {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "would block"))
+        }
+    }
+
+    #[test]
+    fn read_once_without_data() {
+        let mut ring_buf = super::RingByteBuffer::with_capacity(6);
+
+        assert_eq!(ring_buf.read_once_from(&mut WouldBlockStream).unwrap(), 0);
+        assert_eq!(ring_buf.read_once_from(&mut &[1u8, 2, 3][..]).unwrap(), 3);
+        assert_eq!(ring_buf.current_read_slice(), [1, 2, 3]);
+    }
 }
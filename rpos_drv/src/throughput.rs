@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::time::{ Instant, Duration };
+
+const DEFAULT_THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Samples closer than this are merged to limit memory usage
+const THROUGHPUT_SAMPLE_RESOLUTION: Duration = Duration::from_millis(10);
+
+/// Rolling meter of bytes transferred per second
+///
+/// # Example
+/// ```rust
+/// # use std::time::{ Instant, Duration };
+/// let mut meter = rpos_drv::ThroughputMeter::new();
+/// let start = Instant::now();
+/// meter.record(0, start);
+/// meter.record(100, start + Duration::from_millis(500));
+/// assert_eq!(meter.bytes_per_second(), Some(200f32));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThroughputMeter {
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl ThroughputMeter {
+    /// create a meter with one second rolling window
+    pub fn new() -> ThroughputMeter {
+        ThroughputMeter::with_window(DEFAULT_THROUGHPUT_WINDOW)
+    }
+
+    /// create a meter with non-default rolling window
+    pub fn with_window(window: Duration) -> ThroughputMeter {
+        ThroughputMeter {
+            window: window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// record bytes transferred at specific time (zero bytes transferred should also be recorded)
+    pub fn record(&mut self, bytes: usize, now: Instant) {
+        let can_merge = self.samples.len() > 1;
+
+        match self.samples.back_mut() {
+            Some((time, total)) if can_merge && now.duration_since(*time) < THROUGHPUT_SAMPLE_RESOLUTION => {
+                *total += bytes;
+            }
+            _ => self.samples.push_back((now, bytes)),
+        }
+
+        while let Some((time, _)) = self.samples.front() {
+            if now.duration_since(*time) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// bytes per second in the rolling window, or `None` if there is not enough samples
+    pub fn bytes_per_second(&self) -> Option<f32> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+
+        let (start, _) = self.samples.front()?;
+        let (end, _) = self.samples.back()?;
+        let elapsed = end.duration_since(*start).as_secs_f32();
+
+        if elapsed <= 0f32 {
+            return None;
+        }
+
+        let bytes: usize = self.samples.iter().skip(1).map(|(_, bytes)| *bytes).sum();
+        return Some((bytes as f32) / elapsed);
+    }
+
+    /// clear all samples
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+impl Default for ThroughputMeter {
+    fn default() -> ThroughputMeter {
+        ThroughputMeter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{ Instant, Duration };
+
+    #[test]
+    fn rolling_throughput() {
+        let mut meter = super::ThroughputMeter::new();
+        let start = Instant::now();
+
+        assert_eq!(meter.bytes_per_second(), None);
+
+        meter.record(0, start);
+        for i in 1..=20u64 {
+            meter.record(1000, start + Duration::from_millis(i * 50));
+        }
+
+        assert_eq!(meter.bytes_per_second(), Some(20000f32));
+
+        // the samples out of the window should be dropped
+        for i in 21..=40u64 {
+            meter.record(100, start + Duration::from_millis(i * 50));
+        }
+
+        assert_eq!(meter.bytes_per_second(), Some(2000f32));
+    }
+}
@@ -1,8 +1,12 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Device info response
 pub const RPLIDAR_ANS_TYPE_DEVINFO : u8 = 0x4;
 
 /// Rplidar device info data strcture
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(packed)]
 #[repr(C)]
 pub struct RplidarResponseDeviceInfo {
@@ -18,6 +22,7 @@ pub const RPLIDAR_ANS_TYPE_DEVHEALTH : u8 = 0x6;
 
 /// Rplidar device health info data structure
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(packed)]
 #[repr(C)]
 pub struct RplidarResponseDeviceHealth {
@@ -56,7 +61,8 @@ pub struct RplidarResponseMeasurementNode {
 pub const RPLIDAR_RESP_MEASUREMENT_SYNCBIT : u8 = 1;
 pub const RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT : usize = 2;
 pub const RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT : usize = 1;
-// pub const RPLIDAR_RESP_MEASUREMENT_CHECKBIT : u8 = 1;
+pub const RPLIDAR_RESP_MEASUREMENT_INVERTED_SYNCBIT : u8 = 1 << 1;
+pub const RPLIDAR_RESP_MEASUREMENT_CHECKBIT : u16 = 1;
 
 /// Capsuled measurement answer (32pts per response)
 /// Added in FW ver 1.17
@@ -113,8 +119,19 @@ pub struct RplidarResponseHqCapsuledMeasurementNodes {
 pub const RPLIDAR_RESP_HQ_FLAG_SYNCBIT : u8 = 1;
 pub const RPLIDAR_RESP_MEASUREMENT_HQ_SYNC : u8 = 0xA5;
 
-// Added in FW ver 1.17
-// pub const RPLIDAR_ANS_TYPE_SAMPLE_RATE : u8 = 0x15;
+/// Sample rate answer
+/// Added in FW ver 1.17
+pub const RPLIDAR_ANS_TYPE_SAMPLE_RATE : u8 = 0x15;
+
+/// Sample durations of standard and express scan
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(packed)]
+#[repr(C)]
+pub struct RplidarResponseSampleRate {
+    pub std_sample_duration_us: u16,
+    pub express_sample_duration_us: u16
+}
 
 /// Ultra Capsuled measurement answer (96pts per response)
 /// added in FW ver 1.23alpha
@@ -131,6 +148,20 @@ pub struct RplidarResponseUltraCapsuleMeasurementNodes {
     pub ultra_cabins: [u32;32],
 }
 
+/// Dense Capsuled measurement answer (40pts per response, used by S3 high density modes)
+pub const RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED : u8 = 0x85;
+
+/// The data structure for each response packet of dense capsuled measurements
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(packed)]
+#[repr(C)]
+pub struct RplidarResponseDenseCapsuleMeasurementNodes {
+    pub s_checksum_1: u8,
+    pub s_checksum_2: u8,
+    pub start_angle_sync_q6: u16,
+    pub dense_cabins: [u16;40],
+}
+
 /// Answer type for getting LIDAR configuration
 /// added in FW ver 1.24
 pub const RPLIDAR_ANS_TYPE_GET_LIDAR_CONF : u8 = 0x20;
@@ -142,4 +173,4 @@ pub const RPLIDAR_ANS_TYPE_GET_LIDAR_CONF : u8 = 0x20;
 pub const RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG : u8 = 0xFF;
 
 /// Flag indicate that accessory board support motor control
-pub const RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK : u32 = (0x1);
+pub const RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK : u32 = 0x1;
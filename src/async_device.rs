@@ -0,0 +1,253 @@
+use super::answers::*;
+use super::cmds::*;
+use super::internals::*;
+use super::response_parser::FromResponse;
+use super::{
+    answer_mismatch_error, empty_message_error, lidar_conf_request, parse_lidar_conf_answer, parse_scan_mode_name,
+    scan_start_request, RplidarDevice, Result, RposError, ScanMode, ScanOptions, ScanPoint,
+    RPLIDAR_GET_LIDAR_CONF_START_VERSION,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use rpos_drv::{Message, ProtocolEncoder};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Bytes read from the transport at once
+const ASYNC_READ_CHUNK_SIZE: usize = 1024;
+
+/// Stream of the decoding core, which is never read or written, as the bytes are fed by `AsyncRplidarDevice`
+#[derive(Debug)]
+struct DetachedStream;
+
+impl Read for DetachedStream {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        return Ok(0);
+    }
+}
+
+impl Write for DetachedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+/// RPLIDAR over an async transport (e.g. a tokio serial port or TCP stream), enabled by the `async` feature
+///
+/// The answers are decoded by a `RplidarDevice` fed with the bytes read, so scan data is decoded the same way
+/// as the blocking API
+pub struct AsyncRplidarDevice<S> {
+    stream: S,
+    core: RplidarDevice<DetachedStream>,
+    pending_messages: VecDeque<Message>,
+    read_buf: Vec<u8>,
+}
+
+impl<S> AsyncRplidarDevice<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Construct a new AsyncRplidarDevice with async stream
+    ///
+    /// # Example
+    /// ```ignore
+    /// let stream = tokio::net::TcpStream::connect("192.168.11.2:20108").await?;
+    /// let mut rplidar = AsyncRplidarDevice::with_stream(stream);
+    /// ```
+    pub fn with_stream(stream: S) -> AsyncRplidarDevice<S> {
+        AsyncRplidarDevice {
+            stream: stream,
+            core: RplidarDevice::with_stream(Box::new(DetachedStream)),
+            pending_messages: VecDeque::new(),
+            read_buf: vec![0u8; ASYNC_READ_CHUNK_SIZE],
+        }
+    }
+
+    /// get device info of the RPLIDAR
+    pub async fn get_device_info(&mut self) -> Result<RplidarResponseDeviceInfo> {
+        self.get_device_info_with_timeout(RPLIDAR_DEFAULT_TIMEOUT).await
+    }
+
+    /// get device info of the RPLIDAR with timeout
+    pub async fn get_device_info_with_timeout(&mut self, timeout: Duration) -> Result<RplidarResponseDeviceInfo> {
+        let msg = self.invoke(&Message::new(RPLIDAR_CMD_GET_DEVICE_INFO), timeout).await?;
+
+        if msg.data.is_empty() {
+            return Err(empty_message_error());
+        } else if msg.cmd != RPLIDAR_ANS_TYPE_DEVINFO {
+            return Err(answer_mismatch_error(msg.cmd));
+        }
+
+        let device_info = RplidarResponseDeviceInfo::parse(&msg.data)?;
+        self.core.device_info = Some(device_info);
+        return Ok(device_info);
+    }
+
+    /// start scan with options
+    pub async fn start_scan_with_options(&mut self, options: &ScanOptions) -> Result<ScanMode> {
+        self.start_scan_with_options_and_timeout(options, RPLIDAR_DEFAULT_TIMEOUT).await
+    }
+
+    /// start scan with options and non-default timeout
+    pub async fn start_scan_with_options_and_timeout(
+        &mut self,
+        options: &ScanOptions,
+        timeout: Duration,
+    ) -> Result<ScanMode> {
+        let scan_mode = match options.scan_mode {
+            Some(mode) => mode,
+            None => self.get_typical_scan_mode_with_timeout(timeout).await?,
+        };
+
+        let scan_mode_info = self.get_scan_mode_with_timeout(scan_mode, timeout).await?;
+
+        let (scan_path, request) = scan_start_request(scan_mode, options);
+        self.write(&request).await?;
+        self.core.on_scan_started(&scan_mode_info, options, scan_path);
+
+        return Ok(scan_mode_info);
+    }
+
+    /// read scan point
+    pub async fn grab_scan_point(&mut self) -> Result<ScanPoint> {
+        self.grab_scan_point_with_timeout(RPLIDAR_DEFAULT_TIMEOUT).await
+    }
+
+    /// read scan point with timeout
+    pub async fn grab_scan_point_with_timeout(&mut self, timeout: Duration) -> Result<ScanPoint> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(point) = self.core.cached_measurement_nodes.pop_front() {
+                return Ok(point);
+            }
+
+            let msg = self.read_message(deadline).await?;
+            self.core.on_scan_data_msg(&msg)?;
+        }
+    }
+
+    /// get typical scan mode of target LIDAR with timeout
+    async fn get_typical_scan_mode_with_timeout(&mut self, timeout: Duration) -> Result<u16> {
+        let device_info = self.get_device_info_with_timeout(timeout).await?;
+
+        if device_info.firmware_version < RPLIDAR_GET_LIDAR_CONF_START_VERSION {
+            return Ok(if device_info.model >= 0x20u8 { 1u16 } else { 0u16 });
+        }
+
+        let scan_mode_data = self.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_TYPICAL, &[], timeout).await?;
+        return u16::parse(&scan_mode_data);
+    }
+
+    /// get scan mode of specific scan mode id
+    async fn get_scan_mode_with_timeout(&mut self, scan_mode: u16, timeout: Duration) -> Result<ScanMode> {
+        let mut param = [0; 2];
+        LittleEndian::write_u16(&mut param, scan_mode);
+
+        let us_per_sample_data = self
+            .get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, &param, timeout)
+            .await?;
+        let max_distance_data = self
+            .get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, &param, timeout)
+            .await?;
+        let ans_type_data = self
+            .get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, &param, timeout)
+            .await?;
+        let name_data = self.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_NAME, &param, timeout).await?;
+
+        return Ok(ScanMode {
+            id: scan_mode,
+            us_per_sample: (u32::parse(&us_per_sample_data)? as f32) / 256f32,
+            max_distance: (u32::parse(&max_distance_data)? as f32) / 256f32,
+            ans_type: u8::parse(&ans_type_data)?,
+            name: parse_scan_mode_name(&name_data),
+        });
+    }
+
+    /// get lidar config with parameter and timeout
+    async fn get_lidar_conf_with_timeout(&mut self, config_type: u32, param: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+        let response = self.invoke(&lidar_conf_request(config_type, param), timeout).await?;
+        return parse_lidar_conf_answer(config_type, response);
+    }
+
+    /// send a request and wait for the next message
+    async fn invoke(&mut self, request: &Message, timeout: Duration) -> Result<Message> {
+        self.write(request).await?;
+        return self.read_message(Instant::now() + timeout).await;
+    }
+
+    /// encode and send the message
+    async fn write(&mut self, msg: &Message) -> Result<()> {
+        let mut bytes = Vec::new();
+        super::RplidarHostProtocol::new().write_to(msg, &mut bytes)?;
+
+        self.stream.write_all(&bytes).await?;
+        self.stream.flush().await?;
+        return Ok(());
+    }
+
+    /// read the next message, decoding the bytes received until the deadline
+    async fn read_message(&mut self, deadline: Instant) -> Result<Message> {
+        loop {
+            if let Some(msg) = self.pending_messages.pop_front() {
+                return Ok(msg);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let read = match tokio::time::timeout(remaining, self.stream.read(&mut self.read_buf)).await {
+                Ok(read) => read?,
+                Err(_) => return Err(RposError::OperationTimeout.into()),
+            };
+
+            if read == 0 {
+                return Err(RposError::OperationFail { description: "stream closed".to_owned() }.into());
+            }
+
+            let msgs = self.core.channel.feed(&self.read_buf[..read])?;
+            self.pending_messages.extend(msgs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{answer, device_info_payload, hq_capsule, scan_mode_answers};
+
+    #[tokio::test]
+    async fn grab_scan_points_from_duplex_stream() {
+        let (client, mut server) = tokio::io::duplex(4096);
+
+        let mut responses = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x61, 0x0118), false);
+        responses.extend(scan_mode_answers(125f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Boost\0"));
+        responses.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true));
+        responses.extend(hq_capsule(256, 8000));
+        server.write_all(&responses).await.unwrap();
+
+        let mut rplidar = AsyncRplidarDevice::with_stream(client);
+        let device_info = rplidar.get_device_info().await.unwrap();
+        assert_eq!(device_info.model, 0x61);
+
+        let scan_mode = rplidar.start_scan_with_options(&ScanOptions::with_mode(2)).await.unwrap();
+        assert_eq!(scan_mode.name, "Boost");
+        assert_eq!(scan_mode.ans_type, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ);
+
+        for i in 0..32u16 {
+            let point = rplidar.grab_scan_point().await.unwrap();
+            assert_eq!(point.angle_z_q14, if i < 16 { i * 16 } else { 256 + (i - 16) * 16 });
+        }
+
+        let err = rplidar.grab_scan_point_with_timeout(Duration::from_millis(20)).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationTimeout)));
+
+        // the requests are encoded as the blocking API does
+        let mut requests = vec![0u8; 2];
+        server.read_exact(&mut requests).await.unwrap();
+        assert_eq!(requests, [0xA5, RPLIDAR_CMD_GET_DEVICE_INFO]);
+    }
+}
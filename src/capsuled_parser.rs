@@ -1,8 +1,8 @@
 use super::CachedPrevCapsule;
 use super::answers::*;
 
-const ANGLE_360_Q8: u32 = (360u32 << 8);
-const ANGLE_360_Q16: u32 = (360u32 << 16);
+const ANGLE_360_Q8: u32 = 360u32 << 8;
+const ANGLE_360_Q16: u32 = 360u32 << 16;
 
 fn get_start_angle_q8(nodes: &RplidarResponseCapsuleMeasurementNodes) -> u32 {
     return ((nodes.start_angle_sync_q6 & 0x7fffu16) as u32) << 2;
@@ -46,13 +46,13 @@ pub fn check_sync(cur_angle_q16: u32, angle_inc_q16: u32) -> bool {
     ((cur_angle_q16 + angle_inc_q16) % ANGLE_360_Q16) < angle_inc_q16
 }
 
-fn angle_q6_to_angle_z_q14(angle_q6: u32) -> u16 {
+pub fn angle_q6_to_angle_z_q14(angle_q6: u32) -> u16 {
     ((angle_q6 << 8) / 90) as u16
 }
 
 pub fn generate_quality(dist_q2: u32) -> u8 {
     if dist_q2 != 0 {
-        (0x2fu8 << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT)
+        0x2fu8 << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT
     } else {
         0u8
     }
@@ -68,7 +68,7 @@ pub fn to_hq(node: &ParsedNode, cur_angle_raw_q16: u32, angle_inc_q16: u32) -> R
 
     RplidarResponseMeasurementNodeHq {
         angle_z_q14: angle_q6_to_angle_z_q14(angle_q6),
-        dist_mm_q2: node.dist_q2 as u32,
+        dist_mm_q2: node.dist_q2,
         quality: generate_quality(node.dist_q2),
         flag: generate_flag(sync)
     }
@@ -79,7 +79,7 @@ pub fn parse_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseCap
         let mut output_nodes : Vec<RplidarResponseMeasurementNodeHq> = Vec::with_capacity(32);
 
         let cur_start_angle_q8 = get_start_angle_q8(&nodes);
-        let prev_start_angle_q8 = get_start_angle_q8(&prev_capsule);
+        let prev_start_angle_q8 = get_start_angle_q8(prev_capsule);
 
         let diff_angle_q8 = angle_diff_q8(prev_start_angle_q8, cur_start_angle_q8);
 
@@ -90,7 +90,7 @@ pub fn parse_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseCap
             let parsed_nodes = parse_cabin(cabin);
 
             for node in parsed_nodes.iter() {
-                output_nodes.push(to_hq(&node, cur_angle_raw_q16, angle_inc_q16));
+                output_nodes.push(to_hq(node, cur_angle_raw_q16, angle_inc_q16));
                 cur_angle_raw_q16 += angle_inc_q16;
             }
         }
@@ -100,3 +100,44 @@ pub fn parse_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseCap
         return (Vec::new(), CachedPrevCapsule::Capsuled(nodes));
     }
 }
+
+fn get_dense_start_angle_q8(nodes: &RplidarResponseDenseCapsuleMeasurementNodes) -> u32 {
+    return ((nodes.start_angle_sync_q6 & 0x7fffu16) as u32) << 2;
+}
+
+fn dense_to_hq(dist_mm: u16, cur_angle_raw_q16: u32, angle_inc_q16: u32) -> RplidarResponseMeasurementNodeHq {
+    let dist_q2 = (dist_mm as u32) << 2;
+    let sync = check_sync(cur_angle_raw_q16, angle_inc_q16);
+
+    RplidarResponseMeasurementNodeHq {
+        angle_z_q14: angle_q6_to_angle_z_q14(cur_angle_raw_q16 >> 10),
+        dist_mm_q2: dist_q2,
+        quality: generate_quality(dist_q2),
+        flag: generate_flag(sync)
+    }
+}
+
+pub fn parse_dense_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseDenseCapsuleMeasurementNodes) -> (Vec<RplidarResponseMeasurementNodeHq>, CachedPrevCapsule) {
+    if let CachedPrevCapsule::DenseCapsuled(prev_capsule) = cached_prev {
+        let cabins = { prev_capsule.dense_cabins };
+        let mut output_nodes : Vec<RplidarResponseMeasurementNodeHq> = Vec::with_capacity(cabins.len());
+
+        let cur_start_angle_q8 = get_dense_start_angle_q8(&nodes);
+        let prev_start_angle_q8 = get_dense_start_angle_q8(prev_capsule);
+
+        let diff_angle_q8 = angle_diff_q8(prev_start_angle_q8, cur_start_angle_q8);
+
+        // distances are not compressed in dense capsules, so the cabins are evenly spread without angle offsets
+        let angle_inc_q16 = (diff_angle_q8 << 8) / (cabins.len() as u32);
+        let mut cur_angle_raw_q16 = prev_start_angle_q8 << 8;
+
+        for dist_mm in cabins.iter() {
+            output_nodes.push(dense_to_hq(*dist_mm, cur_angle_raw_q16, angle_inc_q16));
+            cur_angle_raw_q16 += angle_inc_q16;
+        }
+
+        return (output_nodes, CachedPrevCapsule::DenseCapsuled(nodes));
+    } else {
+        return (Vec::new(), CachedPrevCapsule::DenseCapsuled(nodes));
+    }
+}
@@ -1,4 +1,7 @@
+use std::fmt::Debug;
+
 /// checksum calculation for RPLIDAR protocol
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Checksum {
     current: u8
 }
@@ -9,12 +12,17 @@ impl Checksum {
         Checksum { current: 0 }
     }
 
-    /*
     /// reset the calculation of `Checksum`
     pub fn reset(&mut self) {
         self.current = 0;
     }
-    */
+
+    /// check if the checksum of `data` is `expected`
+    pub fn verify(data: &[u8], expected: u8) -> bool {
+        let mut checksum = Checksum::new();
+        checksum.push_slice(data);
+        return checksum.checksum() == expected;
+    }
 
     /// push data into the `Checksum`
     pub fn push(&mut self, data: u8) {
@@ -23,8 +31,8 @@ impl Checksum {
 
     /// push slice into the `Checksum`
     pub fn push_slice(&mut self, data: &[u8]) {
-        for i in 0..data.len() {
-            self.current ^= data[i];
+        for byte in data {
+            self.current ^= byte;
         }
     }
 
@@ -33,3 +41,46 @@ impl Checksum {
         self.current
     }
 }
+
+/// Verifier of the checksum of capsuled measurements
+///
+/// Implement it for modified firmwares with different integrity schemes
+pub trait ChecksumVerifier: Debug {
+    /// verify `data` (the capsule after the sync and checksum bytes) against the received checksum
+    fn verify(&self, data: &[u8], received: u8) -> bool;
+}
+
+/// The XOR checksum used by RPLIDAR firmwares (default)
+#[derive(Debug, Copy, Clone, Default)]
+pub struct XorChecksumVerifier;
+
+impl ChecksumVerifier for XorChecksumVerifier {
+    fn verify(&self, data: &[u8], received: u8) -> bool {
+        return Checksum::verify(data, received);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_for_reuse() {
+        let mut checksum = Checksum::new();
+        checksum.push_slice(&[0x12, 0x34]);
+        assert_eq!(checksum.checksum(), 0x26);
+
+        checksum.reset();
+        assert_eq!(checksum.checksum(), 0);
+
+        checksum.push(0x56);
+        assert_eq!(checksum.checksum(), 0x56);
+    }
+
+    #[test]
+    fn verify_data() {
+        assert!(Checksum::verify(&[], 0));
+        assert!(Checksum::verify(&[0x12, 0x34, 0x56], 0x70));
+        assert!(!Checksum::verify(&[0x12, 0x34, 0x56], 0x71));
+    }
+}
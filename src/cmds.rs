@@ -21,15 +21,23 @@ pub const RPLIDAR_CMD_GET_DEVICE_INFO : u8 = 0x50;
 /// Get device health info
 pub const RPLIDAR_CMD_GET_DEVICE_HEALTH : u8 = 0x52;
 
-// pub const RPLIDAR_CMD_GET_SAMPLERATE : u8 = 0x59; //added in fw 1.17
+/// Get sample durations of standard and express scan
+pub const RPLIDAR_CMD_GET_SAMPLERATE : u8 = 0x59; //added in fw 1.17
 
-// pub const RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL : u8 = 0xA8;
+/// Set motor speed in RPM
+pub const RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL : u8 = 0xA8;
 
 // Commands with payload and have response
 
 /// Start express scan (both legacy and extended mode)
 pub const RPLIDAR_CMD_EXPRESS_SCAN : u8 = 0x82; //added in fw 1.17;
 
+/// Work flag of express scan to boost the sample rate
+pub const RPLIDAR_EXPRESS_SCAN_FLAG_BOOST : u16 = 0x0001;
+
+/// Work flag of express scan to reject the interference of sunlight
+pub const RPLIDAR_EXPRESS_SCAN_FLAG_SUNLIGHT_REJECTION : u16 = 0x0002;
+
 /// Options to start scan
 #[repr(packed)]
 #[repr(C)]
@@ -79,3 +87,9 @@ pub const RPLIDAR_CONF_SCAN_MODE_TYPICAL: u32 = 0x0000007C;
 
 /// LIDAR config entry for the name of specific scan mode
 pub const RPLIDAR_CONF_SCAN_MODE_NAME: u32 = 0x0000007F;
+
+/// LIDAR config entry for min motor speed in RPM
+pub const RPLIDAR_CONF_MIN_ROT_FREQ: u32 = 0x00000004;
+
+/// LIDAR config entry for max motor speed in RPM
+pub const RPLIDAR_CONF_MAX_ROT_FREQ: u32 = 0x00000005;
@@ -1,5 +1,8 @@
 use std::time::Duration;
 use super::answers::*;
+use super::prelude::DecodeError;
+use std::fmt;
+use rpos_drv::Message;
 
 /// Default timeout when communicating with RPLIDAR
 pub const RPLIDAR_DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
@@ -7,12 +10,90 @@ pub const RPLIDAR_DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
 /// Default cache depth of scan points
 pub const RPLIDAR_DEFAULT_CACHE_DEPTH: usize = 8192;
 
+/// Default min count of points in a scan grabbed by `grab_scan` (every rotation boundary is accepted)
+pub const RPLIDAR_DEFAULT_MIN_POINTS_PER_SCAN: usize = 1;
+
+/// Default attempts of each lidar config query
+pub const RPLIDAR_DEFAULT_LIDAR_CONF_ATTEMPTS: usize = 1;
+
+/// Backoff before the first retry of a timed out lidar config query, doubled on each retry
+pub const RPLIDAR_LIDAR_CONF_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
 /// Default motor PWM
 pub const RPLIDAR_DEFAULT_MOTOR_PWM: u16 = 600;
 
+/// Timeout of waiting for the acknowledgement of `SET_MOTOR_PWM`
+pub const RPLIDAR_MOTOR_PWM_ACK_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Default motor speed (in RPM) of LIDARs controlled by speed instead of PWM
+pub const RPLIDAR_DEFAULT_MOTOR_RPM: u16 = 600;
+
+/// Rotation speed range (in Hz) of LIDARs without rotation speed configs (firmware before 1.24)
+pub const RPLIDAR_DEFAULT_ROTATION_SPEED_RANGE_HZ: (f32, f32) = (5f32, 15f32);
+
+/// Timeout of each command when probing capabilities of the LIDAR
+pub const RPLIDAR_CAPABILITY_PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Timeout of `GET_DEVICE_INFO` at each candidate baud rate when detecting the baud rate
+#[cfg(feature = "serialport")]
+pub const RPLIDAR_AUTOBAUD_PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Max bytes of the last scan message retained for debugging (larger than all capsules)
+pub const RPLIDAR_LAST_RAW_MESSAGE_MAX_SIZE: usize = 256;
+
+/// Express scan is added in firmware 1.17
+pub const RPLIDAR_EXPRESS_SCAN_START_VERSION: u16 = (1 << 8) | 17;
+
+/// Rotation periods to wait for a scan in `grab_scan_adaptive`
+pub const RPLIDAR_ADAPTIVE_TIMEOUT_ROTATIONS: u32 = 3;
+
+/// Min timeout of `grab_scan_adaptive`, so a mismeasured rotation period never gives a timeout too short to grab a scan
+pub const RPLIDAR_ADAPTIVE_TIMEOUT_MIN: Duration = Duration::from_millis(200);
+
+/// Rotation frequency assumed when it's not measured yet
+pub const RPLIDAR_NOMINAL_ROTATION_FREQUENCY_HZ: f32 = 10f32;
+
+/// Rotations with less points than this ratio of the points expected at the max rotation speed are
+/// skipped by `measure_scan_frequency`, as they have dropped data
+pub const RPLIDAR_MIN_PLAUSIBLE_POINTS_RATIO: f32 = 0.5f32;
+
+/// Scans recorded by `record_to` between flushes of the writer
+pub const RPLIDAR_RECORD_FLUSH_INTERVAL: usize = 16;
+
+/// Least increment of the start of frames grabbed by `grab_scan_monotonic`
+pub const RPLIDAR_MIN_FRAME_START_INCREMENT: Duration = Duration::from_micros(1);
+
+/// Max count of rotation periods kept to estimate the jitter
+pub const RPLIDAR_SCAN_PERIOD_HISTORY: usize = 64;
+
+/// Max count of fingerprints of recent scans kept to detect frozen devices
+pub const RPLIDAR_SCAN_FINGERPRINT_HISTORY: usize = 64;
+
+/// Bytes read from the transport at once when discarding streamed data
+pub const RPLIDAR_FLUSH_READ_CHUNK_SIZE: usize = 256;
+
+/// Handler installed by `set_decode_error_handler`
+pub struct DecodeErrorHandler(pub Box<dyn FnMut(&DecodeError)>);
+
+impl fmt::Debug for DecodeErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "DecodeErrorHandler");
+    }
+}
+
+/// Observer installed by `set_raw_message_observer`
+pub struct RawMessageObserver(pub Box<dyn FnMut(&Message)>);
+
+impl fmt::Debug for RawMessageObserver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "RawMessageObserver");
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CachedPrevCapsule {
     None,
     Capsuled(RplidarResponseCapsuleMeasurementNodes),
     UltraCapsuled(RplidarResponseUltraCapsuleMeasurementNodes),
+    DenseCapsuled(RplidarResponseDenseCapsuleMeasurementNodes),
 }
@@ -2,38 +2,59 @@
 //!
 //! `rplidar_drv` is driver for Slamtec Rplidar series
 
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 extern crate byteorder;
 extern crate crc;
 extern crate rpos_drv;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serialport")]
+extern crate serialport;
+#[cfg(feature = "async")]
+extern crate tokio;
 
 mod internals;
+mod models;
 mod answers;
 mod capsuled_parser;
 mod ultra_capsuled_parser;
+mod response_parser;
 mod checksum;
 mod cmds;
+mod motor_control;
 mod errors;
 mod prelude;
 mod protocol;
+#[cfg(feature = "serialport")]
+mod serial;
+#[cfg(feature = "async")]
+mod async_device;
 pub mod utils;
 
 pub use self::prelude::*;
 pub use self::errors::*;
+pub use self::models::*;
 
-pub use self::answers::RplidarResponseDeviceInfo;
+pub use self::answers::{RplidarResponseDeviceInfo, RplidarResponseSampleRate};
 
 use self::answers::*;
 use self::internals::*;
-use self::capsuled_parser::parse_capsuled;
+use self::capsuled_parser::{parse_capsuled, parse_dense_capsuled};
 use self::ultra_capsuled_parser::parse_ultra_capsuled;
-use self::checksum::Checksum;
+use self::response_parser::FromResponse;
+pub use self::checksum::{Checksum, ChecksumVerifier, XorChecksumVerifier};
+pub use self::motor_control::MotorControl;
 use self::cmds::*;
 pub use self::protocol::RplidarHostProtocol;
+#[cfg(feature = "async")]
+pub use self::async_device::AsyncRplidarDevice;
+#[cfg(feature = "serialport")]
+pub use self::serial::{open_port_autobaud, RplidarRegistry, SerialDtrMotorControl, RPLIDAR_AUTOBAUD_CANDIDATES};
 use byteorder::{ByteOrder, LittleEndian};
-use rpos_drv::{Channel, Message, Result};
+use rpos_drv::{Channel, Message};
 use std::collections::VecDeque;
-use std::io::{Read, Write};
-use std::mem::transmute_copy;
+use std::io::{self, Read, Write};
 use std::time::{ Instant, Duration };
 use crc::{ crc32 };
 
@@ -45,19 +66,62 @@ pub struct RplidarDevice<T: ?Sized> {
     channel: Channel<RplidarHostProtocol, T>,
     cached_measurement_nodes: VecDeque<ScanPoint>,
     cached_prev_capsule: CachedPrevCapsule,
+    standby: bool,
+    recent_scan_fingerprints: VecDeque<u64>,
+    active_scan_mode: Option<ScanMode>,
+    active_scan_options: Option<ScanOptions>,
+    active_scan_path: Option<ScanPath>,
+    acc_board_flag: Option<AccBoardFlag>,
+    check_motor_pwm_support: bool,
+    capabilities: Option<Capabilities>,
+    scan_sequence: u64,
+    last_sync_decoded_at: Option<Instant>,
+    samples_since_sync: u64,
+    last_frame_start: Option<Instant>,
+    rotation_period: Option<Duration>,
+    recent_scan_periods: VecDeque<Duration>,
+    min_points_per_scan: usize,
+    unexpected_answer_policy: UnexpectedAnswerPolicy,
+    corrupt_node_policy: CorruptNodePolicy,
+    corrupt_node_count: u64,
+    unexpected_answer_count: u64,
+    lidar_conf_attempts: usize,
+    transport_kind: TransportKind,
+    frame_angle_offset_deg: f32,
+    frame_angle_offset_z_q14: u16,
+    range_bias_mm: f32,
+    range_bias_q2: i64,
+    rotation_speed_range_hz: Option<(f32, f32)>,
+    device_info: Option<RplidarResponseDeviceInfo>,
+    checksum_verifier: Box<dyn ChecksumVerifier>,
+    motor_control: Option<Box<dyn MotorControl>>,
+    decode_error_handler: Option<DecodeErrorHandler>,
+    raw_message_observer: Option<RawMessageObserver>,
+    heading_prev_scan: Option<Vec<ScanPoint>>,
+    heading_deg: f32,
+    angular_decimation: u16,
+    angular_decimation_phase: u16,
+    rotation_count: u64,
+    warmup_syncs_remaining: usize,
+    protocol_version_hint: ProtocolVersion,
+    last_raw_message: Option<Vec<u8>>,
+    delta_stream_ranges: Vec<f32>,
+    decoded_point_count: u64,
+    max_cached_points: Option<usize>,
+    dropped_point_count: u64,
+    capsule_segments: VecDeque<(u64, f32)>,
+    sync_mode: SyncMode,
+    distance_unit: DistanceUnit,
+    sync_bit_seen: bool,
+    last_decoded_angle_z_q14: Option<u16>,
+    session_stats: SessionStats,
+    discarded_points: Option<usize>,
 }
 
 macro_rules! parse_resp_data {
-    ($x:expr, $t:ty) => {{
-        const SIZE: usize = std::mem::size_of::<$t>();
-        if $x.len() != SIZE {
-            Err(Error::from(RposError::OperationFail { description: "answer type mismatch".to_owned() }))
-        } else {
-            let mut slice = [0u8; SIZE];
-            slice.clone_from_slice(&$x[..]);
-            Ok(unsafe { transmute_copy::<[u8; SIZE], $t>(&slice) })
-        }
-    }};
+    ($x:expr, $t:ty) => {
+        <$t as FromResponse>::parse(&$x[..])
+    };
 }
 
 macro_rules! parse_resp {
@@ -68,8 +132,10 @@ macro_rules! parse_resp {
 
 macro_rules! handle_resp {
     ($ans:expr, $x:expr, $t:ty) => {
-        if $x.cmd != $ans {
-            Err(RposError::OperationFail { description: "answer type mismatch".to_owned() }.into())
+        if $x.data.is_empty() {
+            Err(empty_message_error())
+        } else if $x.cmd != $ans {
+            Err(answer_mismatch_error($x.cmd))
         } else {
             parse_resp!($x, $t)
         }
@@ -100,10 +166,65 @@ where
     /// let rplidar_device = RplidarDevice::new(channel);
     /// ```
     pub fn new(channel: Channel<RplidarHostProtocol, T>) -> RplidarDevice<T> {
+        RplidarDevice::<T>::new_with_cache_depth(channel, RPLIDAR_DEFAULT_CACHE_DEPTH)
+    }
+
+    /// Construct a new RplidarDevice with channel, and room for `depth` cached scan points (see `set_cache_depth`)
+    pub fn new_with_cache_depth(channel: Channel<RplidarHostProtocol, T>, depth: usize) -> RplidarDevice<T> {
         RplidarDevice {
             channel: channel,
-            cached_measurement_nodes: VecDeque::with_capacity(RPLIDAR_DEFAULT_CACHE_DEPTH),
+            cached_measurement_nodes: VecDeque::with_capacity(depth),
             cached_prev_capsule: CachedPrevCapsule::None,
+            standby: false,
+            recent_scan_fingerprints: VecDeque::with_capacity(RPLIDAR_SCAN_FINGERPRINT_HISTORY),
+            active_scan_mode: None,
+            active_scan_options: None,
+            active_scan_path: None,
+            acc_board_flag: None,
+            check_motor_pwm_support: false,
+            capabilities: None,
+            scan_sequence: 0,
+            last_sync_decoded_at: None,
+            samples_since_sync: 0,
+            last_frame_start: None,
+            rotation_period: None,
+            recent_scan_periods: VecDeque::with_capacity(RPLIDAR_SCAN_PERIOD_HISTORY),
+            min_points_per_scan: RPLIDAR_DEFAULT_MIN_POINTS_PER_SCAN,
+            unexpected_answer_policy: UnexpectedAnswerPolicy::Error,
+            corrupt_node_policy: CorruptNodePolicy::Error,
+            corrupt_node_count: 0,
+            unexpected_answer_count: 0,
+            lidar_conf_attempts: RPLIDAR_DEFAULT_LIDAR_CONF_ATTEMPTS,
+            transport_kind: TransportKind::Serial,
+            frame_angle_offset_deg: 0f32,
+            frame_angle_offset_z_q14: 0,
+            range_bias_mm: 0f32,
+            range_bias_q2: 0,
+            rotation_speed_range_hz: None,
+            device_info: None,
+            checksum_verifier: Box::new(XorChecksumVerifier),
+            motor_control: None,
+            decode_error_handler: None,
+            raw_message_observer: None,
+            heading_prev_scan: None,
+            heading_deg: 0f32,
+            angular_decimation: 1,
+            angular_decimation_phase: 0,
+            rotation_count: 0,
+            warmup_syncs_remaining: 0,
+            protocol_version_hint: ProtocolVersion::Extended,
+            last_raw_message: None,
+            delta_stream_ranges: Vec::new(),
+            decoded_point_count: 0,
+            max_cached_points: None,
+            dropped_point_count: 0,
+            capsule_segments: VecDeque::new(),
+            sync_mode: SyncMode::StandardBit,
+            distance_unit: DistanceUnit::Millimeters,
+            sync_bit_seen: false,
+            last_decoded_angle_z_q14: None,
+            session_stats: SessionStats::default(),
+            discarded_points: None,
         }
     }
 
@@ -118,6 +239,42 @@ where
         RplidarDevice::<T>::new(rpos_drv::Channel::new(RplidarHostProtocol::new(), stream))
     }
 
+    /// Limit the count of cached scan points, the oldest points are dropped when the limit is reached
+    ///
+    /// The cache is unbounded by default (`None`), so it grows if scan points are not consumed in time.
+    /// Dropped points are counted by `dropped_point_count`
+    pub fn set_max_cached_points(&mut self, max_cached_points: Option<usize>) {
+        self.max_cached_points = max_cached_points;
+    }
+
+    /// Get the limit of cached scan points set by `set_max_cached_points`
+    pub fn max_cached_points(&self) -> Option<usize> {
+        return self.max_cached_points;
+    }
+
+    /// Get count of scan points dropped since the cache reached `max_cached_points`
+    pub fn dropped_point_count(&self) -> u64 {
+        return self.dropped_point_count;
+    }
+
+    /// Construct a new RplidarDevice with stream, and room for `depth` cached scan points (see `set_cache_depth`)
+    pub fn with_stream_and_cache_depth(stream: Box<T>, depth: usize) -> RplidarDevice<T> {
+        RplidarDevice::<T>::new_with_cache_depth(rpos_drv::Channel::new(RplidarHostProtocol::new(), stream), depth)
+    }
+
+    /// Reserve room for `depth` cached scan points, or release the room beyond it
+    ///
+    /// The cache is allocated up front to avoid allocations while scanning (8192 points by default, about 64KB).
+    /// Smaller caches save memory on constrained targets, and larger ones buffer several rotations of fast LIDARs.
+    /// The cache still grows beyond the depth if the points are not consumed in time, at the cost of allocations
+    pub fn set_cache_depth(&mut self, depth: usize) {
+        let cached = self.cached_measurement_nodes.len();
+        if depth > cached {
+            self.cached_measurement_nodes.reserve(depth - cached);
+        }
+        self.cached_measurement_nodes.shrink_to(std::cmp::max(depth, cached));
+    }
+
     /// get device info of the RPLIDAR
     pub fn get_device_info(&mut self) -> Result<RplidarResponseDeviceInfo> {
         self.get_device_info_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
@@ -128,22 +285,131 @@ where
         &mut self,
         timeout: Duration,
     ) -> Result<RplidarResponseDeviceInfo> {
+        self.check_not_in_standby()?;
+
         if let Some(msg) = self
             .channel
             .invoke(&Message::new(RPLIDAR_CMD_GET_DEVICE_INFO), timeout)?
         {
-            return handle_resp!(RPLIDAR_ANS_TYPE_DEVINFO, msg, RplidarResponseDeviceInfo);
+            let device_info = handle_resp!(RPLIDAR_ANS_TYPE_DEVINFO, msg, RplidarResponseDeviceInfo)?;
+            self.device_info = Some(device_info);
+            return Ok(device_info);
+        }
+
+        return Err(RposError::OperationTimeout.into());
+    }
+
+    /// measure the round trip time of `GET_DEVICE_INFO`, to characterize the responsiveness of the link
+    pub fn ping_latency(&mut self) -> Result<Duration> {
+        self.ping_latency_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// measure the round trip time of `GET_DEVICE_INFO` with timeout
+    pub fn ping_latency_with_timeout(&mut self, timeout: Duration) -> Result<Duration> {
+        let start = Instant::now();
+        self.get_device_info_with_timeout(timeout)?;
+        return Ok(start.elapsed());
+    }
+
+    /// Send arbitrary command to the RPLIDAR and return the raw answer type and payload
+    ///
+    /// This is mainly used to diagnose what the device actually responds with
+    pub fn invoke_and_return_ans_type(
+        &mut self,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Result<(u8, Vec<u8>)> {
+        self.check_not_in_standby()?;
+
+        if let Some(resp) = self.channel.invoke(msg, timeout)? {
+            return Ok((resp.cmd, resp.data));
         }
 
         return Err(RposError::OperationTimeout.into());
     }
 
+    /// Get bytes per second received from the transport recently
+    ///
+    /// Compare it against the data rate of current scan mode to find out whether the link is the bottleneck
+    pub fn measured_throughput_bps(&self) -> Option<f32> {
+        return self.channel.measured_throughput_bps();
+    }
+
+    /// Get bytes received from the transport but not decoded yet
+    ///
+    /// A growing value means the consumer is falling behind the data stream.
+    /// Bytes queued in the OS driver are not included (see `pending_serial_bytes` with `serialport` feature)
+    pub fn pending_bytes(&self) -> usize {
+        return self.channel.pending_bytes();
+    }
+
+    /// Get the max bytes buffered for decoding since the device is created or `reset_decode_stats` is called
+    ///
+    /// A value close to `decode_buffer_capacity` means bursts filled the buffer, read more often to keep up
+    pub fn decode_buffer_high_water(&self) -> usize {
+        return self.channel.read_buffer_high_water();
+    }
+
+    /// Get the capacity of the buffer of bytes to decode
+    pub fn decode_buffer_capacity(&self) -> usize {
+        return self.channel.read_buffer_capacity();
+    }
+
+    /// Restart tracking of `decode_buffer_high_water` from the bytes currently buffered
+    pub fn reset_decode_stats(&mut self) {
+        self.channel.reset_read_buffer_high_water();
+    }
+
+    /// Borrow the underlying transport (e.g. the serial port)
+    pub fn transport(&self) -> &T {
+        return self.channel.stream();
+    }
+
+    /// Mutably borrow the underlying transport, for low level needs like changing serial parameters at runtime
+    ///
+    /// Reading from or writing to the transport while scanning will corrupt the data stream
+    /// and lose scan points, stop the scan first where possible
+    pub fn transport_mut(&mut self) -> &mut T {
+        return self.channel.stream_mut();
+    }
+
     /// Stop lidar
     pub fn stop(&mut self) -> Result<()> {
         self.channel.write(&Message::new(RPLIDAR_CMD_STOP))?;
+        self.cached_prev_capsule = CachedPrevCapsule::None;
         return Ok(());
     }
 
+    /// Stop lidar, and discard the scan data still streamed for up to `drain`, returns count of discarded bytes
+    ///
+    /// The LIDAR keeps sending buffered data for a moment after stopping, which would otherwise be decoded
+    /// by the next `start_scan`. Draining ends early once the transport has no more data
+    pub fn stop_and_flush(&mut self, drain: Duration) -> Result<usize> {
+        self.stop()?;
+        self.cached_measurement_nodes.clear();
+
+        let deadline = Instant::now() + drain;
+        let mut discarded = self.channel.pending_bytes();
+        let mut buf = [0u8; RPLIDAR_FLUSH_READ_CHUNK_SIZE];
+
+        while Instant::now() < deadline {
+            let read = match self.channel.stream_mut().read(&mut buf) {
+                Ok(read) => read,
+                Err(ref err) if err.kind() == io::ErrorKind::TimedOut || err.kind() == io::ErrorKind::WouldBlock => 0,
+                Err(err) => return Err(err.into()),
+            };
+
+            if read == 0 {
+                break;
+            }
+
+            discarded += read;
+        }
+
+        self.reset_scan_state();
+        return Ok(discarded);
+    }
+
     /// Reset RPLIDAR core
     pub fn core_reset(&mut self) -> Result<()> {
         self.channel.write(&Message::new(RPLIDAR_CMD_RESET))?;
@@ -151,26 +417,202 @@ where
     }
 
     /// Set motor PWM (via accessory board)
+    ///
+    /// The acknowledgement sent by some models is read and discarded, if the model is known from `get_device_info`.
+    /// LIDARs without motor control ignore the command silently, unless `set_check_motor_pwm_support` is enabled
     pub fn set_motor_pwm(&mut self, pwm: u16) -> Result<()> {
+        self.check_not_in_standby()?;
+
+        if self.check_motor_pwm_support {
+            let acc_board_flag = match self.acc_board_flag {
+                Some(acc_board_flag) => acc_board_flag,
+                None => self.get_acc_board_flag()?,
+            };
+
+            if !acc_board_flag.motor_ctrl {
+                return Err(RposError::OperationNotSupport.into());
+            }
+        }
+
         let mut payload = [0; 2];
         LittleEndian::write_u16(&mut payload, pwm);
 
         self.channel
             .write(&Message::with_data(RPLIDAR_CMD_SET_MOTOR_PWM, &payload))?;
 
+        // the ack left unread would be taken as the answer of the next command
+        let acks_motor_pwm = self.device_info.map(|info| info.model().acks_motor_pwm()).unwrap_or(false);
+        if acks_motor_pwm {
+            if let Err(err) = self.channel.read_until(RPLIDAR_MOTOR_PWM_ACK_TIMEOUT) {
+                if !is_timeout(&err) {
+                    return Err(err);
+                }
+                self.channel.reset();
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Set the verifier of capsuled measurement checksums (XOR checksum by default)
+    ///
+    /// This is only useful for modified firmwares with different integrity schemes
+    pub fn set_checksum_verifier(&mut self, verifier: Box<dyn ChecksumVerifier>) {
+        self.checksum_verifier = verifier;
+    }
+
+    /// Set the control of the motor through the DTR line, used by `start_motor` and `stop_motor`
+    /// when the LIDAR doesn't support PWM motor control (e.g. A1)
+    pub fn set_motor_control(&mut self, motor_control: Box<dyn MotorControl>) {
+        self.motor_control = Some(motor_control);
+    }
+
+    /// Set kind of the transport, which decides how the motor is controlled
+    pub fn set_transport_kind(&mut self, transport_kind: TransportKind) {
+        self.transport_kind = transport_kind;
+    }
+
+    /// Get kind of the transport
+    pub fn transport_kind(&self) -> TransportKind {
+        return self.transport_kind;
+    }
+
+    /// Set motor speed in RPM
+    ///
+    /// The speed is set with the motor speed command (`HQ_MOTOR_SPEED_CTRL`), like the Slamtec SDK does for
+    /// LIDARs with speed controlled motors, which is also the only way to control the motor on Ethernet transport
+    ///
+    /// Nonzero speeds are clamped into the range got by `get_rotation_speed_range`, if it was called before
+    pub fn set_motor_speed_rpm(&mut self, rpm: u16) -> Result<()> {
+        self.check_not_in_standby()?;
+
+        let rpm = match self.rotation_speed_range_hz {
+            Some((min_hz, max_hz)) if rpm != 0 => {
+                ((rpm as f32).clamp(min_hz * 60f32, max_hz * 60f32).round()) as u16
+            }
+            _ => rpm,
+        };
+
+        let mut payload = [0; 2];
+        LittleEndian::write_u16(&mut payload, rpm);
+
+        self.channel
+            .write(&Message::with_data(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &payload))?;
+
         return Ok(());
     }
 
+    /// Get the range `(min_hz, max_hz)` of the rotation speed supported by the LIDAR
+    ///
+    /// Firmwares before 1.24 don't report the range, and the range of A-series (5 to 15 Hz) is returned.
+    /// The range is kept to clamp the speed set by `set_motor_speed_rpm`
+    pub fn get_rotation_speed_range(&mut self) -> Result<(f32, f32)> {
+        self.get_rotation_speed_range_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// Get the range `(min_hz, max_hz)` of the rotation speed supported by the LIDAR with timeout
+    pub fn get_rotation_speed_range_with_timeout(&mut self, timeout: Duration) -> Result<(f32, f32)> {
+        let device_info = self.get_device_info_with_timeout(timeout)?;
+
+        let range = if device_info.firmware_version < RPLIDAR_GET_LIDAR_CONF_START_VERSION {
+            RPLIDAR_DEFAULT_ROTATION_SPEED_RANGE_HZ
+        } else {
+            let min_data = self.get_lidar_conf_with_timeout(RPLIDAR_CONF_MIN_ROT_FREQ, timeout)?;
+            let max_data = self.get_lidar_conf_with_timeout(RPLIDAR_CONF_MAX_ROT_FREQ, timeout)?;
+            (
+                (parse_resp_data!(min_data, u16)? as f32) / 60f32,
+                (parse_resp_data!(max_data, u16)? as f32) / 60f32,
+            )
+        };
+
+        self.rotation_speed_range_hz = Some(range);
+        return Ok(range);
+    }
+
     /// Stop motor
+    ///
+    /// On LIDARs with PWM motor control, this is the same as `set_motor_pwm(0)`.
+    /// The DTR line is set instead if a motor control is installed and PWM is not supported
     pub fn stop_motor(&mut self) -> Result<()> {
+        if self.transport_kind == TransportKind::Ethernet {
+            return self.set_motor_speed_rpm(0);
+        }
+
+        if self.uses_dtr_motor_control() {
+            return self.set_motor_dtr(true);
+        }
+
         self.set_motor_pwm(0)
     }
 
     /// Start motor
+    ///
+    /// The DTR line is cleared instead of setting PWM if a motor control is installed and PWM is not supported
     pub fn start_motor(&mut self) -> Result<()> {
+        if self.transport_kind == TransportKind::Ethernet {
+            return self.set_motor_speed_rpm(RPLIDAR_DEFAULT_MOTOR_RPM);
+        }
+
+        if self.uses_dtr_motor_control() {
+            return self.set_motor_dtr(false);
+        }
+
         self.set_motor_pwm(RPLIDAR_DEFAULT_MOTOR_PWM)
     }
 
+    /// whether the motor should be controlled by DTR instead of PWM
+    fn uses_dtr_motor_control(&mut self) -> bool {
+        return self.motor_control.is_some() && !self.supports_pwm_motor_control();
+    }
+
+    /// set DTR with the installed motor control
+    fn set_motor_dtr(&mut self, on: bool) -> Result<()> {
+        self.check_not_in_standby()?;
+
+        match self.motor_control.as_mut() {
+            Some(motor_control) => return motor_control.set_dtr(on),
+            None => return Err(RposError::OperationNotSupport.into()),
+        }
+    }
+
+    /// Put the LIDAR into standby (stop measurement and motor)
+    ///
+    /// Commands other than `stop`, `core_reset` and `wake` will be rejected until `wake` is called
+    pub fn standby(&mut self) -> Result<()> {
+        if self.standby {
+            return Ok(());
+        }
+
+        self.stop()?;
+        self.stop_motor()?;
+        self.standby = true;
+        return Ok(());
+    }
+
+    /// Wake the LIDAR up from standby (spin up the motor and reset the session)
+    pub fn wake(&mut self) -> Result<()> {
+        self.standby = false;
+        self.channel.reset();
+        self.cached_measurement_nodes.clear();
+        self.cached_prev_capsule = CachedPrevCapsule::None;
+        self.start_motor()?;
+        return Ok(());
+    }
+
+    /// Check if the LIDAR is in standby
+    pub fn is_in_standby(&self) -> bool {
+        return self.standby;
+    }
+
+    /// Return error if the LIDAR is in standby
+    fn check_not_in_standby(&self) -> Result<()> {
+        if self.standby {
+            return Err(RposError::OperationFail { description: "device is in standby".to_owned() }.into());
+        }
+
+        return Ok(());
+    }
+
     /*
     /// Get LIDAR config
     fn get_lidar_conf(&mut self, config_type: u32) -> Result<Vec<u8>> {
@@ -192,30 +634,61 @@ where
         self.get_lidar_conf_with_param_and_timeout(config_type, &[], timeout)
     }
 
-    /// get lidar config with parameter and timeout
+    /// set the attempts of each lidar config query (at least 1)
+    ///
+    /// Timed out queries are retried with backoff, so transient timeouts on marginal links
+    /// will not fail the whole scan mode enumeration
+    pub fn set_lidar_conf_attempts(&mut self, attempts: usize) {
+        self.lidar_conf_attempts = std::cmp::max(1, attempts);
+    }
+
+    /// get the attempts of each lidar config query
+    pub fn lidar_conf_attempts(&self) -> usize {
+        return self.lidar_conf_attempts;
+    }
+
+    /// get lidar config with parameter and timeout, and retry if timed out
     fn get_lidar_conf_with_param_and_timeout(
         &mut self,
         config_type: u32,
         param: &[u8],
         timeout: Duration,
     ) -> Result<Vec<u8>> {
-        let mut msg = Message::with_data(RPLIDAR_CMD_GET_LIDAR_CONF, &[0; 4]);
+        let mut backoff = RPLIDAR_LIDAR_CONF_RETRY_BACKOFF;
+        let mut attempt = 1;
 
-        LittleEndian::write_u32(&mut msg.data, config_type);
-        msg.data.extend_from_slice(param);
+        loop {
+            let result = self.get_lidar_conf_once_with_param_and_timeout(config_type, param, timeout);
 
-        let response = self.channel.invoke(&msg, timeout)?;
+            let timed_out = match &result {
+                Err(err) => is_timeout(err),
+                Ok(_) => false,
+            };
 
-        if let Some(mut response_msg) = response {
-            if response_msg.cmd != RPLIDAR_ANS_TYPE_GET_LIDAR_CONF {
-                return Err(RposError::OperationFail { description: "answer type mismatch".to_owned() }.into());
-            } else if response_msg.data.len() < 4
-                || LittleEndian::read_u32(&response_msg.data[0..4]) != config_type
-            {
-                return Err(RposError::OperationFail { description: "answer config type mismatch".to_owned() }.into());
-            } else {
-                return Ok(response_msg.data.split_off(4));
+            if !timed_out || attempt >= self.lidar_conf_attempts {
+                return result;
             }
+
+            self.channel.reset();
+            std::thread::sleep(backoff);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    /// get lidar config with parameter and timeout (single attempt)
+    fn get_lidar_conf_once_with_param_and_timeout(
+        &mut self,
+        config_type: u32,
+        param: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        self.check_not_in_standby()?;
+
+        let response = self.channel.invoke(&lidar_conf_request(config_type, param), timeout)?;
+
+        if let Some(response_msg) = response {
+            return parse_lidar_conf_answer(config_type, response_msg);
         } else {
             return Err(RposError::OperationTimeout.into());
         }
@@ -308,18 +781,14 @@ where
             timeout,
         )?;
 
-        if let Ok(name) = std::str::from_utf8(&ans_type_data) {
-            return Ok(name.to_owned().trim_matches('\0').to_owned());
-        } else {
-            return Err(RposError::ProtocolError { description: "invalid scan mode name".to_owned() }.into());
-        }
+        return Ok(parse_scan_mode_name(&ans_type_data));
     }
 
     /// get scan mode count
     fn get_scan_mode_count_with_timeout(&mut self, timeout: Duration) -> Result<u16> {
         let scan_mode_count_data =
             self.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_COUNT, timeout)?;
-        return parse_resp_data!(scan_mode_count_data, u16);
+        return parse_scan_mode_count(&scan_mode_count_data);
     }
 
     /// get scan mode of specific scan mode id
@@ -330,8 +799,7 @@ where
     ) -> Result<ScanMode> {
         Ok(ScanMode {
             id: scan_mode,
-            us_per_sample: self.get_scan_mode_us_per_sample_with_timeout(scan_mode, timeout)?
-                as f32,
+            us_per_sample: self.get_scan_mode_us_per_sample_with_timeout(scan_mode, timeout)?,
             max_distance: self.get_scan_mode_max_distance_with_timeout(scan_mode, timeout)?,
             ans_type: self.get_scan_mode_ans_type_with_timeout(scan_mode, timeout)?,
             name: self.get_scan_mode_name_with_timeout(scan_mode, timeout)?,
@@ -377,7 +845,7 @@ where
             let mut output: Vec<ScanMode> = Vec::with_capacity(scan_mode_count as usize);
 
             for i in 0..scan_mode_count {
-                output.push(self.get_scan_mode_with_timeout(i as u16, timeout)?);
+                output.push(self.get_scan_mode_with_timeout(i, timeout)?);
             }
 
             return Ok(output);
@@ -405,7 +873,7 @@ where
         options: &ScanOptions,
         timeout: Duration,
     ) -> Result<ScanMode> {
-        self.cached_prev_capsule = CachedPrevCapsule::None;
+        self.check_not_in_standby()?;
 
         let scan_mode = match options.scan_mode {
             Some(mode) => mode,
@@ -414,291 +882,3974 @@ where
 
         let scan_mode_info = self.get_scan_mode_with_timeout(scan_mode, timeout)?;
 
-        match scan_mode {
-            0 => self.legacy_start_scan(options.force_scan)?,
-            _ => {
-                let payload = RplidarPayloadExpressScan {
-                    work_mode: scan_mode as u8,
-                    work_flags: options.options as u16,
-                    param: 0,
-                };
-                self.start_express_scan(&payload)?;
-            }
-        }
+        let (scan_path, request) = scan_start_request(scan_mode, options);
+        self.channel.write(&request)?;
+        self.on_scan_started(&scan_mode_info, options, scan_path);
 
         return Ok(scan_mode_info);
     }
 
-    /// use legacy command to start scan
-    fn legacy_start_scan(&mut self, force_scan: bool) -> Result<()> {
-        self.channel.write(&Message::new(if force_scan {
-            RPLIDAR_CMD_FORCE_SCAN
-        } else {
-            RPLIDAR_CMD_SCAN
-        }))?;
-        return Ok(());
+    /// reset the decoding state for the scan just started
+    fn on_scan_started(&mut self, scan_mode_info: &ScanMode, options: &ScanOptions, scan_path: ScanPath) {
+        self.cached_prev_capsule = CachedPrevCapsule::None;
+        self.active_scan_mode = Some(scan_mode_info.clone());
+        self.active_scan_options = Some(options.clone());
+        self.active_scan_path = Some(scan_path);
+        self.last_sync_decoded_at = None;
+        self.samples_since_sync = 0;
+        self.rotation_period = None;
+        self.recent_scan_periods.clear();
+        self.rotation_count = 0;
+        self.warmup_syncs_remaining = if options.warmup_rotations > 0 { options.warmup_rotations + 1 } else { 0 };
+        self.sync_bit_seen = false;
+        self.last_decoded_angle_z_q14 = None;
     }
 
-    /// start express scan with options
-    fn start_express_scan(&mut self, options: &RplidarPayloadExpressScan) -> Result<()> {
-        let data = unsafe {
-            transmute_copy::<
-                RplidarPayloadExpressScan,
-                [u8; std::mem::size_of::<RplidarPayloadExpressScan>()],
-            >(options)
-        };
-        self.channel
-            .write(&Message::with_data(RPLIDAR_CMD_EXPRESS_SCAN, &data))?;
-        return Ok(());
+    /// check if points are being discarded for `ScanOptions::warmup_rotations` after the scan started
+    pub fn is_warming_up(&self) -> bool {
+        return self.warmup_syncs_remaining > 0;
     }
 
-    /// when hq measurement node received
-    fn on_measurement_node_hq(&mut self, node: RplidarResponseMeasurementNodeHq) {
-        self.cached_measurement_nodes
-            .push_back(ScanPoint::from(node));
-    }
+    /// start scan in the scan mode which reaches `required_range_m` meters with the lowest sample rate
+    ///
+    /// Longer range usually comes with lower sample rate, so this picks the most conservative mode
+    /// satisfying the requirement. Returns error if no scan mode reaches the range
+    pub fn start_scan_for_range(&mut self, required_range_m: f32) -> Result<ScanMode> {
+        let scan_modes = self.get_all_supported_scan_modes()?;
 
-    /// when measurement node received
-    fn on_measurement_node(&mut self, node: RplidarResponseMeasurementNode) {
-        self.on_measurement_node_hq(RplidarResponseMeasurementNodeHq {
-            angle_z_q14: ((((node.angle_q6_checkbit as u32)
-                >> RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT as u32)
-                << 8)
-                / 90) as u16,
-            dist_mm_q2: node.distance_q2 as u32,
-            flag: node.sync_quality & RPLIDAR_RESP_MEASUREMENT_SYNCBIT,
-            quality: (node.sync_quality >> RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8)
-                << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8,
-        });
-    }
+        let selected = scan_modes
+            .iter()
+            .filter(|mode| mode.max_distance >= required_range_m)
+            .fold(None, |selected: Option<&ScanMode>, mode| match selected {
+                Some(selected) if selected.us_per_sample >= mode.us_per_sample => Some(selected),
+                _ => Some(mode),
+            });
 
-    /// when capsuled measurement msg received
-    fn on_measurement_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
-        check_sync_and_checksum(msg)?;
-        self.on_measurement_capsuled(parse_resp!(msg, RplidarResponseCapsuleMeasurementNodes)?);
-        return Ok(());
+        match selected {
+            Some(mode) => {
+                let options = ScanOptions::with_mode(mode.id);
+                return self.start_scan_with_options(&options);
+            }
+            None => {
+                return Err(RposError::OperationFail {
+                    description: format!("no scan mode reaches {}m", required_range_m)
+                }.into());
+            }
+        }
     }
 
-    /// when capsuled measurement response received
-    fn on_measurement_capsuled(&mut self, nodes: RplidarResponseCapsuleMeasurementNodes) {
-        let (parsed_nodes, new_cached_capsuled) = parse_capsuled(&self.cached_prev_capsule, nodes);
-        self.cached_prev_capsule = new_cached_capsuled;
+    /// find the supported scan mode by name (e.g. "Standard", "Express", "Boost"), case insensitive
+    pub fn find_scan_mode_by_name(&mut self, name: &str) -> Result<Option<ScanMode>> {
+        return Ok(self
+            .get_all_supported_scan_modes()?
+            .into_iter()
+            .find(|mode| mode.name.eq_ignore_ascii_case(name)));
+    }
 
-        for node in parsed_nodes {
-            self.on_measurement_node_hq(node);
+    /// start scan in the scan mode with the name (case insensitive)
+    ///
+    /// Returns error if the LIDAR doesn't support the scan mode
+    pub fn start_scan_by_name(&mut self, name: &str) -> Result<ScanMode> {
+        match self.find_scan_mode_by_name(name)? {
+            Some(mode) => return self.start_scan_with_options(&ScanOptions::with_mode(mode.id)),
+            None => {
+                return Err(RposError::OperationFail {
+                    description: format!("scan mode {:?} is not supported", name)
+                }.into());
+            }
         }
     }
 
-    /// when ultra capsuled measurement msg received
-    fn on_measurement_ultra_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
-        check_sync_and_checksum(msg)?;
-        self.on_measurement_ultra_capsuled(parse_resp!(
-            msg,
-            RplidarResponseUltraCapsuleMeasurementNodes
-        )?);
-        return Ok(());
-    }
+    /// start scan in the scan mode with the longest range, whose rotation completes within `max_rotation_period`
+    /// when the LIDAR rotates at `rotation_hz`
+    ///
+    /// A rotation completes when its last sample is decoded, which is the rotation period plus the samples
+    /// buffered in an answer of the scan mode (e.g. 96 samples of ultra capsules). Among modes with the same range,
+    /// the one with the lowest sample rate is picked. Returns error if no scan mode fits the budget
+    pub fn start_scan_best_within(&mut self, max_rotation_period: Duration, rotation_hz: f32) -> Result<ScanMode> {
+        if rotation_hz <= 0f32 {
+            return Err(RposError::OperationFail {
+                description: format!("invalid rotation frequency {}Hz", rotation_hz)
+            }.into());
+        }
 
-    /// when ultra capsuled measurement response received
-    fn on_measurement_ultra_capsuled(
-        &mut self,
-        nodes: RplidarResponseUltraCapsuleMeasurementNodes,
-    ) {
-        let (parsed_nodes, new_cached_capsuled) = parse_ultra_capsuled(&self.cached_prev_capsule, nodes);
-        self.cached_prev_capsule = new_cached_capsuled;
+        let scan_modes = self.get_all_supported_scan_modes()?;
 
-        for node in parsed_nodes {
-            self.on_measurement_node_hq(node);
+        let selected = scan_modes
+            .iter()
+            .filter(|mode| {
+                let buffered_us = (samples_per_answer(mode.ans_type) as f32) * mode.us_per_sample;
+                let rotation_period_s = 1f32 / rotation_hz + buffered_us / 1000000f32;
+                rotation_period_s <= max_rotation_period.as_secs_f32()
+            })
+            .fold(None, |selected: Option<&ScanMode>, mode| match selected {
+                Some(selected) if selected.max_distance > mode.max_distance => Some(selected),
+                Some(selected) if selected.max_distance == mode.max_distance
+                    && selected.us_per_sample >= mode.us_per_sample => Some(selected),
+                _ => Some(mode),
+            });
+
+        match selected {
+            Some(mode) => {
+                let options = ScanOptions::with_mode(mode.id);
+                return self.start_scan_with_options(&options);
+            }
+            None => {
+                return Err(RposError::OperationFail {
+                    description: format!("no scan mode completes a rotation within {:?}", max_rotation_period)
+                }.into());
+            }
         }
     }
 
-    /// when hq capsuled measurement msg received
-    fn on_measurement_hq_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
-        check_sync_and_checksum_hq(msg)?;
-        self.on_measurement_hq_capsuled(parse_resp!(
-            msg,
-            RplidarResponseHqCapsuledMeasurementNodes
-        )?);
-        return Ok(());
+    /// get the options used by the last successful `start_scan`
+    pub fn active_scan_options(&self) -> Option<&ScanOptions> {
+        return self.active_scan_options.as_ref();
     }
 
-    /// when hq capsuled measurement response received
-    fn on_measurement_hq_capsuled(
-        &mut self,
-        nodes: RplidarResponseHqCapsuledMeasurementNodes,
-    ) {
-        for node in nodes.nodes.iter() {
-            self.on_measurement_node_hq(node.clone());
-        }
+    /// get whether the last successful `start_scan` used the legacy or express command
+    pub fn active_scan_path(&self) -> Option<ScanPath> {
+        return self.active_scan_path;
     }
 
-    /// wait for next section of scan data
-    fn wait_scan_data_with_timeout(&mut self, timeout: Duration) -> Result<()> {
-        let opt_msg = self.channel.read_until(timeout)?;
+    /// when hq measurement node received
+    fn on_measurement_node_hq(&mut self, node: RplidarResponseMeasurementNodeHq) {
+        let mut point = ScanPoint::from(node);
+        self.interpret_sync(&mut point);
 
-        if let Some(msg) = opt_msg {
-            match msg.cmd {
-                RPLIDAR_ANS_TYPE_MEASUREMENT => {
-                    self.on_measurement_node(parse_resp!(msg, RplidarResponseMeasurementNode)?)
-                }
-                RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED => self.on_measurement_capsuled_msg(&msg)?,
-                RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA => self.on_measurement_ultra_capsuled_msg(&msg)?,
-                RPLIDAR_ANS_TYPE_MEASUREMENT_HQ => self.on_measurement_hq_capsuled_msg(&msg)?,
-                _ => {
-                    return Err(RposError::ProtocolError { description: "unexpected response".to_owned() }.into());
+        if point.is_sync() {
+            self.on_sync_decoded();
+            self.angular_decimation_phase = 0;
+            self.rotation_count += 1;
+            self.warmup_syncs_remaining = self.warmup_syncs_remaining.saturating_sub(1);
+        }
+        self.samples_since_sync += 1;
+
+        if self.warmup_syncs_remaining > 0 {
+            return;
+        }
+
+        let keep = self.angular_decimation_phase == 0;
+        self.angular_decimation_phase = (self.angular_decimation_phase + 1) % self.angular_decimation;
+
+        if let (true, Some(discarded)) = (keep, self.discarded_points.as_mut()) {
+            *discarded += 1;
+        } else if keep {
+            point.angle_z_q14 = point.angle_z_q14.wrapping_add(self.frame_angle_offset_z_q14);
+            if point.dist_mm_q2 != 0 && self.range_bias_q2 != 0 {
+                point.dist_mm_q2 = (point.dist_mm_q2 as i64 - self.range_bias_q2).clamp(0, u32::MAX as i64) as u32;
+            }
+            if let Some(max_cached_points) = self.max_cached_points {
+                while self.cached_measurement_nodes.len() >= max_cached_points.max(1) {
+                    self.cached_measurement_nodes.pop_front();
+                    self.dropped_point_count += 1;
                 }
             }
-            return Ok(());
-        } else {
-            return Ok(());
+
+            self.cached_measurement_nodes.push_back(point);
+            self.decoded_point_count += 1;
+            self.session_stats.points_emitted += 1;
         }
     }
 
-    /// read scan point
-    pub fn grab_scan_point(&mut self) -> Result<ScanPoint> {
-        self.grab_scan_point_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
-    }
+    /// set the sync flag of the decoded point according to the sync mode
+    fn interpret_sync(&mut self, point: &mut ScanPoint) {
+        self.sync_bit_seen = self.sync_bit_seen || point.is_sync();
 
-    /// read scan point with timeout
-    pub fn grab_scan_point_with_timeout(&mut self, timeout: Duration) -> Result<ScanPoint> {
-        if self.cached_measurement_nodes.is_empty() {
-            self.wait_scan_data_with_timeout(timeout)?;
+        let wrapped = match self.last_decoded_angle_z_q14 {
+            Some(last) => last > point.angle_z_q14 && last - point.angle_z_q14 > 0x8000,
+            None => false,
+        };
+        self.last_decoded_angle_z_q14 = Some(point.angle_z_q14);
 
-            if self.cached_measurement_nodes.is_empty() {
-                return Err(RposError::OperationTimeout.into());
+        let use_angle_wrap = match self.sync_mode {
+            SyncMode::StandardBit => false,
+            SyncMode::AngleWrap => true,
+            SyncMode::Auto => !self.sync_bit_seen,
+        };
+
+        if use_angle_wrap {
+            point.flag &= !RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
+            if wrapped {
+                point.flag |= RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
             }
         }
+    }
 
-        return Ok(self.cached_measurement_nodes.pop_front().unwrap());
+    /// set how rotation boundaries are detected, for firmwares placing the sync flag differently
+    ///
+    /// The sync flags of decoded points are rewritten accordingly, so it applies to `grab_scan` and all others
+    pub fn set_sync_interpretation(&mut self, mode: SyncMode) {
+        self.sync_mode = mode;
     }
 
-    /// read scan frame
-    pub fn grab_scan(&mut self) -> Result<Vec<ScanPoint>> {
-        self.grab_scan_with_timeout(RPLIDAR_DEFAULT_TIMEOUT * 5)
+    /// get how rotation boundaries are detected
+    pub fn sync_interpretation(&self) -> SyncMode {
+        return self.sync_mode;
     }
 
-    /// read scan frame
-    pub fn grab_scan_with_timeout(&mut self, timeout: Duration) -> Result<Vec<ScanPoint>> {
-        let deadline = Instant::now() + timeout;
-        let mut end = 1;
+    /// set the unit of distances exported by `scan_distances`, `ordered_ranges`, `averaged_ranges`,
+    /// `normalized_ranges` and `scan_rle`
+    ///
+    /// Only exported distances are converted, the raw fixed-point `ScanPoint::dist_mm_q2` is unchanged,
+    /// and `ScanPoint::distance` as well as the free functions of `utils` are always in meters
+    pub fn set_distance_unit(&mut self, unit: DistanceUnit) {
+        self.distance_unit = unit;
+    }
 
-        'outer_loop: loop {
-            if Instant::now() > deadline {
-                return Err(RposError::OperationTimeout.into());
-            }
+    /// get the unit of exported distances
+    pub fn distance_unit(&self) -> DistanceUnit {
+        return self.distance_unit;
+    }
 
-            if self.cached_measurement_nodes.len() <= end {
-                self.wait_scan_data_with_timeout(std::cmp::min(deadline - Instant::now(), RPLIDAR_DEFAULT_TIMEOUT))?;
-            }
+    /// convert `meters` to the unit set by `set_distance_unit`
+    fn meters_to_distance_unit(&self, meters: f32) -> f32 {
+        return meters * 1000f32 / self.distance_unit.mm_per_unit();
+    }
 
-            for i in end..self.cached_measurement_nodes.len() {
-                if self.cached_measurement_nodes[i].is_sync() {
-                    end = i;
-                    break 'outer_loop;
+    /// distances of the scan points in the unit set by `set_distance_unit` (millimeters by default)
+    pub fn scan_distances(&self, scan: &[ScanPoint]) -> Vec<f32> {
+        return scan.iter().map(|point| point.distance_in(self.distance_unit)).collect();
+    }
+
+    /// same as `utils::to_ordered_ranges`, but `range_min`, `range_max` and the ranges are in the unit set by
+    /// `set_distance_unit`
+    pub fn ordered_ranges(&self, scan: &[ScanPoint], bins: usize, range_min: f32, range_max: f32) -> utils::OrderedScan {
+        let mm_per_unit = self.distance_unit.mm_per_unit();
+        let mut ordered = utils::to_ordered_ranges(scan, bins, range_min * mm_per_unit / 1000f32, range_max * mm_per_unit / 1000f32);
+
+        ordered.range_min = range_min;
+        ordered.range_max = range_max;
+        for range in ordered.ranges.iter_mut() {
+            *range = self.meters_to_distance_unit(*range);
+        }
+
+        return ordered;
+    }
+
+    /// same as `utils::average_scans`, but the ranges are in the unit set by `set_distance_unit`
+    pub fn averaged_ranges(&self, scans: &[Vec<ScanPoint>], bins: usize) -> Vec<f32> {
+        return utils::average_scans(scans, bins)
+            .iter()
+            .map(|range| self.meters_to_distance_unit(*range))
+            .collect();
+    }
+
+    /// same as `normalize_scan`, but the distances are in the unit set by `set_distance_unit`
+    pub fn normalized_ranges(&self, scan: &[ScanPoint], bins: usize) -> Vec<Option<f32>> {
+        return normalize_scan(scan, bins)
+            .iter()
+            .map(|range| range.map(|range| self.meters_to_distance_unit(range)))
+            .collect();
+    }
+
+    /// same as `utils::scan_to_rle`, but `quantum` and the ranges are in the unit set by `set_distance_unit`
+    pub fn scan_rle(&self, scan: &[ScanPoint], bins: usize, quantum: f32) -> Vec<(u16, f32)> {
+        let mm_per_unit = self.distance_unit.mm_per_unit();
+
+        return utils::scan_to_rle(scan, bins, quantum * mm_per_unit)
+            .iter()
+            .map(|(run_length, range_mm)| (*run_length, range_mm / mm_per_unit))
+            .collect();
+    }
+
+    /// mark the points decoded from now on as measured in the capsule starting at `start_angle_deg`
+    /// (`NaN` for points not from capsules)
+    fn begin_capsule_segment(&mut self, start_angle_deg: f32) {
+        // forget segments whose points are no longer cached
+        let cache_front = self.decoded_point_count - self.cached_measurement_nodes.len() as u64;
+        while self.capsule_segments.len() >= 2 && self.capsule_segments[1].0 <= cache_front {
+            self.capsule_segments.pop_front();
+        }
+
+        match self.capsule_segments.back() {
+            Some((_, last)) if last.is_nan() && start_angle_deg.is_nan() => {}
+            _ => self.capsule_segments.push_back((self.decoded_point_count, start_angle_deg)),
+        }
+    }
+
+    /// start angle (in degrees, with frame transform applied) of the cached previous capsule
+    fn prev_capsule_start_angle_deg(&self) -> Option<f32> {
+        let start_angle_sync_q6 = match &self.cached_prev_capsule {
+            CachedPrevCapsule::None => return None,
+            CachedPrevCapsule::Capsuled(nodes) => nodes.start_angle_sync_q6,
+            CachedPrevCapsule::UltraCapsuled(nodes) => nodes.start_angle_sync_q6,
+            CachedPrevCapsule::DenseCapsuled(nodes) => nodes.start_angle_sync_q6,
+        };
+
+        let start_angle_deg = ((start_angle_sync_q6 & 0x7fff) as f32) / 64f32;
+        return Some((start_angle_deg + self.frame_angle_offset_deg).rem_euclid(360f32));
+    }
+
+    /// read scan frame, with the start angle (in degrees) of the capsule each point is decoded from
+    ///
+    /// The start angles are raw anchors from the protocol for custom motion compensation, in the same frame as the points.
+    /// Points not decoded from capsules (legacy and HQ answers) have `NaN` start angles
+    pub fn grab_scan_with_capsule_angles(&mut self) -> Result<(Vec<ScanPoint>, Vec<f32>)> {
+        let scan = self.grab_scan()?;
+
+        let end = self.decoded_point_count - self.cached_measurement_nodes.len() as u64;
+        let mut segments = self.capsule_segments.iter().peekable();
+        let mut start_angle_deg = f32::NAN;
+
+        let angles = (end - scan.len() as u64..end)
+            .map(|index| {
+                while let Some((first, angle)) = segments.peek() {
+                    if *first > index {
+                        break;
+                    }
+                    start_angle_deg = *angle;
+                    segments.next();
                 }
-            }
+                start_angle_deg
+            })
+            .collect();
+
+        return Ok((scan, angles));
+    }
+
+    /// keep only every `keep_every_n` points received (`1` to keep every point, which is the default)
+    ///
+    /// This is lossy: the dropped points are discarded by the decoder and never cached,
+    /// which saves CPU and memory when only coarse angular resolution is needed (e.g. in boost mode).
+    /// The first point of each rotation (with sync flag) is always kept
+    pub fn set_angular_decimation(&mut self, keep_every_n: u16) {
+        self.angular_decimation = keep_every_n.max(1);
+        self.angular_decimation_phase = 0;
+    }
+
+    /// get the decimation set by `set_angular_decimation`
+    pub fn angular_decimation(&self) -> u16 {
+        return self.angular_decimation;
+    }
+
+    /// set the angle offset (in degrees) added to all received points, to align the zero of the LIDAR with the robot
+    pub fn set_frame_transform(&mut self, angle_offset_deg: f32) {
+        let offset = angle_offset_deg.rem_euclid(360f32);
+        self.frame_angle_offset_deg = angle_offset_deg;
+        self.frame_angle_offset_z_q14 = ((offset / 360f32 * 65536f32).round() as u32 & 0xffff) as u16;
+    }
+
+    /// get the angle offset (in degrees) added to all received points
+    pub fn frame_transform(&self) -> f32 {
+        return self.frame_angle_offset_deg;
+    }
+
+    /// calculate the angle offset to feed into `set_frame_transform`, so the dominant wall in the scan
+    /// has the expected bearing (direction of the wall line, in degrees)
+    ///
+    /// The scan should be grabbed with the current frame transform, which is taken into account.
+    /// As walls have no direction, the correction is within `[-90, 90)` degrees of the current transform.
+    /// Returns `NaN` if there is no wall in the scan
+    pub fn calibrate_zero_offset(&mut self, reference_feature_bearing_deg: f32, scan: &[ScanPoint]) -> f32 {
+        let wall_bearing_deg = match utils::dominant_wall_angle(scan) {
+            Some(angle) => angle.to_degrees(),
+            None => return f32::NAN,
+        };
+
+        let correction = (reference_feature_bearing_deg - wall_bearing_deg + 90f32).rem_euclid(180f32) - 90f32;
+        return self.frame_angle_offset_deg + correction;
+    }
+
+    /// set the systematic range offset (in millimeters) subtracted from distances of all received points
+    ///
+    /// Points closer than a positive bias become invalid (zero distance)
+    pub fn set_range_bias(&mut self, bias_mm: f32) {
+        self.range_bias_mm = bias_mm;
+        self.range_bias_q2 = (bias_mm * 4f32).round() as i64;
+    }
+
+    /// get the range offset (in millimeters) subtracted from distances of all received points
+    pub fn range_bias(&self) -> f32 {
+        return self.range_bias_mm;
+    }
 
-            end = self.cached_measurement_nodes.len();
+    /// calculate the range bias to feed into `set_range_bias`, from points on a target at a known distance
+    ///
+    /// The scan should only contain points on the target (e.g. split by `utils::split_into_sectors`), and should be
+    /// grabbed with the current range bias, which is taken into account. The median distance is compared, so a few
+    /// stray points don't skew the bias. Returns `NaN` if there is no valid point in the scan
+    pub fn calibrate_range_bias(&mut self, known_distance_mm: f32, scan: &[ScanPoint]) -> f32 {
+        let mut distances: Vec<f32> = utils::valid_points(scan).map(|point| (point.dist_mm_q2 as f32) / 4f32).collect();
+        if distances.is_empty() {
+            return f32::NAN;
         }
 
-        let mut out = Vec::<ScanPoint>::with_capacity(end);
-        for _ in 0..end {
-            if let Some(point) = self.cached_measurement_nodes.pop_front() {
-                out.push(point);
+        let middle = distances.len() / 2;
+        let (_, median, _) = distances.select_nth_unstable_by(middle, |a, b| a.total_cmp(b));
+        return self.range_bias_mm + *median - known_distance_mm;
+    }
+
+    /// integrate rotation between this scan and the previous tracked scan into the heading (in degrees)
+    ///
+    /// The heading starts from 0 at the first tracked scan, and is not wrapped (it's the cumulative rotation).
+    /// It's a cheap gyro-free heading for platforms rotating in place: each step is estimated by
+    /// `utils::estimate_scan_delta`, so the heading drifts as a random walk of sub-degree registration errors,
+    /// and drifts quickly with translation, moving objects or rotation over 45 degrees between scans.
+    /// The heading is kept when the rotation could not be estimated
+    pub fn track_heading(&mut self, scan: &[ScanPoint]) -> f32 {
+        if let Some(prev) = &self.heading_prev_scan {
+            if let Some(delta) = utils::estimate_scan_delta(prev, scan) {
+                self.heading_deg += delta;
             }
         }
 
-        return Ok(out);
+        self.heading_prev_scan = Some(scan.to_vec());
+        return self.heading_deg;
     }
 
-    /// Get LIDAR health information
-    pub fn get_device_health(&mut self) -> Result<Health> {
-        self.get_device_health_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    /// reset the heading tracked by `track_heading` to 0, and forget the previous scan
+    pub fn reset_heading(&mut self) {
+        self.heading_prev_scan = None;
+        self.heading_deg = 0f32;
     }
 
-    /// Get LIDAR health information
-    pub fn get_device_health_with_timeout(
+    /// when measurement node received
+    fn on_measurement_node(&mut self, node: RplidarResponseMeasurementNode) -> Result<()> {
+        if let Err(err) = check_measurement_node(&node) {
+            self.corrupt_node_count += 1;
+
+            if self.corrupt_node_policy == CorruptNodePolicy::Error {
+                return Err(err);
+            }
+            return Ok(());
+        }
+
+        self.begin_capsule_segment(f32::NAN);
+        self.on_measurement_node_hq(RplidarResponseMeasurementNodeHq {
+            angle_z_q14: ((((node.angle_q6_checkbit as u32)
+                >> RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT as u32)
+                << 8)
+                / 90) as u16,
+            dist_mm_q2: node.distance_q2 as u32,
+            flag: node.sync_quality & RPLIDAR_RESP_MEASUREMENT_SYNCBIT,
+            quality: (node.sync_quality >> RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8)
+                << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8,
+        });
+        return Ok(());
+    }
+
+    /// fail the capsule if its checksum does not match, counting it in the session stats
+    fn on_capsule_checked(&mut self, checksum_matched: bool) -> Result<()> {
+        if !checksum_matched {
+            self.session_stats.checksum_failures += 1;
+            return Err(checksum_mismatch_error());
+        }
+
+        return Ok(());
+    }
+
+    /// when capsuled measurement msg received
+    fn on_measurement_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
+        let checksum_matched = check_sync_and_checksum(msg, self.checksum_verifier.as_ref())?;
+        self.on_capsule_checked(checksum_matched)?;
+        let nodes = parse_resp!(msg, RplidarResponseCapsuleMeasurementNodes)?;
+        self.session_stats.capsules_parsed += 1;
+        self.on_measurement_capsuled(nodes);
+        return Ok(());
+    }
+
+    /// when capsuled measurement response received
+    fn on_measurement_capsuled(&mut self, nodes: RplidarResponseCapsuleMeasurementNodes) {
+        if let Some(start_angle_deg) = self.prev_capsule_start_angle_deg() {
+            self.begin_capsule_segment(start_angle_deg);
+        }
+
+        let (parsed_nodes, new_cached_capsuled) = parse_capsuled(&self.cached_prev_capsule, nodes);
+        self.cached_prev_capsule = new_cached_capsuled;
+
+        for node in parsed_nodes {
+            self.on_measurement_node_hq(node);
+        }
+    }
+
+    /// when ultra capsuled measurement msg received
+    fn on_measurement_ultra_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
+        let checksum_matched = check_sync_and_checksum(msg, self.checksum_verifier.as_ref())?;
+        self.on_capsule_checked(checksum_matched)?;
+        let nodes = parse_resp!(msg, RplidarResponseUltraCapsuleMeasurementNodes)?;
+        self.session_stats.capsules_parsed += 1;
+        self.on_measurement_ultra_capsuled(nodes);
+        return Ok(());
+    }
+
+    /// when ultra capsuled measurement response received
+    fn on_measurement_ultra_capsuled(
         &mut self,
-        timeout: Duration,
-    ) -> Result<Health> {
-        if let Some(msg) = self
-            .channel
-            .invoke(&Message::new(RPLIDAR_CMD_GET_DEVICE_HEALTH), timeout)?
-        {
-            let resp = handle_resp!(RPLIDAR_ANS_TYPE_DEVHEALTH, msg, RplidarResponseDeviceHealth)?;
+        nodes: RplidarResponseUltraCapsuleMeasurementNodes,
+    ) {
+        if let Some(start_angle_deg) = self.prev_capsule_start_angle_deg() {
+            self.begin_capsule_segment(start_angle_deg);
+        }
 
-            return Ok(match resp.status {
-                RPLIDAR_HEALTH_STATUS_OK => Health::Healthy,
-                RPLIDAR_HEALTH_STATUS_WARNING => Health::Warning(resp.error_code),
-                RPLIDAR_HEALTH_STATUS_ERROR => Health::Error(resp.error_code),
-                _ => Health::Healthy
-            });
+        let (parsed_nodes, new_cached_capsuled) = parse_ultra_capsuled(&self.cached_prev_capsule, nodes);
+        self.cached_prev_capsule = new_cached_capsuled;
+
+        for node in parsed_nodes {
+            self.on_measurement_node_hq(node);
         }
+    }
 
-        return Err(RposError::OperationTimeout.into());
+    /// when dense capsuled measurement msg received
+    fn on_measurement_dense_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
+        let checksum_matched = check_sync_and_checksum(msg, self.checksum_verifier.as_ref())?;
+        self.on_capsule_checked(checksum_matched)?;
+        let nodes = parse_resp!(msg, RplidarResponseDenseCapsuleMeasurementNodes)?;
+        self.session_stats.capsules_parsed += 1;
+        self.on_measurement_dense_capsuled(nodes);
+        return Ok(());
     }
 
-    /// Check if the connected LIDAR supports motor control
-    pub fn check_motor_ctrl_support(&mut self) -> Result<bool> {
-        self.check_motor_ctrl_support_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    /// when dense capsuled measurement response received
+    fn on_measurement_dense_capsuled(
+        &mut self,
+        nodes: RplidarResponseDenseCapsuleMeasurementNodes,
+    ) {
+        if let Some(start_angle_deg) = self.prev_capsule_start_angle_deg() {
+            self.begin_capsule_segment(start_angle_deg);
+        }
+
+        let (parsed_nodes, new_cached_capsuled) = parse_dense_capsuled(&self.cached_prev_capsule, nodes);
+        self.cached_prev_capsule = new_cached_capsuled;
+
+        for node in parsed_nodes {
+            self.on_measurement_node_hq(node);
+        }
     }
 
-    /// Check if the connected LIDAR supports motor control with timeout
-    pub fn check_motor_ctrl_support_with_timeout(&mut self, timeout: Duration) -> Result<bool> {
-        let mut data = [0u8; 4];
-        LittleEndian::write_u32(&mut data, 0u32);
+    /// when hq capsuled measurement msg received
+    fn on_measurement_hq_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
+        let checksum_matched = check_sync_and_checksum_hq(msg)?;
+        self.on_capsule_checked(checksum_matched)?;
+        let nodes = parse_resp!(msg, RplidarResponseHqCapsuledMeasurementNodes)?;
+        self.session_stats.capsules_parsed += 1;
+        self.on_measurement_hq_capsuled(nodes);
+        return Ok(());
+    }
 
-        let resp_msg = self.channel.invoke(&Message::with_data(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &data), timeout)?;
+    /// when hq capsuled measurement response received
+    fn on_measurement_hq_capsuled(
+        &mut self,
+        nodes: RplidarResponseHqCapsuledMeasurementNodes,
+    ) {
+        self.begin_capsule_segment(f32::NAN);
+        for node in nodes.nodes.iter() {
+            self.on_measurement_node_hq(*node);
+        }
+    }
 
-        if let Some(msg) = resp_msg {
-            let support_flag = handle_resp!(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, msg, u32)?;
-            
-            return Ok((support_flag & RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK) == RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK);
-        } else {
-            return Err(RposError::OperationTimeout.into());
+    /// wait for next section of scan data
+    fn wait_scan_data_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.check_not_in_standby()?;
+
+        let result = match self.channel.read_until(timeout) {
+            Ok(Some(msg)) => self.on_scan_data_msg(&msg),
+            Ok(None) => Ok(()),
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = &result {
+            if is_protocol_error(err) {
+                self.session_stats.protocol_errors += 1;
+            }
         }
+
+        return result;
     }
-}
 
-fn check_sync_and_checksum(msg: &Message) -> Result<()> {
-    if msg.data.len() < 2 {
-        return Err(RposError::ProtocolError { description: "data too short".to_owned() }.into());
+    /// when scan data msg received
+    fn on_scan_data_msg(&mut self, msg: &Message) -> Result<()> {
+        if let Some(observer) = self.raw_message_observer.as_mut() {
+            (observer.0)(msg);
+        }
+
+        let result = self.decode_scan_data_msg(msg);
+
+        if let (Err(err), Some(handler)) = (&result, self.decode_error_handler.as_mut()) {
+            (handler.0)(&DecodeError {
+                ans_type: msg.cmd,
+                message_len: msg.data.len(),
+                reason: err.to_string(),
+            });
+        }
+
+        return result;
     }
 
-    if (msg.data[0] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 {
-        return Err(RposError::ProtocolError { description: "miss sync 1".to_owned() }.into());
+    /// decode scan data msg into the cache of scan points
+    fn decode_scan_data_msg(&mut self, msg: &Message) -> Result<()> {
+        let retained = &msg.data[..std::cmp::min(msg.data.len(), RPLIDAR_LAST_RAW_MESSAGE_MAX_SIZE)];
+        match &mut self.last_raw_message {
+            Some(last_raw_message) => {
+                last_raw_message.clear();
+                last_raw_message.extend_from_slice(retained);
+            }
+            None => self.last_raw_message = Some(retained.to_vec()),
+        }
+
+        if msg.data.is_empty() {
+            return Err(empty_message_error());
+        }
+
+        match msg.cmd {
+            _ if !self.protocol_version_hint.has_measurement_ans_type(msg.cmd) => self.on_unexpected_answer()?,
+            // answers are decoded as declared by the active scan mode, stray answers of other types are unexpected
+            _ if matches!(&self.active_scan_mode, Some(mode) if mode.ans_type != msg.cmd) => self.on_unexpected_answer()?,
+            RPLIDAR_ANS_TYPE_MEASUREMENT => {
+                self.on_measurement_node(parse_resp!(msg, RplidarResponseMeasurementNode)?)?
+            }
+            RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED => self.on_measurement_capsuled_msg(msg)?,
+            RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA => self.on_measurement_ultra_capsuled_msg(msg)?,
+            RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED => self.on_measurement_dense_capsuled_msg(msg)?,
+            RPLIDAR_ANS_TYPE_MEASUREMENT_HQ => self.on_measurement_hq_capsuled_msg(msg)?,
+            _ => self.on_unexpected_answer()?,
+        }
+        return Ok(());
     }
 
-    if (msg.data[1] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 {
-        return Err(RposError::ProtocolError { description: "miss sync 2".to_owned() }.into());
+    /// set the handler called with the details of each scan message failed to decode, before the error is returned
+    ///
+    /// Log the details in the handler to debug decoding failures in the field
+    pub fn set_decode_error_handler(&mut self, handler: Box<dyn FnMut(&DecodeError)>) {
+        self.decode_error_handler = Some(DecodeErrorHandler(handler));
     }
 
-    let recv_checksum = (msg.data[0] & 0xf) | (msg.data[1] << 4);
-    let mut checksum = Checksum::new();
-    checksum.push_slice(&msg.data[2..]);
+    /// set the observer called with each scan message received, before it's validated and decoded
+    ///
+    /// Malformed messages are observed too, so protocol analyzers could inspect them
+    pub fn set_raw_message_observer(&mut self, observer: Box<dyn FnMut(&Message)>) {
+        self.raw_message_observer = Some(RawMessageObserver(observer));
+    }
 
-    if checksum.checksum() != recv_checksum {
-        return Err(RposError::ProtocolError { description: "checksum mismatch".to_owned() }.into());
-    } else {
+    /// get the payload of the last scan message received (truncated to 256 bytes), even if it failed to decode
+    ///
+    /// Dump it into bug reports or test fixtures when decoding fails
+    pub fn last_raw_message(&self) -> Option<&[u8]> {
+        return self.last_raw_message.as_deref();
+    }
+
+    /// when an answer of unexpected type received during scan
+    fn on_unexpected_answer(&mut self) -> Result<()> {
+        self.unexpected_answer_count += 1;
+
+        if self.unexpected_answer_policy == UnexpectedAnswerPolicy::Error {
+            return Err(RposError::ProtocolError { description: "unexpected response".to_owned() }.into());
+        }
         return Ok(());
     }
-}
 
-fn check_sync_and_checksum_hq(msg: &Message) -> Result<()> {
-    if msg.data.len() != std::mem::size_of::<RplidarResponseHqCapsuledMeasurementNodes>() {
-        return Err(RposError::ProtocolError { description: "data length mismatch".to_owned() }.into());
+    /// set the protocol generation of the LIDAR, which decides the answer layouts to decode
+    ///
+    /// There is no negotiation when decoding captures from older firmwares with `feed_bytes`,
+    /// so set the hint (e.g. `ProtocolVersion::from_firmware_version`) to only decode layouts of that firmware.
+    /// Answers of other layouts are handled as unexpected answers. Defaults to `ProtocolVersion::Extended`
+    pub fn set_protocol_version_hint(&mut self, version: ProtocolVersion) {
+        self.protocol_version_hint = version;
     }
 
-    if msg.data[0] != RPLIDAR_RESP_MEASUREMENT_HQ_SYNC {
-        return Err(RposError::ProtocolError { description: "sync mismatch".to_owned() }.into());
+    /// get the protocol generation set by `set_protocol_version_hint`
+    pub fn protocol_version_hint(&self) -> ProtocolVersion {
+        return self.protocol_version_hint;
     }
 
-    let checksum = crc32::checksum_ieee(&msg.data[0..msg.data.len()-4]);
-    let recv_checksum = LittleEndian::read_u32(&msg.data[msg.data.len()-4..msg.data.len()]);
+    /// decode bytes captured elsewhere (e.g. recorded sessions) as if they were received from the LIDAR
+    ///
+    /// The decoded scan points are cached, and could be retrieved by `grab_scan` or `grab_scan_point`
+    pub fn feed_bytes(&mut self, data: &[u8]) -> Result<()> {
+        for msg in self.channel.feed(data)? {
+            self.on_scan_data_msg(&msg)?;
+        }
+        return Ok(());
+    }
+
+    /// read and decode the data available from the LIDAR, but discard the decoded points
+    ///
+    /// Call this while the consumer can't process scans, so the OS buffer of the transport won't overflow.
+    /// Returns count of discarded points when no more data is available or timed out
+    pub fn drain_and_discard(&mut self, timeout: Duration) -> Result<usize> {
+        self.check_not_in_standby()?;
+
+        // points decoded meanwhile are counted instead of cached, so the cache is left as is
+        self.discarded_points = Some(0);
+        let result = self.drain_messages(Instant::now() + timeout);
+        let discarded = self.discarded_points.take().unwrap_or(0);
+
+        result?;
+        return Ok(discarded);
+    }
+
+    /// read and decode messages until no more data is available or `deadline`
+    fn drain_messages(&mut self, deadline: Instant) -> Result<()> {
+        while Instant::now() < deadline {
+            match self.channel.read()? {
+                Some(msg) => self.on_scan_data_msg(&msg)?,
+                None => break,
+            }
+        }
 
-    if checksum != recv_checksum {
-        return Err(RposError::ProtocolError { description: "checksum mismatch".to_owned() }.into());
-    } else {
         return Ok(());
     }
+
+    /// read scan point
+    pub fn grab_scan_point(&mut self) -> Result<ScanPoint> {
+        self.grab_scan_point_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// read scan point with timeout
+    pub fn grab_scan_point_with_timeout(&mut self, timeout: Duration) -> Result<ScanPoint> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RposError::OperationTimeout.into());
+            }
+
+            if let Some(point) = self.poll_scan_point_with_timeout(deadline - now)? {
+                return Ok(point);
+            }
+        }
+    }
+
+    /// read scan point, waiting up to `timeout` for one message of scan data
+    ///
+    /// Returns `None` if a message is received but produces no scan points (e.g. the first capsule or skipped
+    /// answers), which means no point yet. `OperationTimeout` is only returned if nothing is received
+    pub fn poll_scan_point_with_timeout(&mut self, timeout: Duration) -> Result<Option<ScanPoint>> {
+        if let Some(point) = self.cached_measurement_nodes.pop_front() {
+            return Ok(Some(point));
+        }
+
+        self.wait_scan_data_with_timeout(timeout)?;
+        return Ok(self.cached_measurement_nodes.pop_front());
+    }
+
+    /// read the first scan point with at least `min_quality` and a distance within `[min_distance_mm, max_distance_mm]`
+    ///
+    /// Points failing the filter are discarded. The timeout applies to the whole call, so a noisy sensor can't make it hang
+    pub fn grab_scan_point_filtered(
+        &mut self,
+        min_quality: u8,
+        min_distance_mm: u32,
+        max_distance_mm: u32,
+        timeout: Duration,
+    ) -> Result<ScanPoint> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            while let Some(point) = self.cached_measurement_nodes.pop_front() {
+                if utils::passes_filter(&point, min_quality, min_distance_mm, max_distance_mm) {
+                    return Ok(point);
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RposError::OperationTimeout.into());
+            }
+
+            self.wait_scan_data_with_timeout(deadline - now)?;
+        }
+    }
+
+    /// clear the decoding state of scan data, so the following `start_scan` works cleanly after data corruption
+    ///
+    /// Cached scan points, the cached capsule and the bytes not decoded yet are discarded,
+    /// and the decoder waits for the answer header of the next scan
+    pub fn reset_scan_state(&mut self) {
+        self.cached_measurement_nodes.clear();
+        self.cached_prev_capsule = CachedPrevCapsule::None;
+        self.capsule_segments.clear();
+        self.channel.reset();
+        self.channel.discard_pending_bytes();
+    }
+
+    /// read scan point, and recover once from protocol errors (e.g. data corrupted by USB glitches)
+    ///
+    /// On protocol error, the scan state is reset and the scan is restarted with the options of the last
+    /// `start_scan` (if any) before retrying. Errors of the retry are returned
+    pub fn grab_scan_point_recovering(&mut self) -> Result<ScanPoint> {
+        match self.grab_scan_point() {
+            Err(err) if is_protocol_error(&err) => {
+                self.reset_scan_state();
+                if let Some(options) = self.active_scan_options.clone() {
+                    self.start_scan_with_options(&options)?;
+                }
+                return self.grab_scan_point();
+            }
+            result => return result,
+        }
+    }
+
+    /// read scan point without waiting, returns `None` if no point is cached or decodable from the received data
+    ///
+    /// The bytes already received are decoded first, and the transport is read at most once per call only if they
+    /// give no point, so it never waits longer than the read timeout of the transport. Reads timed out or would
+    /// block give `None`. Incomplete messages are kept, and completed by the data read in later calls
+    pub fn try_grab_scan_point(&mut self) -> Result<Option<ScanPoint>> {
+        self.check_not_in_standby()?;
+
+        self.decode_buffered_scan_data()?;
+        if self.cached_measurement_nodes.is_empty() && self.channel.read_once()? > 0 {
+            self.decode_buffered_scan_data()?;
+        }
+
+        return Ok(self.cached_measurement_nodes.pop_front());
+    }
+
+    /// decode the received scan data until a point is cached, without reading the transport
+    fn decode_buffered_scan_data(&mut self) -> Result<()> {
+        while self.cached_measurement_nodes.is_empty() {
+            match self.channel.decode_buffered()? {
+                Some(msg) => self.on_scan_data_msg(&msg)?,
+                None => return Ok(()),
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// iterate over scan points, waiting up to the default timeout for each point
+    pub fn iter_scan_points(&mut self) -> ScanPointIterator<'_, T> {
+        self.iter_scan_points_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// iterate over scan points, waiting up to `timeout` for each point
+    ///
+    /// The iterator never ends, errors (including timeouts) are yielded for the caller to decide whether to stop
+    pub fn iter_scan_points_with_timeout(&mut self, timeout: Duration) -> ScanPointIterator<'_, T> {
+        ScanPointIterator {
+            device: self,
+            timeout: timeout,
+        }
+    }
+
+    /// peek the next scan point without removing it from the cache
+    ///
+    /// If there is no cached scan point, it will try to read scan data once.
+    /// Returns `None` if no scan point is available before timeout, other errors (e.g. protocol errors) are returned
+    pub fn peek_scan_point(&mut self) -> Result<Option<&ScanPoint>> {
+        if self.cached_measurement_nodes.is_empty() {
+            match self.wait_scan_data_with_timeout(RPLIDAR_DEFAULT_TIMEOUT) {
+                Err(err) if !is_timeout(&err) => return Err(err),
+                _ => {}
+            }
+        }
+
+        return Ok(self.cached_measurement_nodes.front());
+    }
+
+    /// set how to handle answers of unexpected types received during scan
+    pub fn set_unexpected_answer_policy(&mut self, policy: UnexpectedAnswerPolicy) {
+        self.unexpected_answer_policy = policy;
+    }
+
+    /// get how answers of unexpected types received during scan are handled
+    pub fn unexpected_answer_policy(&self) -> UnexpectedAnswerPolicy {
+        return self.unexpected_answer_policy;
+    }
+
+    /// set how to handle legacy measurement nodes failing the check bit or sync bit validation
+    pub fn set_corrupt_node_policy(&mut self, policy: CorruptNodePolicy) {
+        self.corrupt_node_policy = policy;
+    }
+
+    /// get how corrupt legacy measurement nodes are handled
+    pub fn corrupt_node_policy(&self) -> CorruptNodePolicy {
+        return self.corrupt_node_policy;
+    }
+
+    /// running counters of the decoded scan data, useful to diagnose flaky sensors or cables
+    pub fn stats(&self) -> &SessionStats {
+        return &self.session_stats;
+    }
+
+    /// restart counting the stats returned by `stats`
+    pub fn reset_stats(&mut self) {
+        self.session_stats = SessionStats::default();
+    }
+
+    /// count of legacy measurement nodes failing the check bit or sync bit validation
+    pub fn corrupt_node_count(&self) -> u64 {
+        return self.corrupt_node_count;
+    }
+
+    /// count of answers of unexpected types received during scan
+    pub fn unexpected_answer_count(&self) -> u64 {
+        return self.unexpected_answer_count;
+    }
+
+    /// read scan points available after a single read/decode cycle
+    ///
+    /// This doesn't wait for a rotation boundary, so each call only covers a small angular
+    /// range (usually no more than one capsule). Use this when latency matters more than coverage.
+    pub fn grab_points_available(&mut self, timeout: Duration) -> Result<Vec<ScanPoint>> {
+        self.wait_scan_data_with_timeout(timeout)?;
+        return Ok(self.cached_measurement_nodes.drain(..).collect());
+    }
+
+    /// set the min count of points of scans grabbed by `grab_scan`
+    ///
+    /// Rotation boundaries (sync flags) found before this count of points accumulated are ignored,
+    /// so glitched back-to-back sync flags will not produce spuriously tiny scans.
+    /// It's 1 by default, which accepts every rotation boundary
+    pub fn set_min_points_per_scan(&mut self, min_points_per_scan: usize) {
+        self.min_points_per_scan = min_points_per_scan;
+    }
+
+    /// get the min count of points of scans grabbed by `grab_scan`
+    pub fn min_points_per_scan(&self) -> usize {
+        return self.min_points_per_scan;
+    }
+
+    /// start the motor and scan, collect `k` rotations, then stop scanning and the motor
+    ///
+    /// The scan and the motor are stopped even if collecting fails, and `timeout` applies to the whole capture
+    pub fn scan_for_rotations(&mut self, k: usize, options: &ScanOptions, timeout: Duration) -> Result<Vec<Vec<ScanPoint>>> {
+        let deadline = Instant::now() + timeout;
+
+        let result = self.start_motor()
+            .and_then(|_| self.start_scan_with_options_and_timeout(options, timeout))
+            .and_then(|_| {
+                let mut rotations = Vec::with_capacity(k);
+                for _ in 0..k {
+                    rotations.push(self.grab_scan_with_timeout(deadline.saturating_duration_since(Instant::now()))?);
+                }
+                Ok(rotations)
+            });
+
+        let stopped = self.stop().and_then(|_| self.stop_motor());
+
+        let rotations = result?;
+        stopped?;
+        return Ok(rotations);
+    }
+
+    /// read scan frame
+    pub fn grab_scan(&mut self) -> Result<Vec<ScanPoint>> {
+        self.grab_scan_with_timeout(RPLIDAR_DEFAULT_TIMEOUT * 5)
+    }
+
+    /// read a full rotation, from a point with sync flag to the point before the next one
+    ///
+    /// Unlike `grab_scan`, the partial rotation before the first sync point is discarded,
+    /// so the scan always covers a clean sweep. Points of the next rotation are kept cached
+    pub fn grab_scan_frame(&mut self) -> Result<Vec<ScanPoint>> {
+        self.grab_scan_frame_with_timeout(RPLIDAR_DEFAULT_TIMEOUT * 5)
+    }
+
+    /// read a full rotation with timeout
+    pub fn grab_scan_frame_with_timeout(&mut self, timeout: Duration) -> Result<Vec<ScanPoint>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            while let Some(point) = self.cached_measurement_nodes.front() {
+                if point.is_sync() {
+                    return self.grab_scan_with_timeout(deadline.saturating_duration_since(Instant::now()));
+                }
+                self.cached_measurement_nodes.pop_front();
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RposError::OperationTimeout.into());
+            }
+
+            self.wait_scan_data_with_timeout(deadline - now)?;
+        }
+    }
+
+    /// read scan frame, with the timeout adapted to the measured rotation frequency
+    pub fn grab_scan_adaptive(&mut self) -> Result<Vec<ScanPoint>> {
+        let timeout = self.adaptive_scan_timeout();
+        self.grab_scan_with_timeout(timeout)
+    }
+
+    /// the timeout used by `grab_scan_adaptive`
+    ///
+    /// It's 3 rotation periods (but no shorter than 200ms) once the rotation frequency is measured,
+    /// or the timeout of `grab_scan` before that
+    pub fn adaptive_scan_timeout(&self) -> Duration {
+        match self.measured_rotation_frequency_hz() {
+            Some(frequency_hz) => {
+                let timeout = Duration::from_secs_f32(1f32 / frequency_hz) * RPLIDAR_ADAPTIVE_TIMEOUT_ROTATIONS;
+                return timeout.max(RPLIDAR_ADAPTIVE_TIMEOUT_MIN);
+            }
+            None => return RPLIDAR_DEFAULT_TIMEOUT * 5,
+        }
+    }
+
+    /// read scan frame
+    pub fn grab_scan_with_timeout(&mut self, timeout: Duration) -> Result<Vec<ScanPoint>> {
+        let mut out = Vec::<ScanPoint>::new();
+        self.fill_scan_with_timeout(&mut out, timeout)?;
+        return Ok(out);
+    }
+
+    /// accumulate scan points until `done(accumulated)` returns true (checked after each point), regardless of rotation boundaries
+    ///
+    /// It generalizes `grab_scan` for custom criteria of completeness, like angular coverage or detected features.
+    /// On timeout, the accumulated points are put back to the cache, so they are not lost
+    pub fn grab_scan_until<F: Fn(&[ScanPoint]) -> bool>(&mut self, done: F, timeout: Duration) -> Result<Vec<ScanPoint>> {
+        let deadline = Instant::now() + timeout;
+        let mut accumulated = Vec::new();
+
+        loop {
+            while let Some(point) = self.cached_measurement_nodes.pop_front() {
+                accumulated.push(point);
+
+                if done(&accumulated) {
+                    return Ok(accumulated);
+                }
+            }
+
+            let now = Instant::now();
+            let result = if now >= deadline {
+                Err(RposError::OperationTimeout.into())
+            } else {
+                self.wait_scan_data_with_timeout(deadline - now)
+            };
+
+            if let Err(err) = result {
+                for point in accumulated.into_iter().rev() {
+                    self.cached_measurement_nodes.push_front(point);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    /// read scan frame into the buffer, reusing its capacity
+    ///
+    /// The buffer is cleared first, so it contains only the new scan on success
+    pub fn fill_scan(&mut self, buf: &mut Vec<ScanPoint>) -> Result<()> {
+        self.fill_scan_with_timeout(buf, RPLIDAR_DEFAULT_TIMEOUT * 5)
+    }
+
+    /// read scan frame into the buffer with timeout, reusing its capacity
+    ///
+    /// The buffer is cleared first, so it contains only the new scan on success
+    pub fn fill_scan_with_timeout(&mut self, buf: &mut Vec<ScanPoint>, timeout: Duration) -> Result<()> {
+        self.fill_scan_with_stats(buf, None, timeout)
+    }
+
+    /// read scan frame with the statistics of valid points, computed while the points are taken from the cache
+    pub fn grab_scan_with_stats(&mut self) -> Result<(Vec<ScanPoint>, ScanStats)> {
+        let mut out = Vec::<ScanPoint>::new();
+        let mut accumulator = utils::ScanStatsAccumulator::new();
+        self.fill_scan_with_stats(&mut out, Some(&mut accumulator), RPLIDAR_DEFAULT_TIMEOUT * 5)?;
+        return Ok((out, accumulator.stats()));
+    }
+
+    /// read scan frame into the buffer, and accumulate the points into `stats` if any
+    fn fill_scan_with_stats(
+        &mut self,
+        buf: &mut Vec<ScanPoint>,
+        mut stats: Option<&mut utils::ScanStatsAccumulator>,
+        timeout: Duration,
+    ) -> Result<()> {
+        buf.clear();
+
+        let deadline = Instant::now() + timeout;
+
+        // sync flags within the first `min_points_per_scan` points are ignored to avoid tiny scans
+        let min_end = std::cmp::max(1, self.min_points_per_scan);
+        let mut end = min_end;
+
+        'outer_loop: loop {
+            if Instant::now() > deadline {
+                return Err(RposError::OperationTimeout.into());
+            }
+
+            if self.cached_measurement_nodes.len() <= end {
+                self.wait_scan_data_with_timeout(std::cmp::min(
+                    deadline.saturating_duration_since(Instant::now()),
+                    RPLIDAR_DEFAULT_TIMEOUT,
+                ))?;
+            }
+
+            for i in end..self.cached_measurement_nodes.len() {
+                if self.cached_measurement_nodes[i].is_sync() {
+                    end = i;
+                    break 'outer_loop;
+                }
+            }
+
+            end = std::cmp::max(min_end, self.cached_measurement_nodes.len());
+        }
+
+        buf.reserve(end);
+        for _ in 0..end {
+            if let Some(point) = self.cached_measurement_nodes.pop_front() {
+                if let Some(stats) = stats.as_mut() {
+                    stats.push(&point);
+                }
+                buf.push(point);
+            }
+        }
+
+        self.on_scan_grabbed(buf);
+
+        return Ok(());
+    }
+
+    /// when a full scan is grabbed
+    fn on_scan_grabbed(&mut self, scan: &[ScanPoint]) {
+        if self.recent_scan_fingerprints.len() >= RPLIDAR_SCAN_FINGERPRINT_HISTORY {
+            self.recent_scan_fingerprints.pop_front();
+        }
+        self.recent_scan_fingerprints.push_back(utils::scan_fingerprint(scan));
+        self.scan_sequence += 1;
+    }
+
+    /// when a point with sync flag is decoded, measure the period of the rotation it ends
+    ///
+    /// The period is the samples decoded since the last sync point times the sample duration of the active
+    /// scan mode, so it's the rotation of the device regardless of when the host reads the data.
+    /// The time between decoding the sync points is used if the sample duration is unknown
+    fn on_sync_decoded(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last) = self.last_sync_decoded_at {
+            let period = match &self.active_scan_mode {
+                Some(mode) if mode.us_per_sample > 0f32 => {
+                    Duration::from_secs_f64(mode.us_per_sample as f64 * self.samples_since_sync as f64 / 1000000f64)
+                }
+                _ => now.duration_since(last),
+            };
+            self.rotation_period = Some(period);
+
+            if self.recent_scan_periods.len() >= RPLIDAR_SCAN_PERIOD_HISTORY {
+                self.recent_scan_periods.pop_front();
+            }
+            self.recent_scan_periods.push_back(period);
+        }
+
+        self.last_sync_decoded_at = Some(now);
+        self.samples_since_sync = 0;
+    }
+
+    /// count of rotation boundaries (points with sync flag) decoded since the scan is started
+    ///
+    /// It's counted when the points are decoded, regardless of whether they are grabbed
+    pub fn rotation_count(&self) -> u64 {
+        return self.rotation_count;
+    }
+
+    /// reset the count of rotation boundaries to 0
+    pub fn reset_rotation_count(&mut self) {
+        self.rotation_count = 0;
+    }
+
+    /// Rotation frequency measured from the last rotation decoded (between the last two points with sync flag)
+    pub fn measured_rotation_frequency_hz(&self) -> Option<f32> {
+        return self
+            .rotation_period
+            .filter(|interval| *interval > Duration::from_secs(0))
+            .map(|interval| 1f32 / interval.as_secs_f32());
+    }
+
+    /// Standard deviation (in seconds) of the last `window` rotation periods decoded
+    ///
+    /// High jitter means unstable motor speed or delayed reads on the host. It's `None` before two periods
+    /// are measured, and up to 64 recent periods are kept
+    pub fn scan_period_jitter(&self, window: usize) -> Option<f32> {
+        let count = std::cmp::min(window, self.recent_scan_periods.len());
+        if count < 2 {
+            return None;
+        }
+
+        let periods: Vec<f32> = self
+            .recent_scan_periods
+            .iter()
+            .skip(self.recent_scan_periods.len() - count)
+            .map(|period| period.as_secs_f32())
+            .collect();
+
+        let mean = periods.iter().sum::<f32>() / (count as f32);
+        let variance = periods.iter().map(|period| (period - mean) * (period - mean)).sum::<f32>() / (count as f32);
+        return Some(variance.sqrt());
+    }
+
+    /// Check whether the scan dropped points
+    ///
+    /// The expected point count is calculated from the sample rate of `mode` and the measured
+    /// rotation frequency (10Hz is assumed before a full rotation is decoded)
+    pub fn validate_scan(&self, scan: &[ScanPoint], mode: &ScanMode) -> ScanValidation {
+        let frequency_hz = self
+            .measured_rotation_frequency_hz()
+            .unwrap_or(RPLIDAR_NOMINAL_ROTATION_FREQUENCY_HZ);
+
+        let expected_points = if mode.us_per_sample > 0f32 {
+            (1000000f32 / mode.us_per_sample / frequency_hz).round() as u32
+        } else {
+            0
+        };
+
+        let completeness = if expected_points == 0 {
+            1f32
+        } else {
+            ((scan.len() as f32) / (expected_points as f32)).min(1f32)
+        };
+
+        return ScanValidation {
+            completeness: completeness,
+            expected_points: expected_points,
+            actual_points: scan.len(),
+            has_sync_start: scan.first().map(|point| point.is_sync()).unwrap_or(false),
+        };
+    }
+
+    /// measure the scan frequency by grabbing `revolutions` full rotations of the running scan in `scan_mode`
+    ///
+    /// Rotations with implausibly low point counts (less than half of the points at the max rotation speed)
+    /// have dropped data, they are skipped and more rotations are read instead
+    pub fn measure_scan_frequency(&mut self, scan_mode: &ScanMode, revolutions: u32) -> Result<MeasuredScanFrequency> {
+        let timeout = RPLIDAR_DEFAULT_TIMEOUT * 5 * (revolutions + 1);
+        self.measure_scan_frequency_with_timeout(scan_mode, revolutions, timeout)
+    }
+
+    /// measure the scan frequency by grabbing `revolutions` full rotations with timeout
+    ///
+    /// Fails with `OperationTimeout` if the clean rotations could not be collected before the timeout
+    pub fn measure_scan_frequency_with_timeout(
+        &mut self,
+        scan_mode: &ScanMode,
+        revolutions: u32,
+        timeout: Duration,
+    ) -> Result<MeasuredScanFrequency> {
+        if revolutions == 0 || scan_mode.us_per_sample <= 0f32 {
+            return Err(RposError::OperationFail {
+                description: "invalid revolutions or sample rate to measure scan frequency".to_owned(),
+            }
+            .into());
+        }
+
+        let (_, max_hz) = self
+            .rotation_speed_range_hz
+            .unwrap_or(RPLIDAR_DEFAULT_ROTATION_SPEED_RANGE_HZ);
+        let min_points = (1000000f32 / scan_mode.us_per_sample / max_hz * RPLIDAR_MIN_PLAUSIBLE_POINTS_RATIO) as usize;
+
+        let deadline = Instant::now() + timeout;
+        let mut clean_revolutions = 0u32;
+        let mut total_points = 0usize;
+
+        while clean_revolutions < revolutions {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Err(RposError::OperationTimeout.into());
+            }
+
+            let rotation = self.grab_scan_frame_with_timeout(remaining)?;
+            if rotation.len() >= min_points {
+                clean_revolutions += 1;
+                total_points += rotation.len();
+            }
+        }
+
+        let points_per_rev = (total_points as f32) / (revolutions as f32);
+
+        return Ok(MeasuredScanFrequency {
+            hz: 1000000f32 / (scan_mode.us_per_sample * points_per_rev),
+            points_per_rev: points_per_rev.round() as u32,
+        });
+    }
+
+    /// read scan frame with the time span it's measured in, for sensor fusion
+    ///
+    /// The `start` of each frame is strictly later than the previous one, even if the estimated start is not
+    /// (e.g. the sample rate is not exact), as fusion frameworks reject out-of-order data
+    pub fn grab_scan_monotonic(&mut self) -> Result<ScanFrame> {
+        let points = self.grab_scan()?;
+        let end = Instant::now();
+
+        let measured_in = match self.active_scan_mode {
+            Some(ref mode) if mode.us_per_sample > 0f32 => {
+                Duration::from_secs_f32(mode.us_per_sample * (points.len() as f32) / 1000000f32)
+            }
+            _ => Duration::from_secs(0),
+        };
+
+        let mut start = end.checked_sub(measured_in).unwrap_or(end);
+        if let Some(last_start) = self.last_frame_start {
+            if start <= last_start {
+                start = last_start + RPLIDAR_MIN_FRAME_START_INCREMENT;
+            }
+        }
+        self.last_frame_start = Some(start);
+
+        return Ok(ScanFrame {
+            points: points,
+            start: start,
+            end: std::cmp::max(start, end),
+        });
+    }
+
+    /// read scan frame with the scan mode, estimated frequency, timestamp and sequence number attached
+    pub fn grab_scan_full(&mut self) -> Result<Scan> {
+        let mode = match self.active_scan_mode {
+            Some(ref mode) => mode.clone(),
+            None => return Err(RposError::OperationFail { description: "scan is not started".to_owned() }.into()),
+        };
+
+        let points = self.grab_scan()?;
+        let frequency_hz = if points.is_empty() || mode.us_per_sample <= 0f32 {
+            None
+        } else {
+            Some(1000000f32 / (mode.us_per_sample * (points.len() as f32)))
+        };
+
+        return Ok(Scan {
+            points: points,
+            mode: mode,
+            frequency_hz: frequency_hz,
+            timestamp: Instant::now(),
+            sequence: self.scan_sequence,
+        });
+    }
+
+    /// grab `num_scans` rotations, and append them to the writer in the binary scan format
+    ///
+    /// The recorded scans could be read back with `utils::read_scan_bin`
+    pub fn record_to<W: Write>(&mut self, writer: &mut W, num_scans: usize) -> Result<()> {
+        for i in 0..num_scans {
+            let scan = self.grab_scan_full()?;
+            utils::write_scan_bin(writer, &scan)?;
+
+            if (i + 1) % RPLIDAR_RECORD_FLUSH_INTERVAL == 0 {
+                writer.flush()?;
+            }
+        }
+
+        writer.flush()?;
+        return Ok(());
+    }
+
+    /// Check if the last `window` grabbed scans are exactly the same
+    ///
+    /// Measurement noise makes identical scans very unlikely even if the LIDAR is stationary,
+    /// so this usually means the firmware is frozen and keeps sending the same rotation.
+    /// `window` should be at least 2 and no more than 64
+    pub fn detect_frozen_scan(&mut self, window: usize) -> bool {
+        if window < 2 || self.recent_scan_fingerprints.len() < window {
+            return false;
+        }
+
+        let mut recent = self.recent_scan_fingerprints.iter().rev().take(window);
+        let last = recent.next();
+        return recent.all(|fingerprint| Some(fingerprint) == last);
+    }
+
+    /// grab a scan split into `bins` bins, and return `(bin_index, range_m)` of the bins changed over `threshold_m`
+    /// since the last call, to stream mostly static scenes with little bandwidth
+    ///
+    /// Every bin is returned on the first call (or when `bins` changes). Bins without valid returns have range 0
+    pub fn scan_delta_stream(&mut self, bins: usize, threshold_m: f32) -> Result<Vec<(u16, f32)>> {
+        let scan = self.grab_scan()?;
+        let ranges: Vec<f32> = utils::bin_scan(&scan, bins).iter().map(|bin| bin.unwrap_or(0f32)).collect();
+
+        let full_frame = self.delta_stream_ranges.len() != ranges.len();
+        let deltas = ranges
+            .iter()
+            .enumerate()
+            .filter(|(i, range)| full_frame || (*range - self.delta_stream_ranges[*i]).abs() > threshold_m)
+            .map(|(i, range)| (i as u16, *range))
+            .collect();
+
+        self.delta_stream_ranges = ranges;
+        return Ok(deltas);
+    }
+
+    /// grab `scans` scans, and find the bins (360 degrees split into `bins` bins) without valid returns in every scan
+    ///
+    /// Persistent dropouts usually mean dirty optics, cracked covers or occlusions.
+    /// Only the scans grabbed before any error are taken into account, and nothing is reported if no scan is grabbed
+    pub fn persistent_dropout_sectors(&mut self, scans: usize, bins: usize) -> Vec<usize> {
+        let mut dropouts: Option<Vec<bool>> = None;
+
+        for _ in 0..scans {
+            let scan = match self.grab_scan() {
+                Ok(scan) => scan,
+                Err(_) => break,
+            };
+
+            let empty = utils::bin_scan(&scan, bins).into_iter().map(|bin| bin.is_none());
+            dropouts = Some(match dropouts {
+                Some(dropouts) => dropouts.iter().zip(empty).map(|(was_empty, empty)| *was_empty && empty).collect(),
+                None => empty.collect(),
+            });
+        }
+
+        return dropouts
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .filter(|(_, empty)| **empty)
+            .map(|(bin, _)| bin)
+            .collect();
+    }
+
+    /// read scan frame, and pair each point with its cartesian coordinates `(x_mm, y_mm)`
+    pub fn grab_scan_xy(&mut self) -> Result<Vec<(ScanPoint, (f32, f32))>> {
+        let scan = self.grab_scan()?;
+        return Ok(scan
+            .into_iter()
+            .map(|point| {
+                let xy = point.to_cartesian();
+                (point, xy)
+            })
+            .collect());
+    }
+
+    /// Get LIDAR health information
+    pub fn get_device_health(&mut self) -> Result<Health> {
+        self.get_device_health_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// Get LIDAR health information
+    pub fn get_device_health_with_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Health> {
+        self.check_not_in_standby()?;
+
+        if let Some(msg) = self
+            .channel
+            .invoke(&Message::new(RPLIDAR_CMD_GET_DEVICE_HEALTH), timeout)?
+        {
+            let resp = handle_resp!(RPLIDAR_ANS_TYPE_DEVHEALTH, msg, RplidarResponseDeviceHealth)?;
+
+            return Ok(match resp.status {
+                RPLIDAR_HEALTH_STATUS_OK => Health::Healthy,
+                RPLIDAR_HEALTH_STATUS_WARNING => Health::Warning(resp.error_code),
+                RPLIDAR_HEALTH_STATUS_ERROR => Health::Error(resp.error_code),
+                _ => Health::Healthy
+            });
+        }
+
+        return Err(RposError::OperationTimeout.into());
+    }
+
+    /// Get sample durations (in microseconds) of standard and express scan
+    ///
+    /// Works on firmwares before `GET_LIDAR_CONF` is supported (added in fw 1.17)
+    pub fn get_sample_rate(&mut self) -> Result<RplidarResponseSampleRate> {
+        self.get_sample_rate_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// Get sample durations (in microseconds) of standard and express scan with timeout
+    pub fn get_sample_rate_with_timeout(&mut self, timeout: Duration) -> Result<RplidarResponseSampleRate> {
+        self.check_not_in_standby()?;
+
+        if let Some(msg) = self
+            .channel
+            .invoke(&Message::new(RPLIDAR_CMD_GET_SAMPLERATE), timeout)?
+        {
+            return handle_resp!(RPLIDAR_ANS_TYPE_SAMPLE_RATE, msg, RplidarResponseSampleRate);
+        }
+
+        return Err(RposError::OperationTimeout.into());
+    }
+
+    /// Check if the connected LIDAR supports motor control
+    pub fn check_motor_ctrl_support(&mut self) -> Result<bool> {
+        self.check_motor_ctrl_support_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// Check if the connected LIDAR supports motor control with timeout
+    pub fn check_motor_ctrl_support_with_timeout(&mut self, timeout: Duration) -> Result<bool> {
+        return Ok(self.get_acc_board_flag_with_timeout(timeout)?.motor_ctrl);
+    }
+
+    /// Check if the accessory board of the LIDAR supports motor control
+    pub fn is_motor_control_supported(&mut self) -> Result<bool> {
+        self.check_motor_ctrl_support()
+    }
+
+    /// Get the features supported by the accessory board
+    pub fn get_acc_board_flag(&mut self) -> Result<AccBoardFlag> {
+        self.get_acc_board_flag_with_timeout(RPLIDAR_DEFAULT_TIMEOUT)
+    }
+
+    /// Get the features supported by the accessory board with timeout
+    ///
+    /// The flag is kept to check the support of `set_motor_pwm`
+    pub fn get_acc_board_flag_with_timeout(&mut self, timeout: Duration) -> Result<AccBoardFlag> {
+        self.check_not_in_standby()?;
+
+        let mut data = [0u8; 4];
+        LittleEndian::write_u32(&mut data, 0u32);
+
+        let resp_msg = self.channel.invoke(&Message::with_data(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &data), timeout)?;
+
+        if let Some(msg) = resp_msg {
+            let acc_board_flag = AccBoardFlag::from_flags(handle_resp!(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, msg, u32)?);
+            self.acc_board_flag = Some(acc_board_flag);
+            return Ok(acc_board_flag);
+        } else {
+            return Err(RposError::OperationTimeout.into());
+        }
+    }
+
+    /// Make `set_motor_pwm` fail with `OperationNotSupport` if the accessory board doesn't support motor control
+    /// (disabled by default). The flag is queried once with `get_acc_board_flag`
+    pub fn set_check_motor_pwm_support(&mut self, enabled: bool) {
+        self.check_motor_pwm_support = enabled;
+    }
+
+    /// Gather device info, health, supported scan modes and capabilities into a JSON string
+    ///
+    /// Attach it to support tickets to describe the unit
+    pub fn diagnostic_json(&mut self) -> Result<String> {
+        let device_info = self.get_device_info()?;
+        let health = self.get_device_health()?;
+        let scan_modes = self.get_all_supported_scan_modes()?;
+        let capabilities = self.capabilities();
+
+        let serial = device_info.serial_number();
+        let (health_status, health_error_code) = match health {
+            Health::Healthy => ("healthy", 0),
+            Health::Warning(code) => ("warning", code),
+            Health::Error(code) => ("error", code),
+        };
+
+        let modes: Vec<String> = scan_modes
+            .iter()
+            .map(|mode| format!("{{{}}}", scan_mode_json_fields(mode)))
+            .collect();
+
+        let mut json = String::new();
+        json += &format!(
+            "{{\"device_info\":{{\"model\":{},\"model_id\":{},\"firmware_version\":\"{}.{:02}\",\"hardware_version\":{},\"serial_number\":\"{}\"}},",
+            json_string(&format!("{:?}", device_info.model())),
+            device_info.model,
+            device_info.firmware_version >> 8,
+            device_info.firmware_version & 0xff,
+            device_info.hardware_version,
+            serial
+        );
+        json += &format!(
+            "\"health\":{{\"status\":\"{}\",\"error_code\":{}}},",
+            health_status, health_error_code
+        );
+        json += &format!("\"scan_modes\":[{}],", modes.join(","));
+        json += &format!(
+            "\"capabilities\":{{\"has_get_lidar_conf\":{},\"has_health\":{},\"has_accessory_board\":{},\"has_motor_ctrl\":{},\"has_express\":{}}}}}",
+            capabilities.has_get_lidar_conf,
+            capabilities.has_health,
+            capabilities.has_accessory_board,
+            capabilities.has_motor_ctrl,
+            capabilities.has_express
+        );
+
+        return Ok(json);
+    }
+
+    /// Export the supported scan modes as JSON, for mode pickers of configuration UIs
+    ///
+    /// Each mode has its sample rate (samples per second) and whether it's the typical mode
+    pub fn scan_modes_json(&mut self) -> Result<String> {
+        let scan_modes = self.get_all_supported_scan_modes()?;
+        let typical_mode = self.get_typical_scan_mode()?;
+
+        let modes: Vec<String> = scan_modes
+            .iter()
+            .map(|mode| {
+                format!(
+                    "{{{},\"sample_rate\":{},\"typical\":{}}}",
+                    scan_mode_json_fields(mode),
+                    1000000f32 / mode.us_per_sample,
+                    mode.id == typical_mode
+                )
+            })
+            .collect();
+
+        return Ok(format!("{{\"typical_mode\":{},\"scan_modes\":[{}]}}", typical_mode, modes.join(",")));
+    }
+
+    /// Check if `set_motor_pwm` has any effect on the connected LIDAR
+    ///
+    /// PWM is ignored on Ethernet transport and by LIDARs with speed controlled motors (ToF models and C1),
+    /// otherwise it requires an accessory board with motor control
+    pub fn supports_pwm_motor_control(&mut self) -> bool {
+        if self.transport_kind == TransportKind::Ethernet {
+            return false;
+        }
+
+        let timeout = RPLIDAR_CAPABILITY_PROBE_TIMEOUT;
+        if let Ok(device_info) = self.probe(|device| device.get_device_info_with_timeout(timeout)) {
+            match device_info.model() {
+                Model::A1 | Model::A2 | Model::A3 | Model::Unknown(_) => {}
+                _ => return false,
+            }
+        }
+
+        return self.capabilities().has_motor_ctrl;
+    }
+
+    /// Probe commands and features supported by the LIDAR
+    ///
+    /// Each command is probed with a short timeout, and failures are treated as not supported.
+    /// The result is cached, so only the first call communicates with the LIDAR
+    pub fn capabilities(&mut self) -> Capabilities {
+        if let Some(capabilities) = self.capabilities {
+            return capabilities;
+        }
+
+        let timeout = RPLIDAR_CAPABILITY_PROBE_TIMEOUT;
+        let mut capabilities = Capabilities::default();
+
+        if let Ok(device_info) = self.probe(|device| device.get_device_info_with_timeout(timeout)) {
+            capabilities.has_express = device_info.model >= 0x20u8
+                && device_info.firmware_version >= RPLIDAR_EXPRESS_SCAN_START_VERSION;
+        }
+
+        capabilities.has_health = self
+            .probe(|device| device.get_device_health_with_timeout(timeout))
+            .is_ok();
+
+        capabilities.has_get_lidar_conf = self
+            .probe(|device| device.get_scan_mode_count_with_timeout(timeout))
+            .is_ok();
+
+        if let Ok(motor_ctrl_support) = self.probe(|device| device.check_motor_ctrl_support_with_timeout(timeout)) {
+            capabilities.has_accessory_board = true;
+            capabilities.has_motor_ctrl = motor_ctrl_support;
+        }
+
+        self.capabilities = Some(capabilities);
+        return capabilities;
+    }
+
+    /// run probing command, and reset the channel if the command failed
+    fn probe<R, F>(&mut self, command: F) -> Result<R>
+    where
+        F: FnOnce(&mut RplidarDevice<T>) -> Result<R>,
+    {
+        let result = command(self);
+        if result.is_err() {
+            self.channel.reset();
+        }
+        return result;
+    }
+}
+
+/// Iterator over scan points of `RplidarDevice`, created by `iter_scan_points`
+pub struct ScanPointIterator<'a, T: ?Sized> {
+    device: &'a mut RplidarDevice<T>,
+    timeout: Duration,
+}
+
+impl<'a, T: ?Sized> ScanPointIterator<'a, T> {
+    /// set the timeout of waiting for each point
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<'a, T: ?Sized> Iterator for ScanPointIterator<'a, T>
+where
+    T: Read + Write,
+{
+    type Item = Result<ScanPoint>;
+
+    fn next(&mut self) -> Option<Result<ScanPoint>> {
+        return Some(self.device.grab_scan_point_with_timeout(self.timeout));
+    }
+}
+
+/// the request to start scan in `scan_mode`, and whether it's the legacy or express command
+fn scan_start_request(scan_mode: u16, options: &ScanOptions) -> (ScanPath, Message) {
+    if scan_mode == 0 {
+        let cmd = if options.force_scan { RPLIDAR_CMD_FORCE_SCAN } else { RPLIDAR_CMD_SCAN };
+        return (ScanPath::Legacy, Message::new(cmd));
+    }
+
+    let payload = RplidarPayloadExpressScan {
+        work_mode: scan_mode as u8,
+        work_flags: options.options as u16,
+        param: 0,
+    };
+
+    let mut data = [0u8; 5];
+    data[0] = payload.work_mode;
+    LittleEndian::write_u16(&mut data[1..3], payload.work_flags);
+    LittleEndian::write_u16(&mut data[3..5], payload.param);
+    return (ScanPath::Express, Message::with_data(RPLIDAR_CMD_EXPRESS_SCAN, &data));
+}
+
+/// the request of getting lidar config of `config_type` with parameter
+fn lidar_conf_request(config_type: u32, param: &[u8]) -> Message {
+    let mut msg = Message::with_data(RPLIDAR_CMD_GET_LIDAR_CONF, &[0; 4]);
+
+    LittleEndian::write_u32(&mut msg.data, config_type);
+    msg.data.extend_from_slice(param);
+    return msg;
+}
+
+/// validate the answer of getting lidar config of `config_type`, and take the config value
+fn parse_lidar_conf_answer(config_type: u32, mut response_msg: Message) -> Result<Vec<u8>> {
+    if response_msg.data.is_empty() {
+        return Err(empty_message_error());
+    } else if response_msg.cmd != RPLIDAR_ANS_TYPE_GET_LIDAR_CONF {
+        return Err(answer_mismatch_error(response_msg.cmd));
+    } else if response_msg.data.len() < 4 || LittleEndian::read_u32(&response_msg.data[0..4]) != config_type {
+        return Err(RposError::OperationFail { description: "answer config type mismatch".to_owned() }.into());
+    } else {
+        return Ok(response_msg.data.split_off(4));
+    }
+}
+
+/// parse scan mode name from lidar config value
+///
+/// Some firmwares report names with invalid characters, which should not break the enumeration of scan modes,
+/// and some pad names with spaces instead of nulls
+fn parse_scan_mode_name(data: &[u8]) -> String {
+    let name = String::from_utf8_lossy(data);
+    return name.trim_matches(|c| c == '\0' || c == ' ').to_owned();
+}
+
+/// parse scan mode count, which is 2 bytes on most firmwares but 4 bytes on some
+fn parse_scan_mode_count(data: &[u8]) -> Result<u16> {
+    match data.len() {
+        2 => return Ok(LittleEndian::read_u16(data)),
+        4 if LittleEndian::read_u32(data) <= u16::MAX as u32 => return Ok(LittleEndian::read_u32(data) as u16),
+        _ => return Err(RposError::OperationFail { description: "answer type mismatch".to_owned() }.into()),
+    }
+}
+
+/// format fields of scan mode as JSON object members
+fn scan_mode_json_fields(mode: &ScanMode) -> String {
+    return format!(
+        "\"id\":{},\"name\":{},\"us_per_sample\":{},\"max_distance\":{},\"ans_type\":{}",
+        mode.id,
+        json_string(&mode.name),
+        mode.us_per_sample,
+        mode.max_distance,
+        mode.ans_type
+    );
+}
+
+/// quote and escape a string for JSON
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    return quoted;
+}
+
+/// check if the error is caused by timeout
+fn is_timeout(err: &Error) -> bool {
+    return matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationTimeout));
+}
+
+fn is_protocol_error(err: &Error) -> bool {
+    return matches!(err.downcast_ref::<RposError>(), Some(RposError::ProtocolError { .. }));
+}
+
+
+/// count of samples carried in an answer of `ans_type` (they are decoded together)
+fn samples_per_answer(ans_type: u8) -> u32 {
+    match ans_type {
+        RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED => return 32,
+        RPLIDAR_ANS_TYPE_MEASUREMENT_HQ => return 16,
+        RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA => return 96,
+        RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED => return 40,
+        _ => return 1,
+    }
+}
+
+/// the error when an answer of `ans_type` is received where another answer type is expected
+///
+/// There is no explicit busy answer in the protocol, but measurement answers received for other commands
+/// mean the LIDAR is still streaming (e.g. for another session), which is reported as `DeviceBusy`
+fn answer_mismatch_error(ans_type: u8) -> Error {
+    match ans_type {
+        RPLIDAR_ANS_TYPE_MEASUREMENT
+        | RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED
+        | RPLIDAR_ANS_TYPE_MEASUREMENT_HQ
+        | RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA
+        | RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED => RposError::DeviceBusy.into(),
+        _ => RposError::OperationFail { description: "answer type mismatch".to_owned() }.into(),
+    }
+}
+
+/// the error when the checksum of a capsule does not match
+fn checksum_mismatch_error() -> Error {
+    RposError::ProtocolError { description: "checksum mismatch".to_owned() }.into()
+}
+
+/// the error when a message without payload is received where payload is expected
+fn empty_message_error() -> Error {
+    RposError::ProtocolError { description: "empty message".to_owned() }.into()
+}
+
+/// validate the check bit (always 1) and the sync bits (the inverted sync bit is the complement of the sync bit)
+fn check_measurement_node(node: &RplidarResponseMeasurementNode) -> Result<()> {
+    if (node.angle_q6_checkbit & RPLIDAR_RESP_MEASUREMENT_CHECKBIT) == 0 {
+        return Err(RposError::ProtocolError { description: "check bit mismatch".to_owned() }.into());
+    }
+
+    let sync = (node.sync_quality & RPLIDAR_RESP_MEASUREMENT_SYNCBIT) != 0;
+    let inverted_sync = (node.sync_quality & RPLIDAR_RESP_MEASUREMENT_INVERTED_SYNCBIT) != 0;
+    if sync == inverted_sync {
+        return Err(RposError::ProtocolError { description: "sync bit mismatch".to_owned() }.into());
+    }
+
+    return Ok(());
+}
+
+/// verify the sync bits and XOR checksum of a capsuled or dense capsuled measurement message
+///
+/// # Example
+/// ```ignore
+/// for msg in recorded_capsules {
+///     verify_capsule_checksum(&msg)?;
+/// }
+/// ```
+pub fn verify_capsule_checksum(msg: &Message) -> Result<()> {
+    if !check_sync_and_checksum(msg, &XorChecksumVerifier)? {
+        return Err(checksum_mismatch_error());
+    }
+
+    return Ok(());
+}
+
+/// check the sync bits of a capsule, returns whether its checksum matches
+fn check_sync_and_checksum(msg: &Message, verifier: &dyn ChecksumVerifier) -> Result<bool> {
+    if msg.data.len() < 2 {
+        return Err(RposError::ProtocolError { description: "data too short".to_owned() }.into());
+    }
+
+    if (msg.data[0] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 {
+        return Err(RposError::ProtocolError { description: "miss sync 1".to_owned() }.into());
+    }
+
+    if (msg.data[1] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 {
+        return Err(RposError::ProtocolError { description: "miss sync 2".to_owned() }.into());
+    }
+
+    let recv_checksum = (msg.data[0] & 0xf) | (msg.data[1] << 4);
+
+    return Ok(verifier.verify(&msg.data[2..], recv_checksum));
+}
+
+/// check the length and sync byte of a HQ capsule, returns whether its CRC32 checksum matches
+fn check_sync_and_checksum_hq(msg: &Message) -> Result<bool> {
+    if msg.data.len() != RplidarResponseHqCapsuledMeasurementNodes::SIZE {
+        return Err(RposError::ProtocolError { description: "data length mismatch".to_owned() }.into());
+    }
+
+    if msg.data[0] != RPLIDAR_RESP_MEASUREMENT_HQ_SYNC {
+        return Err(RposError::ProtocolError { description: "sync mismatch".to_owned() }.into());
+    }
+
+    let checksum = crc32::checksum_ieee(&msg.data[0..msg.data.len()-4]);
+    let recv_checksum = LittleEndian::read_u32(&msg.data[msg.data.len()-4..msg.data.len()]);
+
+    return Ok(checksum == recv_checksum);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Checksum;
+    use rpos_drv::ProtocolEncoder;
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    /// A fake stream which replays canned bytes and records written bytes
+    pub struct MockStream {
+        pub rx: VecDeque<u8>,
+        pub tx: Rc<RefCell<Vec<u8>>>,
+
+        /// count of the next requests which will not be answered
+        pub muted_requests: usize,
+        muted: bool,
+
+        /// fail reads with `TimedOut` instead of reading nothing when `rx` is empty, like serial ports
+        pub timeout_when_empty: bool,
+
+        /// count of read calls
+        pub reads: usize,
+    }
+
+    impl MockStream {
+        pub fn new(rx: &[u8]) -> MockStream {
+            MockStream {
+                rx: rx.iter().cloned().collect(),
+                tx: Rc::new(RefCell::new(Vec::new())),
+                muted_requests: 0,
+                muted: false,
+                timeout_when_empty: false,
+                reads: 0,
+            }
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+
+            if self.timeout_when_empty && (self.muted || self.rx.is_empty()) {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out"));
+            }
+
+            if self.muted {
+                return Ok(0);
+            }
+
+            let read = std::cmp::min(buf.len(), self.rx.len());
+            for (i, byte) in self.rx.drain(..read).enumerate() {
+                buf[i] = byte;
+            }
+            return Ok(read);
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.muted = self.muted_requests > 0;
+            self.muted_requests = self.muted_requests.saturating_sub(1);
+            self.tx.borrow_mut().extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// encode an answer header followed by the payload
+    pub fn answer(ans_type: u8, payload: &[u8], is_loop: bool) -> Vec<u8> {
+        let mut size_q30_subtype = payload.len() as u32;
+        if is_loop {
+            size_q30_subtype |= 1 << 30;
+        }
+
+        let mut bytes = vec![0xA5, 0x5A, 0, 0, 0, 0, ans_type];
+        LittleEndian::write_u32(&mut bytes[2..6], size_q30_subtype);
+        bytes.extend_from_slice(payload);
+        return bytes;
+    }
+
+    /// encode a hq capsule with 16 nodes evenly spread from `start_angle_z_q14`
+    pub fn hq_capsule(start_angle_z_q14: u16, dist_mm_q2: u32) -> Vec<u8> {
+        let mut bytes = vec![RPLIDAR_RESP_MEASUREMENT_HQ_SYNC];
+        bytes.extend_from_slice(&[0; 8]);
+
+        for i in 0..16u16 {
+            let mut node = [0u8; 8];
+            LittleEndian::write_u16(&mut node[0..2], start_angle_z_q14 + i * 16);
+            LittleEndian::write_u32(&mut node[2..6], dist_mm_q2);
+            node[6] = 0xbc;
+            node[7] = 0;
+            bytes.extend_from_slice(&node);
+        }
+
+        let crc = crc32::checksum_ieee(&bytes);
+        let mut crc_bytes = [0u8; 4];
+        LittleEndian::write_u32(&mut crc_bytes, crc);
+        bytes.extend_from_slice(&crc_bytes);
+        return bytes;
+    }
+
+    /// encode a hq capsule whose first node has sync flag
+    pub fn hq_sync_capsule(start_angle_z_q14: u16, dist_mm_q2: u32) -> Vec<u8> {
+        let mut bytes = hq_capsule(start_angle_z_q14, dist_mm_q2);
+        bytes[1 + 8 + 7] = RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
+
+        let len = bytes.len();
+        let crc = crc32::checksum_ieee(&bytes[0..len - 4]);
+        LittleEndian::write_u32(&mut bytes[len - 4..len], crc);
+        return bytes;
+    }
+
+    /// encode the answer of get lidar conf
+    pub fn conf_answer(config_type: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; 4];
+        LittleEndian::write_u32(&mut data, config_type);
+        data.extend_from_slice(payload);
+        return answer(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, &data, false);
+    }
+
+    /// encode the answers of querying a scan mode (in the order of `get_scan_mode_with_timeout`)
+    pub fn scan_mode_answers(us_per_sample: f32, max_distance: f32, ans_type: u8, name: &[u8]) -> Vec<u8> {
+        let mut q8 = [0u8; 4];
+        let mut bytes = Vec::new();
+
+        LittleEndian::write_u32(&mut q8, (us_per_sample * 256f32) as u32);
+        bytes.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, &q8));
+        LittleEndian::write_u32(&mut q8, (max_distance * 256f32) as u32);
+        bytes.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, &q8));
+        bytes.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, &[ans_type]));
+        bytes.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_NAME, name));
+        return bytes;
+    }
+
+    pub fn mock_device(rx: &[u8]) -> RplidarDevice<MockStream> {
+        RplidarDevice::with_stream(Box::new(MockStream::new(rx)))
+    }
+
+    /// create a mocked device and the handle to the bytes written to it
+    pub fn mock_device_with_tx(rx: &[u8]) -> (RplidarDevice<MockStream>, Rc<RefCell<Vec<u8>>>) {
+        let stream = MockStream::new(rx);
+        let tx = stream.tx.clone();
+        return (RplidarDevice::with_stream(Box::new(stream)), tx);
+    }
+
+    #[test]
+    fn sample_rate_durations() {
+        let (mut rplidar, tx) = mock_device_with_tx(&answer(RPLIDAR_ANS_TYPE_SAMPLE_RATE, &[0xf4, 0x01, 0xfa, 0x00], false));
+
+        let sample_rate = rplidar.get_sample_rate().unwrap();
+        assert_eq!({ sample_rate.std_sample_duration_us }, 500);
+        assert_eq!({ sample_rate.express_sample_duration_us }, 250);
+        assert_eq!(tx.borrow()[..2], [0xa5, RPLIDAR_CMD_GET_SAMPLERATE]);
+    }
+
+    #[test]
+    fn device_health_error_code() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[RPLIDAR_HEALTH_STATUS_ERROR, 0x34, 0x12], false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[RPLIDAR_HEALTH_STATUS_WARNING, 0x01, 0x00], false));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.get_device_health().unwrap(), Health::Error(0x1234));
+        assert_eq!(rplidar.get_device_health().unwrap(), Health::Warning(0x0001));
+    }
+
+    #[test]
+    fn access_transport() {
+        let (mut rplidar, tx) = mock_device_with_tx(&[]);
+        assert!(Rc::ptr_eq(&rplidar.transport().tx, &tx));
+
+        rplidar.transport_mut().rx.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        assert_eq!(rplidar.get_device_health().unwrap(), Health::Healthy);
+    }
+
+    #[test]
+    fn invoke_and_return_ans_type_reports_raw_answer() {
+        let (mut rplidar, tx) = mock_device_with_tx(&answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+
+        let (ans_type, data) = rplidar
+            .invoke_and_return_ans_type(&Message::new(RPLIDAR_CMD_GET_DEVICE_HEALTH), Duration::from_millis(10))
+            .unwrap();
+
+        assert_eq!(ans_type, RPLIDAR_ANS_TYPE_DEVHEALTH);
+        assert_eq!(data, vec![0, 0, 0]);
+        assert_eq!(*tx.borrow(), vec![0xA5, RPLIDAR_CMD_GET_DEVICE_HEALTH]);
+    }
+
+    #[test]
+    fn peek_then_grab_same_point() {
+        let mut rplidar = mock_device(&answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(32, 4000), true));
+
+        let peeked = rplidar.peek_scan_point().unwrap().cloned().unwrap();
+        assert_eq!(peeked.angle_z_q14, 32);
+        assert_eq!(rplidar.peek_scan_point().unwrap(), Some(&peeked));
+        assert_eq!(rplidar.grab_scan_point().unwrap(), peeked);
+        assert_eq!(rplidar.peek_scan_point().unwrap().unwrap().angle_z_q14, 48);
+    }
+
+    #[test]
+    fn peek_scan_point_errors() {
+        // no data before timeout
+        let mut rplidar = mock_device(&[]);
+        assert_eq!(rplidar.peek_scan_point().unwrap(), None);
+
+        // a broken link is not reported as no data
+
+        let mut rplidar = mock_device(&misaligned_hq_answer());
+        assert!(is_protocol_error(&rplidar.peek_scan_point().unwrap_err(), "sync mismatch"));
+    }
+
+    pub fn device_info_payload(model: u8, firmware_version: u16) -> Vec<u8> {
+        let mut payload = vec![model, 0, 0, 5];
+        LittleEndian::write_u16(&mut payload[1..3], firmware_version);
+        payload.extend((0..16).map(|i| i as u8));
+        return payload;
+    }
+
+    #[test]
+    fn streaming_device_is_busy() {
+        let mut rplidar = mock_device(&answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true));
+        let err = rplidar.get_device_info().unwrap_err();
+        assert!(matches!(err.downcast_ref::<RposError>(), Some(RposError::DeviceBusy)));
+
+        let mut rplidar = mock_device(&answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        let err = rplidar.get_device_info().unwrap_err();
+        assert!(matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationFail { .. })));
+    }
+
+    #[test]
+    fn pending_bytes_of_unread_capsules() {
+        let mut rx = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        for i in 1..4 {
+            rx.extend(hq_capsule(i * 1024, 4000));
+        }
+
+        let mut rplidar = mock_device(&rx);
+        assert_eq!(rplidar.pending_bytes(), 0);
+
+        rplidar.channel.read().unwrap().unwrap();
+        let pending = rplidar.pending_bytes();
+        assert_eq!(pending, hq_capsule(0, 4000).len() * 3);
+
+        for i in 4..8 {
+            rplidar.transport_mut().rx.extend(hq_capsule(i * 1024, 4000));
+        }
+
+        rplidar.channel.read().unwrap().unwrap();
+        assert!(rplidar.pending_bytes() > pending);
+    }
+
+    /// stream answering `rx` only after `delay` since the last request
+    struct DelayedStream {
+        stream: MockStream,
+        delay: Duration,
+        requested_at: Option<Instant>,
+    }
+
+    impl Read for DelayedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.requested_at {
+                Some(requested_at) if requested_at.elapsed() >= self.delay => return self.stream.read(buf),
+                _ => return Ok(0),
+            }
+        }
+    }
+
+    impl Write for DelayedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.requested_at = Some(Instant::now());
+            return self.stream.write(buf);
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// stream appending the next reply to `rx` on each request
+    struct ReplyingStream {
+        stream: MockStream,
+        replies: VecDeque<Vec<u8>>,
+    }
+
+    impl Read for ReplyingStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            return self.stream.read(buf);
+        }
+    }
+
+    impl Write for ReplyingStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if let Some(reply) = self.replies.pop_front() {
+                self.stream.rx.extend(reply);
+            }
+            return self.stream.write(buf);
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// hq scan answer whose capsule is shifted by garbage bytes, so the following capsules are misaligned
+    fn misaligned_hq_answer() -> Vec<u8> {
+        let mut data = vec![0x12, 0x34, 0x56];
+        data.extend(hq_capsule(0, 4000));
+        data.extend(hq_capsule(256, 4000));
+
+        let capsule_len = RplidarResponseHqCapsuledMeasurementNodes::SIZE;
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &data[..capsule_len], true);
+        stream.extend_from_slice(&data[capsule_len..]);
+        return stream;
+    }
+
+    #[test]
+    fn reset_scan_state_after_corruption() {
+        let mut rplidar = mock_device(&misaligned_hq_answer());
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "sync mismatch"));
+
+        rplidar.reset_scan_state();
+        assert_eq!(rplidar.pending_bytes(), 0);
+
+        rplidar.transport_mut().rx.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(256, 4000), true));
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 256);
+    }
+
+    #[test]
+    fn grab_scan_point_recovering_restarts_scan() {
+        let mode_answers = scan_mode_answers(125f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Boost\0");
+        let mut replies = VecDeque::new();
+        for scan in [misaligned_hq_answer(), answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(256, 4000), true)] {
+            replies.extend(vec![mode_answers.clone(), Vec::new(), Vec::new(), Vec::new(), scan]);
+        }
+
+        let mut rplidar = RplidarDevice::with_stream(Box::new(ReplyingStream { stream: MockStream::new(&[]), replies: replies }));
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(2)).unwrap();
+
+        assert_eq!(rplidar.grab_scan_point_recovering().unwrap().angle_z_q14, 256);
+    }
+
+    #[test]
+    fn ping_latency_of_delayed_response() {
+        let rx = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x18, 0x0118), false);
+
+        let mut rplidar = RplidarDevice::with_stream(Box::new(DelayedStream {
+            stream: MockStream::new(&rx),
+            delay: Duration::from_millis(30),
+            requested_at: None,
+        }));
+
+        let latency = rplidar.ping_latency().unwrap();
+        assert!(latency >= Duration::from_millis(30));
+        assert!(latency < RPLIDAR_DEFAULT_TIMEOUT);
+    }
+
+    #[test]
+    fn standby_rejects_commands() {
+        let (mut rplidar, tx) = mock_device_with_tx(&answer(
+            RPLIDAR_ANS_TYPE_DEVINFO,
+            &device_info_payload(0x18, 0x0118),
+            false,
+        ));
+
+        rplidar.standby().unwrap();
+        assert!(rplidar.is_in_standby());
+        assert_eq!(*tx.borrow(), vec![0xA5, RPLIDAR_CMD_STOP, 0xA5, 0xF0, 2, 0, 0, 0x57]);
+        assert!(rplidar.get_device_info().is_err());
+        assert!(rplidar.set_motor_pwm(600).is_err());
+        assert!(rplidar.grab_scan_point().is_err());
+
+        rplidar.wake().unwrap();
+        assert!(!rplidar.is_in_standby());
+        assert_eq!(rplidar.get_device_info().unwrap().model, 0x18);
+    }
+
+    #[test]
+    fn grab_scan_xy_pairs_cartesian() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        stream.extend_from_slice(&hq_capsule(4096, 8000));
+        stream.extend_from_slice(&hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+
+        let scan = rplidar.grab_scan_xy().unwrap();
+        assert_eq!(scan.len(), 32);
+
+        for (point, xy) in scan.iter() {
+            assert_eq!(*xy, point.to_cartesian());
+        }
+
+        let (x, y) = scan[0].1;
+        assert!((x - 1000f32).abs() < 1e-3 && y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn scan_mode_with_invalid_name() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x61, 0x0118), false);
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[2, 0]));
+        stream.extend(scan_mode_answers(125f32, 40f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, b"Standard\0"));
+        stream.extend(scan_mode_answers(62.5f32, 40f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, b"Bo\xffst\0"));
+
+        let mut rplidar = mock_device(&stream);
+        let scan_modes = rplidar.get_all_supported_scan_modes().unwrap();
+
+        assert_eq!(scan_modes.len(), 2);
+        assert_eq!(scan_modes[0].name, "Standard");
+        assert_eq!(scan_modes[1].name, "Bo\u{fffd}st");
+        assert_eq!(scan_modes[1].us_per_sample, 62.5f32);
+    }
+
+    #[test]
+    fn detect_frozen_scans() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for _ in 0..3 {
+            stream.extend(hq_capsule(4096, 8000));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+        stream.extend(hq_capsule(4096, 6000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+
+        rplidar.grab_scan().unwrap();
+        assert!(!rplidar.detect_frozen_scan(3));
+        rplidar.grab_scan().unwrap();
+        rplidar.grab_scan().unwrap();
+        assert!(rplidar.detect_frozen_scan(3));
+
+        rplidar.grab_scan().unwrap();
+        assert!(!rplidar.detect_frozen_scan(3));
+    }
+
+    #[test]
+    fn drain_and_discard_scan_data() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.drain_and_discard(Duration::from_secs(1)).unwrap(), 48);
+        assert!(rplidar.cached_measurement_nodes.is_empty());
+        assert!(rplidar.transport().rx.is_empty());
+        assert_eq!(rplidar.drain_and_discard(Duration::from_secs(1)).unwrap(), 0);
+    }
+
+    #[test]
+    fn stop_and_flush_stale_data() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(hq_capsule(256, 4000));
+        let stale = hq_capsule(512, 4000);
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.grab_scan_point().unwrap();
+        rplidar.transport_mut().rx.extend(stale.iter());
+        assert!(!rplidar.cached_measurement_nodes.is_empty());
+
+        // the second capsule is buffered but not decoded yet
+        assert_eq!(rplidar.stop_and_flush(Duration::from_millis(100)).unwrap(), 2 * stale.len());
+        assert!(rplidar.cached_measurement_nodes.is_empty());
+        assert!(rplidar.transport().rx.is_empty());
+        assert_eq!(rplidar.pending_bytes(), 0);
+        assert_eq!(*rplidar.transport().tx.borrow(), encode_cmd(&Message::new(RPLIDAR_CMD_STOP)));
+    }
+
+    #[test]
+    fn drain_and_discard_keeps_buffered_points() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(hq_capsule(256, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_max_cached_points(Some(20));
+        rplidar.grab_points_available(Duration::from_millis(20)).unwrap();
+        rplidar.wait_scan_data_with_timeout(Duration::from_millis(20)).unwrap();
+        let buffered: Vec<ScanPoint> = rplidar.cached_measurement_nodes.iter().cloned().collect();
+        assert_eq!(buffered.len(), 16);
+
+        rplidar.transport_mut().rx.extend(hq_capsule(512, 4000));
+        rplidar.transport_mut().rx.extend(hq_capsule(768, 4000));
+        let points_emitted = rplidar.stats().points_emitted;
+        assert_eq!(rplidar.drain_and_discard(Duration::from_secs(1)).unwrap(), 32);
+
+        assert_eq!(rplidar.cached_measurement_nodes.iter().cloned().collect::<Vec<_>>(), buffered);
+        assert_eq!(rplidar.dropped_point_count(), 0);
+        assert_eq!(rplidar.stats().points_emitted, points_emitted);
+        assert_eq!(rplidar.grab_scan_point().unwrap(), buffered[0]);
+    }
+
+    /// points of a 3 meter long wall passing (1, 1) with specific bearing
+    fn wall_scan(bearing_deg: f32) -> Vec<ScanPoint> {
+        let (dir_x, dir_y) = (bearing_deg.to_radians().cos(), bearing_deg.to_radians().sin());
+
+        return (0..60)
+            .map(|i| {
+                let t = (i as f32) * 0.05f32 - 1.5f32;
+                let (x, y) = (1f32 + t * dir_x, 1f32 + t * dir_y);
+                let mut point = ScanPoint { angle_z_q14: 0, dist_mm_q2: 0, quality: 0xbc, flag: 0 };
+                point.set_angle(y.atan2(x).rem_euclid(2f32 * std::f32::consts::PI));
+                point.set_distance((x * x + y * y).sqrt());
+                point
+            })
+            .collect();
+    }
+
+    /// scan in a rectangular room off its center, with the sensor rotated by `heading_deg`
+    pub fn room_scan(heading_deg: f32) -> Vec<ScanPoint> {
+        let (x_min, x_max, y_min, y_max) = (-2.3f32, 3.5f32, -1.5f32, 2.6f32);
+
+        return (0..720)
+            .map(|i| {
+                let angle = ((i as f32) * 0.5f32 + 0.25f32).to_radians();
+                let world_angle = angle + heading_deg.to_radians();
+                let (dir_x, dir_y) = (world_angle.cos(), world_angle.sin());
+                let (sensor_x, sensor_y) = (0.7f32, -0.4f32);
+
+                let t_x = if dir_x > 0f32 { (x_max - sensor_x) / dir_x } else { (x_min - sensor_x) / dir_x };
+                let t_y = if dir_y > 0f32 { (y_max - sensor_y) / dir_y } else { (y_min - sensor_y) / dir_y };
+
+                let mut point = ScanPoint { angle_z_q14: 0, dist_mm_q2: 0, quality: 0xbc, flag: 0 };
+                point.set_angle(angle);
+                point.set_distance(t_x.min(t_y));
+                point
+            })
+            .collect();
+    }
+
+    #[test]
+    fn track_heading_of_rotating_platform() {
+        let mut rplidar = mock_device(&[]);
+        assert_eq!(rplidar.track_heading(&room_scan(0f32)), 0f32);
+
+        let mut heading = 0f32;
+        for step in 1..=12 {
+            heading += if step % 2 == 0 { 6f32 } else { 3.5f32 };
+            let tracked = rplidar.track_heading(&room_scan(heading));
+            assert!((tracked - heading).abs() < 1f32, "tracked {} at {}", tracked, heading);
+        }
+
+        // not estimated without overlap, the heading is kept
+        let tracked = rplidar.track_heading(&[]);
+        assert!((tracked - heading).abs() < 1f32);
+
+        rplidar.reset_heading();
+        assert_eq!(rplidar.track_heading(&room_scan(heading)), 0f32);
+    }
+
+    #[test]
+    fn calibrate_zero_offset_with_wall() {
+        let mut rplidar = mock_device(&[]);
+        assert!(rplidar.calibrate_zero_offset(0f32, &[]).is_nan());
+
+        let correction = rplidar.calibrate_zero_offset(40f32, &wall_scan(30f32));
+        assert!((correction - 10f32).abs() < 0.5f32);
+
+        // the wall direction has no sign, so the correction is kept within 90 degrees
+        let correction = rplidar.calibrate_zero_offset(175f32, &wall_scan(5f32));
+        assert!((correction + 10f32).abs() < 0.5f32);
+
+        rplidar.set_frame_transform(5f32);
+        let correction = rplidar.calibrate_zero_offset(40f32, &wall_scan(30f32));
+        assert!((correction - 15f32).abs() < 0.5f32);
+    }
+
+    #[test]
+    fn frame_transform_rotates_points() {
+        let stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(65000, 4000), false);
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_frame_transform(-90f32);
+        assert_eq!(rplidar.frame_transform(), -90f32);
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 65000 - 16384);
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_frame_transform(90f32);
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 65000u16.wrapping_add(16384));
+    }
+
+    #[test]
+    fn scan_for_two_rotations() {
+        let mut stream = scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Sensitivity\0");
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true));
+        for _ in 0..2 {
+            stream.extend(hq_capsule(4096, 8000));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+        let rotations = rplidar.scan_for_rotations(2, &ScanOptions::with_mode(3), Duration::from_secs(1)).unwrap();
+        assert_eq!(rotations.len(), 2);
+        assert!(rotations.iter().all(|rotation| rotation.len() == 32));
+
+        let mut shutdown = encode_cmd(&Message::new(RPLIDAR_CMD_STOP));
+        shutdown.extend(encode_cmd(&Message::with_data(RPLIDAR_CMD_SET_MOTOR_PWM, &[0, 0])));
+        assert!(tx.borrow().ends_with(&shutdown));
+
+        // the motor is stopped even if the capture failed
+        let (mut rplidar, tx) = mock_device_with_tx(&[]);
+        assert!(rplidar.scan_for_rotations(2, &ScanOptions::with_mode(3), Duration::from_millis(10)).is_err());
+        assert!(tx.borrow().ends_with(&shutdown));
+    }
+
+    #[test]
+    fn report_persistent_dropouts() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for i in 0..3 {
+            stream.extend(hq_capsule(16384, 4000));
+            stream.extend(hq_capsule(32768, 0));
+            stream.extend(hq_capsule(49152, if i == 1 { 0 } else { 4000 }));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.persistent_dropout_sectors(3, 4), vec![2]);
+    }
+
+    #[test]
+    fn adaptive_scan_timeout() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for _ in 0..2 {
+            stream.extend(hq_capsule(4096, 8000));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.active_scan_mode = Some(ScanMode {
+            id: 0,
+            name: "Standard".to_owned(),
+            us_per_sample: 3125f32,
+            max_distance: 12f32,
+            ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_HQ,
+        });
+        assert_eq!(rplidar.adaptive_scan_timeout(), RPLIDAR_DEFAULT_TIMEOUT * 5);
+
+        // 32 samples of 3.125ms are decoded per rotation, however fast the scans are grabbed
+        rplidar.grab_scan_adaptive().unwrap();
+        let timeout = rplidar.adaptive_scan_timeout();
+        assert!(timeout > Duration::from_millis(299) && timeout < Duration::from_millis(301));
+        rplidar.grab_scan_adaptive().unwrap();
+        assert!((rplidar.measured_rotation_frequency_hz().unwrap() - 10f32).abs() < 1e-3);
+
+        rplidar.rotation_period = Some(Duration::from_millis(10));
+        assert_eq!(rplidar.adaptive_scan_timeout(), RPLIDAR_ADAPTIVE_TIMEOUT_MIN);
+    }
+
+    #[test]
+    fn grab_scan_monotonic_timestamps() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for _ in 0..4 {
+            stream.extend(hq_capsule(4096, 8000));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        // the estimated start of every frame is far before the previous one
+        rplidar.active_scan_mode = Some(ScanMode {
+            id: 0,
+            name: "Standard".to_owned(),
+            us_per_sample: 1000000f32,
+            max_distance: 12f32,
+            ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_HQ,
+        });
+
+        let frames: Vec<ScanFrame> = (0..4).map(|_| rplidar.grab_scan_monotonic().unwrap()).collect();
+        for pair in frames.windows(2) {
+            assert!(pair[1].start > pair[0].start);
+        }
+        for frame in frames.iter() {
+            assert_eq!(frame.points.len(), 32);
+            assert!(frame.end >= frame.start);
+        }
+    }
+
+    #[test]
+    fn grab_filtered_scan_points() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 0), true);
+        stream.extend(hq_capsule(256, 50 * 4));
+        stream.extend(hq_capsule(512, 20000 * 4));
+        stream.extend(hq_capsule(768, 1000 * 4));
+
+        let mut rplidar = mock_device(&stream);
+        let point = rplidar
+            .grab_scan_point_filtered(10, 100, 12000, Duration::from_millis(100))
+            .unwrap();
+        assert_eq!(point.angle_z_q14, 768);
+        assert_eq!(rplidar.cached_measurement_nodes.len(), 15);
+
+        // the quality of the points (0xbc) is below the threshold
+        rplidar.cached_measurement_nodes.clear();
+        assert!(rplidar
+            .grab_scan_point_filtered(0xbd, 0, 12000, Duration::from_millis(20))
+            .is_err());
+    }
+
+    #[test]
+    fn export_distances_in_configured_unit() {
+        let mut rplidar = mock_device(&[]);
+        let scan = vec![ScanPoint { angle_z_q14: 0, dist_mm_q2: 1234 * 4, quality: 0xbc, flag: 0 }];
+        assert_eq!(rplidar.distance_unit(), DistanceUnit::Millimeters);
+        assert_eq!(rplidar.scan_distances(&scan), vec![1234f32]);
+
+        rplidar.set_distance_unit(DistanceUnit::Centimeters);
+        assert_eq!(rplidar.scan_distances(&scan), vec![123.4f32]);
+
+        rplidar.set_distance_unit(DistanceUnit::Meters);
+        assert_eq!(rplidar.scan_distances(&scan), vec![1.234f32]);
+        assert_eq!(scan[0].dist_mm_q2, 1234 * 4);
+    }
+
+    #[test]
+    fn export_ranges_in_configured_unit() {
+        let mut rplidar = mock_device(&[]);
+        let scan = vec![ScanPoint { angle_z_q14: 0, dist_mm_q2: 1234 * 4, quality: 0xbc, flag: 0 }];
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-3;
+
+        let ordered = rplidar.ordered_ranges(&scan, 4, 100f32, 2000f32);
+        assert!(near(ordered.ranges[0], 1234f32));
+        assert_eq!(ordered.ranges[1], f32::INFINITY);
+        assert_eq!((ordered.range_min, ordered.range_max), (100f32, 2000f32));
+        assert!(near(rplidar.averaged_ranges(&[scan.clone(), scan.clone()], 4)[0], 1234f32));
+        assert!(near(rplidar.normalized_ranges(&scan, 4)[0].unwrap(), 1234f32));
+        assert_eq!(rplidar.normalized_ranges(&scan, 4)[1], None);
+        assert_eq!(rplidar.scan_rle(&scan, 4, 10f32), vec![(1, 1230f32), (3, 0f32)]);
+
+        // the range limits and the quantum are in the configured unit as well
+        rplidar.set_distance_unit(DistanceUnit::Centimeters);
+        assert!(near(rplidar.ordered_ranges(&scan, 4, 10f32, 100f32).ranges[0], 100f32));
+        assert!(near(rplidar.averaged_ranges(std::slice::from_ref(&scan), 4)[0], 123.4f32));
+        assert!(near(rplidar.normalized_ranges(&scan, 4)[0].unwrap(), 123.4f32));
+        assert_eq!(rplidar.scan_rle(&scan, 4, 1f32), vec![(1, 123f32), (3, 0f32)]);
+
+        rplidar.set_distance_unit(DistanceUnit::Meters);
+        assert!(near(rplidar.ordered_ranges(&scan, 4, 0.1f32, 2f32).ranges[0], 1.234f32));
+        assert_eq!(scan[0].distance(), 1.234f32);
+    }
+
+    #[test]
+    fn scan_period_jitter() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for capsules in [1, 2, 1].iter() {
+            for _ in 0..*capsules {
+                stream.extend(hq_capsule(4096, 8000));
+            }
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.active_scan_mode = Some(ScanMode {
+            id: 0,
+            name: "Standard".to_owned(),
+            us_per_sample: 1000f32,
+            max_distance: 12f32,
+            ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_HQ,
+        });
+        assert_eq!(rplidar.scan_period_jitter(8), None);
+        for _ in 0..3 {
+            rplidar.grab_scan().unwrap();
+        }
+
+        // the periods are counted in decoded samples (1ms each), not between the grabs
+        let periods_ms: Vec<f32> = rplidar
+            .recent_scan_periods
+            .iter()
+            .map(|period| (period.as_secs_f32() * 1000f32).round())
+            .collect();
+        assert_eq!(periods_ms, vec![32f32, 48f32, 32f32]);
+        assert!((rplidar.scan_period_jitter(2).unwrap() - 0.008f32).abs() < 1e-5);
+
+        rplidar.recent_scan_periods = [100, 100, 90, 110, 90, 110]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        assert!((rplidar.scan_period_jitter(4).unwrap() - 0.01f32).abs() < 1e-5);
+        assert!(rplidar.scan_period_jitter(6).unwrap() < 0.01f32);
+        assert_eq!(rplidar.scan_period_jitter(1), None);
+    }
+
+    #[test]
+    fn grab_scan_with_inline_stats() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_capsule(16384, 0));
+        stream.extend(hq_capsule(32768, 12000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        let (scan, stats) = rplidar.grab_scan_with_stats().unwrap();
+        assert_eq!(scan.len(), 64);
+        assert_eq!(stats, utils::scan_stats(&scan));
+        assert_eq!(stats.valid_points, 48);
+        assert_eq!(stats.min_distance, Some(1f32));
+        assert_eq!(stats.max_distance, Some(3f32));
+        assert_eq!(stats.mean_distance, Some(2f32));
+        assert!(stats.coverage > 0f32 && stats.coverage < 0.1f32);
+    }
+
+    #[test]
+    fn fill_scan_reuses_buffer() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for _ in 0..2 {
+            stream.extend(hq_capsule(4096, 8000));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        let mut buf = Vec::with_capacity(1024);
+        buf.push(ScanPoint { angle_z_q14: 0, dist_mm_q2: 0, quality: 0, flag: 0 });
+        let ptr = buf.as_ptr();
+
+        rplidar.fill_scan(&mut buf).unwrap();
+        assert_eq!(buf.len(), 32);
+        assert!(buf[0].is_sync());
+
+        rplidar.fill_scan(&mut buf).unwrap();
+        assert_eq!(buf.len(), 32);
+        assert_eq!(buf.capacity(), 1024);
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn validate_dropped_scan() {
+        let mode = ScanMode {
+            id: 0,
+            us_per_sample: 500f32,
+            max_distance: 12f32,
+            ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_HQ,
+            name: "Standard".to_owned(),
+        };
+
+        let mut scan: Vec<ScanPoint> = (0..200)
+            .map(|i| ScanPoint { angle_z_q14: (i * 327) as u16, dist_mm_q2: 4000, quality: 0xbc, flag: 0 })
+            .collect();
+        scan[0].flag = RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
+
+        let mut rplidar = mock_device(&[]);
+        rplidar.rotation_period = Some(Duration::from_millis(100));
+        assert_eq!(rplidar.measured_rotation_frequency_hz(), Some(10f32));
+
+        let validation = rplidar.validate_scan(&scan, &mode);
+        assert_eq!(validation.expected_points, 200);
+        assert_eq!(validation.completeness, 1f32);
+        assert!(validation.has_sync_start);
+
+        scan.drain(50..100);
+        let validation = rplidar.validate_scan(&scan, &mode);
+        assert_eq!(validation.actual_points, 150);
+        assert!((validation.completeness - 0.75f32).abs() < 1e-6);
+
+        assert!(!rplidar.validate_scan(&scan[1..], &mode).has_sync_start);
+    }
+
+    fn s2e_scan_modes_answers() -> Vec<u8> {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x71, 0x0118), false);
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[2, 0]));
+        stream.extend(scan_mode_answers(1000000f32 / 16000f32, 30f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Standard\0"));
+        stream.extend(scan_mode_answers(1000000f32 / 32000f32, 30f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"DenseBoost\0"));
+        return stream;
+    }
+
+    fn encode_cmd(msg: &Message) -> Vec<u8> {
+        let mut buf = Vec::new();
+        RplidarHostProtocol::new().write_to(msg, &mut buf).unwrap();
+        return buf;
+    }
+
+    #[test]
+    fn trim_space_padded_mode_name() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x31, 0x0118), false);
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[1, 0]));
+        stream.extend(scan_mode_answers(250f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, b"Boost   \0 "));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.get_all_supported_scan_modes().unwrap()[0].name, "Boost");
+    }
+
+    #[test]
+    fn scan_mode_count_widths() {
+        for count in [&[2u8, 0][..], &[2u8, 0, 0, 0][..]].iter() {
+            let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x71, 0x0118), false);
+            stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, count));
+            stream.extend(scan_mode_answers(1000000f32 / 16000f32, 30f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Standard\0"));
+            stream.extend(scan_mode_answers(1000000f32 / 32000f32, 30f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"DenseBoost\0"));
+
+            let mut rplidar = mock_device(&stream);
+            assert_eq!(rplidar.get_all_supported_scan_modes().unwrap().len(), 2);
+        }
+
+        assert_eq!(parse_scan_mode_count(&[3, 0]).unwrap(), 3);
+        assert_eq!(parse_scan_mode_count(&[3, 0, 0, 0]).unwrap(), 3);
+        assert!(parse_scan_mode_count(&[3, 0, 0]).is_err());
+        assert!(parse_scan_mode_count(&[0, 0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn retry_timed_out_lidar_conf() {
+        let stream = conf_answer(RPLIDAR_CONF_SCAN_MODE_TYPICAL, &[2, 0]);
+        let timeout = Duration::from_millis(20);
+
+        let mut mock = MockStream::new(&stream);
+        mock.muted_requests = 1;
+        let mut rplidar = RplidarDevice::with_stream(Box::new(mock));
+        assert_eq!(rplidar.lidar_conf_attempts(), 1);
+        assert!(rplidar.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_TYPICAL, timeout).is_err());
+
+        let mut mock = MockStream::new(&stream);
+        mock.muted_requests = 1;
+        let mut rplidar = RplidarDevice::with_stream(Box::new(mock));
+        rplidar.set_lidar_conf_attempts(3);
+        assert_eq!(rplidar.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_TYPICAL, timeout).unwrap(), [2, 0]);
+    }
+
+    #[test]
+    fn discard_motor_pwm_ack() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x31, 0x0118), false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &[0, 0, 0, 0], false));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.get_device_info().unwrap().model(), Model::A3);
+        rplidar.set_motor_pwm(600).unwrap();
+        assert_eq!(rplidar.get_device_health().unwrap(), Health::Healthy);
+
+        // models not acknowledging PWM don't wait
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x28, 0x0118), false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.get_device_info().unwrap();
+        rplidar.set_motor_pwm(600).unwrap();
+        assert_eq!(rplidar.get_device_health().unwrap(), Health::Healthy);
+    }
+
+    #[test]
+    fn motor_control_on_ethernet() {
+        let (mut rplidar, tx) = mock_device_with_tx(&[]);
+        rplidar.set_transport_kind(TransportKind::Ethernet);
+        rplidar.start_motor().unwrap();
+        rplidar.stop_motor().unwrap();
+
+        let mut expected = encode_cmd(&Message::with_data(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &[0x58, 0x02]));
+        expected.extend(encode_cmd(&Message::with_data(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &[0, 0])));
+        assert_eq!(*tx.borrow(), expected);
+
+        let (mut rplidar, tx) = mock_device_with_tx(&[]);
+        assert_eq!(rplidar.transport_kind(), TransportKind::Serial);
+        rplidar.start_motor().unwrap();
+        assert_eq!(*tx.borrow(), encode_cmd(&Message::with_data(RPLIDAR_CMD_SET_MOTOR_PWM, &[0x58, 0x02])));
+    }
+
+    #[test]
+    fn rotation_speed_range() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x61, 0x0118), false);
+        stream.extend(conf_answer(RPLIDAR_CONF_MIN_ROT_FREQ, &[0x2c, 0x01]));
+        stream.extend(conf_answer(RPLIDAR_CONF_MAX_ROT_FREQ, &[0x84, 0x03]));
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+        assert_eq!(rplidar.get_rotation_speed_range().unwrap(), (5f32, 15f32));
+
+        // nonzero speeds are clamped into the range (300 to 900 RPM)
+        tx.borrow_mut().clear();
+        rplidar.set_motor_speed_rpm(1200).unwrap();
+        rplidar.set_motor_speed_rpm(0).unwrap();
+        rplidar.set_motor_speed_rpm(100).unwrap();
+
+        let mut expected = encode_cmd(&Message::with_data(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &[0x84, 0x03]));
+        expected.extend(encode_cmd(&Message::with_data(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &[0, 0])));
+        expected.extend(encode_cmd(&Message::with_data(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &[0x2c, 0x01])));
+        assert_eq!(*tx.borrow(), expected);
+
+        let mut rplidar = mock_device(&answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x18, 0x0110), false));
+        assert_eq!(rplidar.get_rotation_speed_range().unwrap(), RPLIDAR_DEFAULT_ROTATION_SPEED_RANGE_HZ);
+    }
+
+    #[test]
+    fn grab_scan_full_attaches_metadata() {
+        let mut stream = scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Sensitivity\0");
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true));
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_sync_capsule(0, 4000));
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.grab_scan_full().is_err());
+
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(3)).unwrap();
+
+        let before = Instant::now();
+        let scan = rplidar.grab_scan_full().unwrap();
+        assert_eq!(scan.points.len(), 32);
+        assert_eq!(scan.mode.id, 3);
+        assert_eq!(scan.mode.name, "Sensitivity");
+        assert_eq!(scan.frequency_hz, Some(62.5f32));
+        assert_eq!(scan.sequence, 1);
+        assert!(scan.timestamp >= before);
+
+        assert_eq!(rplidar.grab_scan_full().unwrap().sequence, 2);
+    }
+
+    #[test]
+    fn record_scans() {
+        let mut stream = scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Sensitivity\0");
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true));
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_sync_capsule(0, 4000));
+        stream.extend(hq_capsule(4096, 6000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(3)).unwrap();
+
+        let mut recorded = Vec::new();
+        rplidar.record_to(&mut recorded, 2).unwrap();
+
+        let mut reader = &recorded[..];
+        let first = utils::read_scan_bin(&mut reader).unwrap().unwrap();
+        let second = utils::read_scan_bin(&mut reader).unwrap().unwrap();
+        assert!(utils::read_scan_bin(&mut reader).unwrap().is_none());
+
+        assert_eq!((first.sequence, first.mode_id, first.points.len()), (1, 3, 32));
+        assert_eq!((second.sequence, second.mode_id, second.points.len()), (2, 3, 32));
+        assert!(first.points[0].is_sync());
+        assert_eq!(first.points[16].dist_mm_q2, 8000);
+        assert_eq!(second.points[16].dist_mm_q2, 6000);
+
+        assert!(utils::read_scan_bin(&mut &recorded[..10]).is_err());
+    }
+
+    #[test]
+    fn replay_recorded_scans() {
+        let mut stream = scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Sensitivity\0");
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true));
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_sync_capsule(0, 4000));
+        stream.extend(hq_capsule(4096, 6000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(3)).unwrap();
+
+        let mut recorded = Vec::new();
+        let mut scans = Vec::new();
+        for _ in 0..2 {
+            let scan = rplidar.grab_scan_full().unwrap();
+            utils::write_scan_bin(&mut recorded, &scan).unwrap();
+            scans.push(scan.points);
+        }
+
+        let mut reader = &recorded[..];
+        let replayed: Vec<Vec<ScanPoint>> = utils::replay_from(&mut reader).map(|scan| scan.unwrap()).collect();
+        assert_eq!(replayed, scans);
+
+        let mut truncated = &recorded[..recorded.len() - 1];
+        let mut replay = utils::replay_from(&mut truncated);
+        assert_eq!(replay.next().unwrap().unwrap(), scans[0]);
+        assert!(replay.next().unwrap().is_err());
+        assert!(replay.next().is_none());
+    }
+
+    #[test]
+    fn skip_unexpected_answers() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), false);
+        stream.extend(answer(0x99, &[1, 2, 3], false));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(256, 4000), false));
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_unexpected_answer_policy(UnexpectedAnswerPolicy::Skip);
+
+        let points: Vec<ScanPoint> = (0..32).map(|_| rplidar.grab_scan_point().unwrap()).collect();
+        assert_eq!(points[16].angle_z_q14, 256);
+        assert_eq!(rplidar.unexpected_answer_count(), 1);
+
+        let mut stream = answer(0x99, &[1, 2, 3], false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), false));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.unexpected_answer_policy(), UnexpectedAnswerPolicy::Error);
+        assert!(rplidar.grab_scan_point().is_err());
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 0);
+    }
+
+    #[test]
+    fn active_scan_options_reflect_last_start() {
+        let mut stream = scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT, b"Standard\0");
+        stream.extend(scan_mode_answers(250f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, b"Express\0"));
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+        assert_eq!(rplidar.active_scan_options(), None);
+
+        rplidar.start_scan_with_options(&ScanOptions::force_scan_with_mode(0)).unwrap();
+        assert_eq!(rplidar.active_scan_options(), Some(&ScanOptions::force_scan_with_mode(0)));
+        assert!(tx.borrow().ends_with(&[0xA5, RPLIDAR_CMD_FORCE_SCAN]));
+
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(1)).unwrap();
+        assert!(!rplidar.active_scan_options().unwrap().force_scan);
+        assert_eq!(rplidar.active_scan_options().unwrap().scan_mode, Some(1));
+    }
+
+    #[test]
+    fn active_scan_path_reflects_start_command() {
+        let mut stream = scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT, b"Standard\0");
+        stream.extend(scan_mode_answers(125f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Boost\0"));
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+        assert_eq!(rplidar.active_scan_path(), None);
+
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(0)).unwrap();
+        assert_eq!(rplidar.active_scan_path(), Some(ScanPath::Legacy));
+        assert!(tx.borrow().ends_with(&[0xA5, RPLIDAR_CMD_SCAN]));
+
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(2)).unwrap();
+        assert_eq!(rplidar.active_scan_path(), Some(ScanPath::Express));
+        assert!(tx.borrow().ends_with(&encode_cmd(&Message::with_data(RPLIDAR_CMD_EXPRESS_SCAN, &[2, 0, 0, 0, 0]))));
+    }
+
+    #[test]
+    fn probe_capabilities() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x31, 0x0118), false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[3, 0]));
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+
+        let capabilities = rplidar.capabilities();
+        assert_eq!(capabilities, Capabilities {
+            has_get_lidar_conf: true,
+            has_health: true,
+            has_accessory_board: false,
+            has_motor_ctrl: false,
+            has_express: true,
+        });
+
+        let written = tx.borrow().len();
+        assert_eq!(rplidar.capabilities(), capabilities);
+        assert_eq!(tx.borrow().len(), written);
+    }
+
+    #[derive(Debug)]
+    struct MockMotorControl {
+        dtr: Rc<RefCell<Vec<bool>>>,
+    }
+
+    impl MotorControl for MockMotorControl {
+        fn set_dtr(&mut self, on: bool) -> Result<()> {
+            self.dtr.borrow_mut().push(on);
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn dtr_motor_control() {
+        // A1 without motor control on the accessory board
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x18, 0x0118), false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x18, 0x0118), false));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[3, 0]));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &[0, 0, 0, 0], false));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x18, 0x0118), false));
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+        let dtr = Rc::new(RefCell::new(Vec::new()));
+        rplidar.set_motor_control(Box::new(MockMotorControl { dtr: dtr.clone() }));
+
+        rplidar.start_motor().unwrap();
+        rplidar.stop_motor().unwrap();
+        assert_eq!(*dtr.borrow(), vec![false, true]);
+        assert!(!tx.borrow().windows(2).any(|cmd| cmd == [0xa5, RPLIDAR_CMD_SET_MOTOR_PWM]));
+
+        // PWM is used without motor control installed
+        let (mut rplidar, tx) = mock_device_with_tx(&[]);
+        rplidar.stop_motor().unwrap();
+        assert_eq!(tx.borrow()[..2], [0xa5, RPLIDAR_CMD_SET_MOTOR_PWM]);
+    }
+
+    #[test]
+    fn acc_board_flag_gates_motor_pwm() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &[0x03, 0, 0, 0], false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &[0, 0, 0, 0], false));
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+        assert_eq!(rplidar.get_acc_board_flag().unwrap(), AccBoardFlag { flags: 0x03, motor_ctrl: true });
+        assert!(!rplidar.is_motor_control_supported().unwrap());
+
+        tx.borrow_mut().clear();
+        assert!(rplidar.set_motor_pwm(600).is_ok());
+        assert!(!tx.borrow().is_empty());
+
+        tx.borrow_mut().clear();
+        rplidar.set_check_motor_pwm_support(true);
+        let err = rplidar.set_motor_pwm(600).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationNotSupport)));
+        assert!(tx.borrow().is_empty());
+    }
+
+    #[test]
+    fn pwm_motor_control_support() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x28, 0x0118), false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x28, 0x0118), false));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[3, 0]));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &[1, 0, 0, 0], false));
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.supports_pwm_motor_control());
+
+        let (mut rplidar, tx) = mock_device_with_tx(&[]);
+        rplidar.set_transport_kind(TransportKind::Ethernet);
+        assert!(!rplidar.supports_pwm_motor_control());
+        assert!(tx.borrow().is_empty());
+
+        let mut rplidar = mock_device(&answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x71, 0x0118), false));
+        assert!(!rplidar.supports_pwm_motor_control());
+    }
+
+    #[test]
+    fn diagnostic_json_blob() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x71, 0x0118), false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        stream.extend(s2e_scan_modes_answers());
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x71, 0x0118), false));
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0], false));
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[2, 0]));
+
+        let mut rplidar = mock_device(&stream);
+        let json = rplidar.diagnostic_json().unwrap();
+
+        assert!(json.starts_with("{\"device_info\":{\"model\":\"S2\",\"model_id\":113,\"firmware_version\":\"1.24\""));
+        assert!(json.contains("\"serial_number\":\"000102030405060708090A0B0C0D0E0F\""));
+        assert!(json.contains("\"health\":{\"status\":\"healthy\",\"error_code\":0}"));
+        assert!(json.contains("{\"id\":0,\"name\":\"Standard\",\"us_per_sample\":62.5"));
+        assert!(json.contains("{\"id\":1,\"name\":\"DenseBoost\",\"us_per_sample\":31.25"));
+        assert!(json.contains("\"has_get_lidar_conf\":true"));
+        assert!(json.ends_with("\"has_express\":true}}"));
+
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    #[test]
+    fn scan_modes_json_table() {
+        let mut stream = s2e_scan_modes_answers();
+        stream.extend(answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x71, 0x0118), false));
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_TYPICAL, &[1, 0]));
+
+        let mut rplidar = mock_device(&stream);
+        let json = rplidar.scan_modes_json().unwrap();
+
+        assert!(json.starts_with("{\"typical_mode\":1,\"scan_modes\":["));
+        assert!(json.contains("{\"id\":0,\"name\":\"Standard\",\"us_per_sample\":62.5"));
+        assert!(json.contains("\"sample_rate\":16000,\"typical\":false}"));
+        assert!(json.contains("{\"id\":1,\"name\":\"DenseBoost\",\"us_per_sample\":31.25"));
+        assert!(json.contains("\"sample_rate\":32000,\"typical\":true}"));
+        assert!(json.ends_with("]}"));
+    }
+
+    #[test]
+    fn feed_captured_bytes() {
+        let mut captured = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for i in 1..=8 {
+            captured.extend(hq_capsule(i * 1024, 4000 + i as u32));
+        }
+        captured.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&[]);
+
+        // captured bytes may be fed in arbitrary chunks
+        for chunk in captured.chunks(100) {
+            rplidar.feed_bytes(chunk).unwrap();
+        }
+
+        let scan = rplidar.grab_scan().unwrap();
+        assert_eq!(scan.len(), 16 * 9);
+        assert_eq!(scan[16].angle_z_q14, 1024);
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 0);
+
+        let mut rplidar = mock_device(&[]);
+        rplidar.feed_bytes(&captured).unwrap();
+        assert_eq!(rplidar.grab_scan().unwrap().len(), 16 * 9);
+    }
+
+    #[test]
+    fn feed_legacy_capture_with_version_hint() {
+        let node = |sync: bool, angle_q6: u16, distance_q2: u16| {
+            let sync_bits = if sync { RPLIDAR_RESP_MEASUREMENT_SYNCBIT } else { RPLIDAR_RESP_MEASUREMENT_INVERTED_SYNCBIT };
+            let mut node = vec![(0x2f << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT) | sync_bits, 0, 0, 0, 0];
+            LittleEndian::write_u16(&mut node[1..3], (angle_q6 << RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT) | 1);
+            LittleEndian::write_u16(&mut node[3..5], distance_q2);
+            node
+        };
+
+        let mut captured = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, &node(true, 0, 4000), true);
+        captured.extend(node(false, 90 * 64, 4000));
+        captured.extend(node(true, 0, 4000));
+
+        let mut rplidar = mock_device(&[]);
+        assert_eq!(rplidar.protocol_version_hint(), ProtocolVersion::Extended);
+        rplidar.set_protocol_version_hint(ProtocolVersion::from_firmware_version(0x0110));
+        assert_eq!(rplidar.protocol_version_hint(), ProtocolVersion::Legacy);
+
+        rplidar.feed_bytes(&captured).unwrap();
+        let scan = rplidar.grab_scan().unwrap();
+        assert_eq!(scan.len(), 2);
+        assert_eq!(scan[1].angle_z_q14, 16384);
+
+        // capsules do not exist in legacy firmwares
+        let mut rplidar = mock_device(&[]);
+        rplidar.set_protocol_version_hint(ProtocolVersion::Legacy);
+        let err = rplidar.feed_bytes(&answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true)).unwrap_err();
+        assert!(is_protocol_error(&err, "unexpected response"));
+
+        assert_eq!(ProtocolVersion::from_firmware_version(0x0114), ProtocolVersion::Express);
+        assert_eq!(ProtocolVersion::from_firmware_version(0x0118), ProtocolVersion::Extended);
+    }
+
+    #[test]
+    fn reject_corrupt_legacy_nodes() {
+        let node = |angle_q6_checkbit: u16| {
+            let mut node = vec![(0x2f << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT) | RPLIDAR_RESP_MEASUREMENT_INVERTED_SYNCBIT, 0, 0, 0, 0];
+            LittleEndian::write_u16(&mut node[1..3], angle_q6_checkbit);
+            LittleEndian::write_u16(&mut node[3..5], 4000);
+            node
+        };
+        let valid = node((90 * 64) << RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT | 1);
+        let corrupt = node((45 * 64) << RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT);
+        let mut both_sync_bits = valid.clone();
+        both_sync_bits[0] |= RPLIDAR_RESP_MEASUREMENT_SYNCBIT;
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, &corrupt, true);
+        stream.extend(&both_sync_bits);
+        stream.extend(&valid);
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.corrupt_node_policy(), CorruptNodePolicy::Error);
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "check bit mismatch"));
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "sync bit mismatch"));
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 16384);
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_corrupt_node_policy(CorruptNodePolicy::Skip);
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 16384);
+        assert_eq!(rplidar.corrupt_node_count(), 2);
+    }
+
+    #[test]
+    fn retain_last_raw_message() {
+        let mut rplidar = mock_device(&[]);
+        assert_eq!(rplidar.last_raw_message(), None);
+
+        let mut captured = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        let mut corrupted = hq_capsule(1024, 4000);
+        corrupted[20] ^= 0xff;
+        captured.extend_from_slice(&corrupted);
+
+        assert!(rplidar.feed_bytes(&captured).is_err());
+        assert_eq!(rplidar.last_raw_message(), Some(&corrupted[..]));
+    }
+
+    #[test]
+    fn ignore_back_to_back_sync_flags() {
+        // the second node of the first capsule has sync flag as well
+        let mut glitched = hq_sync_capsule(0, 4000);
+        glitched[1 + 8 + 8 + 7] = RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
+        let len = glitched.len();
+        let crc = crc32::checksum_ieee(&glitched[0..len - 4]);
+        LittleEndian::write_u32(&mut glitched[len - 4..len], crc);
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &glitched, true);
+        stream.extend(hq_capsule(4096, 8000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.min_points_per_scan(), 1);
+        assert_eq!(rplidar.grab_scan().unwrap().len(), 1);
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_min_points_per_scan(16);
+        assert_eq!(rplidar.grab_scan().unwrap().len(), 32);
+    }
+
+    fn scan_modes_with_ranges_answers() -> Vec<u8> {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &device_info_payload(0x31, 0x0118), false);
+        stream.extend(conf_answer(RPLIDAR_CONF_SCAN_MODE_COUNT, &[3, 0]));
+        stream.extend(scan_mode_answers(500f32, 12f32, RPLIDAR_ANS_TYPE_MEASUREMENT, b"Standard\0"));
+        stream.extend(scan_mode_answers(250f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, b"Express\0"));
+        stream.extend(scan_mode_answers(125f32, 25f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, b"Boost\0"));
+        return stream;
+    }
+
+    #[test]
+    fn decode_answers_of_active_scan_mode_only() {
+        let mut rplidar = mock_device(&scan_mode_answers(125f32, 25f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Sensitivity\0"));
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(3)).unwrap();
+
+        let capsuled = Message::with_data(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &[0u8; 84]);
+        let dense = Message::with_data(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &[0u8; 84]);
+
+        rplidar.on_scan_data_msg(&Message::with_data(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000))).unwrap();
+        assert!(is_protocol_error(&rplidar.on_scan_data_msg(&capsuled).unwrap_err(), "unexpected response"));
+        assert_eq!(rplidar.unexpected_answer_count(), 1);
+
+        rplidar.set_unexpected_answer_policy(UnexpectedAnswerPolicy::Skip);
+        rplidar.on_scan_data_msg(&dense).unwrap();
+        assert_eq!(rplidar.unexpected_answer_count(), 2);
+        assert_eq!(rplidar.cached_measurement_nodes.len(), 16);
+    }
+
+    #[test]
+    fn discard_warmup_rotations() {
+        let mut stream = scan_mode_answers(125f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, b"Boost\0");
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0x8000, 1000), true));
+        for rotation in 1..=3 {
+            stream.extend(hq_sync_capsule(0, rotation * 1000));
+            stream.extend(hq_capsule(0x8000, rotation * 1000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        let mut options = ScanOptions::with_mode(2);
+        options.warmup_rotations = 2;
+        rplidar.start_scan_with_options(&options).unwrap();
+        assert!(rplidar.is_warming_up());
+
+        let point = rplidar.grab_scan_point().unwrap();
+        assert!(point.is_sync());
+        assert_eq!(point.dist_mm_q2, 3000);
+        assert!(!rplidar.is_warming_up());
+        assert_eq!(rplidar.rotation_count(), 3);
+    }
+
+    #[test]
+    fn start_scan_by_mode_name() {
+        let mut stream = scan_modes_with_ranges_answers();
+        stream.extend(scan_modes_with_ranges_answers());
+        stream.extend(scan_mode_answers(125f32, 25f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, b"Boost\0"));
+        stream.extend(scan_modes_with_ranges_answers());
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+
+        assert_eq!(rplidar.find_scan_mode_by_name("boost").unwrap().unwrap().id, 2);
+
+        let mode = rplidar.start_scan_by_name("BOOST").unwrap();
+        assert_eq!(mode.id, 2);
+        assert!(tx.borrow().ends_with(&encode_cmd(&Message::with_data(RPLIDAR_CMD_EXPRESS_SCAN, &[2, 0, 0, 0, 0]))));
+
+        let err = rplidar.start_scan_by_name("Sensitivity").unwrap_err();
+        assert!(err.to_string().contains("\"Sensitivity\" is not supported"), "{}", err);
+    }
+
+    #[test]
+    fn start_scan_for_required_range() {
+        let mut stream = scan_modes_with_ranges_answers();
+        stream.extend(scan_mode_answers(250f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, b"Express\0"));
+        stream.extend(scan_modes_with_ranges_answers());
+
+        let (mut rplidar, tx) = mock_device_with_tx(&stream);
+
+        let mode = rplidar.start_scan_for_range(14f32).unwrap();
+        assert_eq!(mode.id, 1);
+        assert_eq!(mode.name, "Express");
+        assert!(tx.borrow().ends_with(&encode_cmd(&Message::with_data(RPLIDAR_CMD_EXPRESS_SCAN, &[1, 0, 0, 0, 0]))));
+
+        assert!(rplidar.start_scan_for_range(30f32).is_err());
+    }
+
+    #[test]
+    fn start_scan_best_within_budget() {
+        let mut stream = scan_modes_with_ranges_answers();
+        stream.extend(scan_mode_answers(250f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, b"Express\0"));
+        stream.extend(scan_modes_with_ranges_answers());
+        stream.extend(scan_mode_answers(125f32, 25f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, b"Boost\0"));
+        stream.extend(scan_modes_with_ranges_answers());
+
+        let mut rplidar = mock_device(&stream);
+
+        // at 10Hz, rotations complete in 100.5ms (Standard), 108ms (Express) and 112ms (Boost)
+        let mode = rplidar.start_scan_best_within(Duration::from_millis(110), 10f32).unwrap();
+        assert_eq!(mode.name, "Express");
+
+        let mode = rplidar.start_scan_best_within(Duration::from_millis(120), 10f32).unwrap();
+        assert_eq!(mode.name, "Boost");
+
+        assert!(rplidar.start_scan_best_within(Duration::from_millis(100), 10f32).is_err());
+        assert!(rplidar.start_scan_best_within(Duration::from_millis(100), 0f32).is_err());
+    }
+
+    fn is_protocol_error(err: &Error, expected: &str) -> bool {
+        match err.downcast_ref::<RposError>() {
+            Some(RposError::ProtocolError { description }) => description == expected,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn empty_message() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_DEVINFO, &[], false);
+        stream.extend(answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &[], false));
+
+        let mut rplidar = mock_device(&stream);
+        assert!(is_protocol_error(&rplidar.get_device_info().unwrap_err(), "empty message"));
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "empty message"));
+    }
+
+    /// encode a dense capsule with all cabins at `dist_mm`
+    fn dense_capsule(start_angle_q6: u16, dist_mm: u16) -> Vec<u8> {
+        let mut bytes = vec![0u8; 4 + 40 * 2];
+        LittleEndian::write_u16(&mut bytes[2..4], start_angle_q6);
+        for cabin in bytes[4..].chunks_mut(2) {
+            LittleEndian::write_u16(cabin, dist_mm);
+        }
+
+        let mut checksum = Checksum::new();
+        checksum.push_slice(&bytes[2..]);
+        set_capsule_checksum(&mut bytes, checksum.checksum());
+        return bytes;
+    }
+
+    fn set_capsule_checksum(capsule: &mut [u8], checksum: u8) {
+        capsule[0] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 << 4) | (checksum & 0xf);
+        capsule[1] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 << 4) | (checksum >> 4);
+    }
+
+    /// a checksum summing up all bytes
+    #[derive(Debug)]
+    struct SumChecksumVerifier;
+
+    impl ChecksumVerifier for SumChecksumVerifier {
+        fn verify(&self, data: &[u8], received: u8) -> bool {
+            return data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == received;
+        }
+    }
+
+    #[test]
+    fn custom_checksum_verifier() {
+        let mut capsules = [dense_capsule(0, 1500), dense_capsule(4 << 6, 1600)];
+        for capsule in capsules.iter_mut() {
+            let sum = capsule[2..].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            set_capsule_checksum(capsule, sum);
+        }
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &capsules[0], true);
+        stream.extend_from_slice(&capsules[1]);
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.grab_points_available(Duration::from_millis(100)).is_err());
+
+        let mut rplidar = mock_device(&stream);
+        rplidar.set_checksum_verifier(Box::new(SumChecksumVerifier));
+        rplidar.grab_points_available(Duration::from_millis(100)).unwrap();
+        assert_eq!(rplidar.grab_points_available(Duration::from_millis(100)).unwrap().len(), 40);
+    }
+
+    #[test]
+    fn poll_scan_point_of_seeding_capsule() {
+        let stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &dense_capsule(0, 1500), true);
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.poll_scan_point_with_timeout(Duration::from_millis(20)).unwrap().is_none());
+
+        rplidar.transport_mut().rx.extend(dense_capsule(4 << 6, 1600));
+        assert!(rplidar.poll_scan_point_with_timeout(Duration::from_millis(20)).unwrap().is_some());
+    }
+
+    #[test]
+    fn poll_scan_point_of_silent_device() {
+        let mut rplidar = mock_device(&[]);
+        let err = rplidar.poll_scan_point_with_timeout(Duration::from_millis(20)).unwrap_err();
+        assert!(is_timeout(&err));
+
+        let stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &dense_capsule(0, 1500), true);
+        let mut rplidar = mock_device(&stream);
+        let err = rplidar.grab_scan_point_with_timeout(Duration::from_millis(20)).unwrap_err();
+        assert!(is_timeout(&err));
+    }
+
+    #[test]
+    fn count_session_stats() {
+        let mut bad = dense_capsule(4 << 6, 1600);
+        bad[10] ^= 0x01;
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &dense_capsule(0, 1500), true);
+        stream.extend(bad);
+        stream.extend(dense_capsule(8 << 6, 1700));
+
+        let mut rplidar = mock_device(&stream);
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "checksum mismatch"));
+        rplidar.grab_scan_point().unwrap();
+
+        assert_eq!(
+            *rplidar.stats(),
+            SessionStats {
+                points_emitted: 40,
+                checksum_failures: 1,
+                protocol_errors: 1,
+                capsules_parsed: 2,
+            }
+        );
+
+        rplidar.reset_stats();
+        assert_eq!(*rplidar.stats(), SessionStats::default());
+    }
+
+    #[test]
+    fn count_only_checksum_mismatches_as_checksum_failures() {
+        let mut missing_sync = dense_capsule(4 << 6, 1600);
+        missing_sync[0] &= 0x0f;
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &dense_capsule(0, 1500), true);
+        stream.extend(missing_sync);
+
+        let mut rplidar = mock_device(&stream);
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "miss sync 1"));
+        assert_eq!(rplidar.stats().protocol_errors, 1);
+        assert_eq!(rplidar.stats().checksum_failures, 0);
+
+        let mut bad_crc = hq_capsule(0, 4000);
+        let len = bad_crc.len();
+        bad_crc[len - 1] ^= 0x01;
+
+        let mut rplidar = mock_device(&answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &bad_crc, true));
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "checksum mismatch"));
+        assert_eq!(rplidar.stats().checksum_failures, 1);
+    }
+
+    #[test]
+    fn verify_recorded_capsule_checksum() {
+        let good = dense_capsule(0, 1500);
+        verify_capsule_checksum(&Message::with_data(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &good)).unwrap();
+
+        let mut bad = good.clone();
+        bad[10] ^= 0x01;
+        let err = verify_capsule_checksum(&Message::with_data(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &bad)).unwrap_err();
+        assert!(is_protocol_error(&err, "checksum mismatch"));
+    }
+
+    #[test]
+    fn decode_dense_capsules() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &dense_capsule(0, 1500), true);
+        stream.extend(dense_capsule(4 << 6, 1600));
+        stream.extend(dense_capsule(8 << 6, 1700));
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.grab_points_available(Duration::from_millis(100)).unwrap().is_empty());
+
+        // cabins of a capsule are decoded when the next capsule arrives
+        for (start_deg, dist_mm) in [(0f32, 1500), (4f32, 1600)].iter() {
+            let points = rplidar.grab_points_available(Duration::from_millis(100)).unwrap();
+            assert_eq!(points.len(), 40);
+            assert!(points.iter().all(|point| point.dist_mm_q2 == dist_mm << 2));
+            assert!(points[0].angle() >= start_deg.to_radians() - 1e-3);
+            assert!(points[39].angle() < (start_deg + 4f32).to_radians());
+        }
+    }
+
+    #[test]
+    fn decode_dense_capsule_bytes() {
+        // 90 degrees, cabins of 1000 + 25 * i mm (cabin 7 has no return)
+        let first = [
+            0xa2, 0x58, 0x80, 0x16, 0xe8, 0x03, 0x01, 0x04, 0x1a, 0x04, 0x33, 0x04, 0x4c, 0x04,
+            0x65, 0x04, 0x7e, 0x04, 0x00, 0x00, 0xb0, 0x04, 0xc9, 0x04, 0xe2, 0x04, 0xfb, 0x04,
+            0x14, 0x05, 0x2d, 0x05, 0x46, 0x05, 0x5f, 0x05, 0x78, 0x05, 0x91, 0x05, 0xaa, 0x05,
+            0xc3, 0x05, 0xdc, 0x05, 0xf5, 0x05, 0x0e, 0x06, 0x27, 0x06, 0x40, 0x06, 0x59, 0x06,
+            0x72, 0x06, 0x8b, 0x06, 0xa4, 0x06, 0xbd, 0x06, 0xd6, 0x06, 0xef, 0x06, 0x08, 0x07,
+            0x21, 0x07, 0x3a, 0x07, 0x53, 0x07, 0x6c, 0x07, 0x85, 0x07, 0x9e, 0x07, 0xb7, 0x07,
+        ];
+        // 93 degrees, cabins of 2000 + i mm
+        let second = [
+            0xa7, 0x55, 0x40, 0x17, 0xd0, 0x07, 0xd1, 0x07, 0xd2, 0x07, 0xd3, 0x07, 0xd4, 0x07,
+            0xd5, 0x07, 0xd6, 0x07, 0xd7, 0x07, 0xd8, 0x07, 0xd9, 0x07, 0xda, 0x07, 0xdb, 0x07,
+            0xdc, 0x07, 0xdd, 0x07, 0xde, 0x07, 0xdf, 0x07, 0xe0, 0x07, 0xe1, 0x07, 0xe2, 0x07,
+            0xe3, 0x07, 0xe4, 0x07, 0xe5, 0x07, 0xe6, 0x07, 0xe7, 0x07, 0xe8, 0x07, 0xe9, 0x07,
+            0xea, 0x07, 0xeb, 0x07, 0xec, 0x07, 0xed, 0x07, 0xee, 0x07, 0xef, 0x07, 0xf0, 0x07,
+            0xf1, 0x07, 0xf2, 0x07, 0xf3, 0x07, 0xf4, 0x07, 0xf5, 0x07, 0xf6, 0x07, 0xf7, 0x07,
+        ];
+        // the cabins of the first capsule spread over the 3 degrees to the second, as the Slamtec SDK computes
+        let expected_angles_z_q14: [u16; 40] = [
+            16384, 16395, 16409, 16423, 16438, 16449, 16463, 16477, 16492, 16506, 16517, 16531, 16546, 16560,
+            16574, 16585, 16600, 16614, 16628, 16642, 16654, 16668, 16682, 16696, 16711, 16722, 16736, 16750,
+            16765, 16779, 16790, 16804, 16819, 16833, 16847, 16859, 16873, 16887, 16901, 16915,
+        ];
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &first, true);
+        stream.extend_from_slice(&second);
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.grab_points_available(Duration::from_millis(100)).unwrap().is_empty());
+
+        let points = rplidar.grab_points_available(Duration::from_millis(100)).unwrap();
+        assert_eq!(points.iter().map(|point| point.angle_z_q14).collect::<Vec<_>>(), expected_angles_z_q14);
+        for (i, point) in points.iter().enumerate() {
+            let dist_mm = if i == 7 { 0 } else { 1000 + 25 * i as u32 };
+            assert_eq!(point.dist_mm_q2, dist_mm << 2);
+            assert_eq!(point.is_valid(), i != 7);
+            assert!(!point.is_sync());
+        }
+    }
+
+    #[test]
+    fn report_capsule_start_angles() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED, &dense_capsule(350 << 6, 1500), true);
+        for start_deg in [354u16, 358, 2, 6].iter() {
+            stream.extend(dense_capsule(start_deg << 6, 1500));
+        }
+
+        let mut rplidar = mock_device(&stream);
+
+        // the scan ends at the sync point in the capsule starting at 358 degrees
+        let (scan, angles) = rplidar.grab_scan_with_capsule_angles().unwrap();
+        assert_eq!(scan.len(), angles.len());
+        assert!(scan.len() > 80 && scan.len() < 120);
+        assert!(angles[..40].iter().all(|angle| *angle == 350f32));
+        assert!(angles[40..80].iter().all(|angle| *angle == 354f32));
+        assert!(angles[80..].iter().all(|angle| *angle == 358f32));
+    }
+
+    #[test]
+    fn grab_scan_frame_discards_partial_rotation() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(16384, 4000), true);
+        stream.extend(hq_sync_capsule(0, 4000));
+        stream.extend(hq_capsule(32768, 4000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+
+        let scan = rplidar.grab_scan_frame_with_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(scan.len(), 32);
+        assert!(scan[0].is_sync());
+        assert_eq!(scan[16].angle_z_q14, 32768);
+
+        // the next rotation is kept
+        assert!(rplidar.grab_scan_point().unwrap().is_sync());
+    }
+
+    #[test]
+    fn measure_scan_frequency_skips_dropped_rotations() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(32768, 4000), true);
+        // rotations of 64, 16 (dropped data), 64 and 64 points
+        for capsules in [4, 1, 4, 4].iter() {
+            stream.extend(hq_sync_capsule(0, 4000));
+            for i in 1..*capsules {
+                stream.extend(hq_capsule(i * 16384, 4000));
+            }
+        }
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mode = ScanMode {
+            id: 0,
+            name: "Standard".to_owned(),
+            us_per_sample: 1000f32,
+            max_distance: 12f32,
+            ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_HQ,
+        };
+
+        let mut rplidar = mock_device(&stream);
+        let measured = rplidar
+            .measure_scan_frequency_with_timeout(&mode, 2, Duration::from_millis(100))
+            .unwrap();
+        assert_eq!(measured.points_per_rev, 64);
+        assert!((measured.hz - 15.625f32).abs() < 1e-3);
+
+        // only short rotations are left
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        stream.extend(hq_sync_capsule(0, 4000));
+        stream.extend(hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar
+            .measure_scan_frequency_with_timeout(&mode, 1, Duration::from_millis(50))
+            .is_err());
+        assert!(rplidar.measure_scan_frequency(&mode, 0).is_err());
+    }
+
+    #[test]
+    fn detect_rotations_by_angle_wrap() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        for i in 1..9 {
+            stream.extend(hq_capsule((i % 4) * 16384, 4000));
+        }
+
+        for mode in [SyncMode::AngleWrap, SyncMode::Auto].iter() {
+            let mut rplidar = mock_device(&stream);
+            rplidar.set_sync_interpretation(*mode);
+            assert_eq!(rplidar.sync_interpretation(), *mode);
+
+            let scan = rplidar.grab_scan_with_timeout(Duration::from_millis(100)).unwrap();
+            assert_eq!(scan.len(), 64);
+            assert!(!scan[0].is_sync());
+
+            let scan = rplidar.grab_scan_with_timeout(Duration::from_millis(100)).unwrap();
+            assert_eq!(scan.len(), 64);
+            assert!(scan[0].is_sync());
+            assert_eq!(scan[0].angle_z_q14, 0);
+        }
+
+        // no rotation boundary with the standard sync flag
+        let mut rplidar = mock_device(&stream);
+        assert!(rplidar.grab_scan_with_timeout(Duration::from_millis(10)).is_err());
+    }
+
+    #[test]
+    fn drop_oldest_points_beyond_cache_limit() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(hq_capsule(256, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.max_cached_points(), None);
+        rplidar.set_max_cached_points(Some(20));
+
+        // both capsules are decoded before the first point is taken
+        rplidar.wait_scan_data_with_timeout(RPLIDAR_DEFAULT_TIMEOUT).unwrap();
+        rplidar.wait_scan_data_with_timeout(RPLIDAR_DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(rplidar.dropped_point_count(), 12);
+
+        let angles: Vec<u16> = (0..20).map(|_| rplidar.grab_scan_point().unwrap().angle_z_q14).collect();
+        assert_eq!(angles[0], 12 * 16);
+        assert_eq!(angles[19], 256 + 15 * 16);
+    }
+
+    #[test]
+    fn configurable_cache_depth() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(hq_capsule(256, 4000));
+
+        let mut rplidar = RplidarDevice::with_stream_and_cache_depth(Box::new(MockStream::new(&stream)), 4);
+        assert!(rplidar.cached_measurement_nodes.capacity() >= 4);
+        assert!(rplidar.cached_measurement_nodes.capacity() < RPLIDAR_DEFAULT_CACHE_DEPTH);
+
+        let angles: Vec<u16> = (0..32).map(|_| rplidar.grab_scan_point().unwrap().angle_z_q14).collect();
+        assert_eq!(angles[31], 256 + 15 * 16);
+
+        rplidar.set_cache_depth(1024);
+        assert!(rplidar.cached_measurement_nodes.capacity() >= 1024);
+        rplidar.set_cache_depth(16);
+        assert!(rplidar.cached_measurement_nodes.capacity() < 1024);
+
+        assert!(mock_device(&[]).cached_measurement_nodes.capacity() >= RPLIDAR_DEFAULT_CACHE_DEPTH);
+    }
+
+    #[test]
+    fn decode_buffer_high_water_of_burst() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        for i in 1..4 {
+            stream.extend(hq_capsule(i * 256, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.decode_buffer_high_water(), 0);
+        assert!(rplidar.decode_buffer_capacity() > stream.len());
+
+        rplidar.grab_scan_point().unwrap();
+        assert_eq!(rplidar.decode_buffer_high_water(), stream.len());
+        assert!(rplidar.pending_bytes() < stream.len());
+
+        rplidar.reset_decode_stats();
+        assert_eq!(rplidar.decode_buffer_high_water(), rplidar.pending_bytes());
+    }
+
+    #[test]
+    fn try_grab_scan_point_without_waiting() {
+        let node = |angle_q6: u16| {
+            let mut node = vec![(0x2f << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT) | RPLIDAR_RESP_MEASUREMENT_INVERTED_SYNCBIT, 0, 0, 0, 0];
+            LittleEndian::write_u16(&mut node[1..3], (angle_q6 << RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT) | 1);
+            LittleEndian::write_u16(&mut node[3..5], 4000);
+            node
+        };
+
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, &node(0), true);
+        stream.extend_from_slice(&node(90 * 64)[..2]);
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.try_grab_scan_point().unwrap().unwrap().angle_z_q14, 0);
+        assert_eq!(rplidar.try_grab_scan_point().unwrap(), None);
+
+        // the incomplete node is completed by the data received later
+        rplidar.transport_mut().rx.extend(&node(90 * 64)[2..]);
+        assert_eq!(rplidar.try_grab_scan_point().unwrap().unwrap().angle_z_q14, 16384);
+        assert_eq!(rplidar.try_grab_scan_point().unwrap(), None);
+    }
+
+    #[test]
+    fn try_grab_scan_point_on_timed_out_transport() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(hq_capsule(256, 4000));
+
+        let mut rplidar = mock_device(&[]);
+        rplidar.transport_mut().timeout_when_empty = true;
+        assert_eq!(rplidar.try_grab_scan_point().unwrap(), None);
+        assert_eq!(rplidar.transport_mut().reads, 1);
+
+        // the points of the capsules read at once are decoded from the buffer without reading the transport
+        rplidar.transport_mut().rx.extend(stream);
+        assert_eq!(rplidar.try_grab_scan_point().unwrap().unwrap().angle_z_q14, 0);
+        let reads = rplidar.transport_mut().reads;
+        for i in 1..32 {
+            assert_eq!(rplidar.try_grab_scan_point().unwrap().unwrap().angle_z_q14, i * 16);
+        }
+        assert_eq!(rplidar.transport_mut().reads, reads);
+
+        assert_eq!(rplidar.try_grab_scan_point().unwrap(), None);
+        assert_eq!(rplidar.transport_mut().reads, reads + 1);
+    }
+
+    #[test]
+    fn iterate_scan_points() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(hq_capsule(256, 4000));
+
+        let mut rplidar = mock_device(&stream);
+
+        let angles: Vec<u16> = rplidar
+            .iter_scan_points_with_timeout(Duration::from_millis(10))
+            .take(32)
+            .map(|point| point.unwrap().angle_z_q14)
+            .collect();
+        assert_eq!(angles[1], 16);
+        assert_eq!(angles[31], 256 + 15 * 16);
+
+        // timeouts are yielded instead of ending the iteration
+        let mut points = rplidar.iter_scan_points_with_timeout(Duration::from_millis(10));
+        assert!(is_timeout(&points.next().unwrap().unwrap_err()));
+        points.set_timeout(Duration::from_millis(1));
+        assert!(points.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn decode_error_handler_receives_context() {
+        let mut corrupt = hq_capsule(0, 4000);
+        corrupt[20] ^= 0xff;
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &corrupt, true);
+        stream.extend(hq_capsule(256, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let handler_errors = errors.clone();
+        rplidar.set_decode_error_handler(Box::new(move |err| handler_errors.borrow_mut().push(err.clone())));
+
+        assert!(is_protocol_error(&rplidar.grab_scan_point().unwrap_err(), "checksum mismatch"));
+        assert_eq!(rplidar.grab_scan_point().unwrap().angle_z_q14, 256);
+
+        let errors = errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].ans_type, RPLIDAR_ANS_TYPE_MEASUREMENT_HQ);
+        assert_eq!(errors[0].message_len, corrupt.len());
+        assert!(errors[0].reason.contains("checksum mismatch"), "{}", errors[0].reason);
+    }
+
+    #[test]
+    fn raw_message_observer_sees_all_messages() {
+        let mut corrupt = hq_capsule(256, 4000);
+        corrupt[20] ^= 0xff;
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend(corrupt);
+        stream.extend(hq_capsule(512, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let observer_messages = observed.clone();
+        rplidar.set_raw_message_observer(Box::new(move |msg| observer_messages.borrow_mut().push(msg.cmd)));
+
+        for _ in 0..3 {
+            let _ = rplidar.wait_scan_data_with_timeout(Duration::from_millis(10));
+        }
+
+        assert_eq!(*observed.borrow(), vec![RPLIDAR_ANS_TYPE_MEASUREMENT_HQ; 3]);
+        assert_eq!(rplidar.cached_measurement_nodes.len(), 32);
+    }
+
+    #[test]
+    fn range_bias_shifts_distances() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 1015 * 4), true);
+        stream.extend(hq_capsule(256, 10 * 4));
+        stream.extend(hq_capsule(512, 1015 * 4));
+
+        let mut rplidar = mock_device(&stream);
+
+        let scan: Vec<ScanPoint> = (0..16).map(|_| rplidar.grab_scan_point().unwrap()).collect();
+        let bias = rplidar.calibrate_range_bias(1000f32, &scan);
+        assert_eq!(bias, 15f32);
+
+        rplidar.set_range_bias(bias);
+        assert_eq!(rplidar.range_bias(), 15f32);
+        assert_eq!(rplidar.calibrate_range_bias(1000f32, &scan), 30f32);
+
+        // points closer than the bias become invalid
+        let point = rplidar.grab_scan_point().unwrap();
+        assert_eq!(point.dist_mm_q2, 0);
+        assert!(!point.is_valid());
+
+        while rplidar.grab_scan_point().unwrap().angle_z_q14 < 512 {}
+        assert_eq!(rplidar.grab_scan_point().unwrap().dist_mm_q2, 1000 * 4);
+
+        assert!(rplidar.calibrate_range_bias(1000f32, &[]).is_nan());
+    }
+
+    #[test]
+    fn angular_decimation_keeps_every_nth_point() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        for i in 1..4 {
+            stream.extend_from_slice(&hq_capsule(i * 256, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.angular_decimation(), 1);
+        rplidar.set_angular_decimation(4);
+
+        let mut points = Vec::new();
+        for _ in 0..4 {
+            points.extend(rplidar.grab_points_available(Duration::from_millis(10)).unwrap());
+        }
+
+        assert_eq!(points.len(), 16);
+        assert_eq!(points[0].angle_z_q14, 0);
+        assert_eq!(points[1].angle_z_q14, 64);
+
+        rplidar.set_angular_decimation(0);
+        assert_eq!(rplidar.angular_decimation(), 1);
+    }
+
+    #[test]
+    fn grab_scan_until_predicate() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        for i in 1..4 {
+            stream.extend_from_slice(&hq_capsule(i * 256, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+
+        let points = rplidar.grab_scan_until(|points| points.len() >= 50, Duration::from_millis(100)).unwrap();
+        assert_eq!(points.len(), 50);
+        assert_eq!(points[49].angle_z_q14, 3 * 256 + 16);
+
+        // points accumulated before timeout are kept
+        assert!(rplidar.grab_scan_until(|_| false, Duration::from_millis(10)).is_err());
+        let points = rplidar.grab_scan_until(|points| points.len() >= 14, Duration::from_millis(10)).unwrap();
+        assert_eq!(points[0].angle_z_q14, 3 * 256 + 32);
+    }
+
+    #[test]
+    fn count_rotation_boundaries() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        stream.extend_from_slice(&hq_capsule(256, 4000));
+        stream.extend_from_slice(&hq_sync_capsule(0, 4000));
+        stream.extend_from_slice(&hq_sync_capsule(0, 4000));
+
+        let mut rplidar = mock_device(&stream);
+        assert_eq!(rplidar.rotation_count(), 0);
+
+        rplidar.grab_points_available(Duration::from_millis(10)).unwrap();
+        rplidar.grab_points_available(Duration::from_millis(10)).unwrap();
+        assert_eq!(rplidar.rotation_count(), 1);
+
+        rplidar.grab_points_available(Duration::from_millis(10)).unwrap();
+        rplidar.grab_points_available(Duration::from_millis(10)).unwrap();
+        assert_eq!(rplidar.rotation_count(), 3);
+
+        rplidar.reset_rotation_count();
+        assert_eq!(rplidar.rotation_count(), 0);
+    }
+
+    #[test]
+    fn stream_changed_bins() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_sync_capsule(0, 4000), true);
+        for distance in [4000, 8000].iter() {
+            stream.extend(hq_capsule(16384, 4000));
+            stream.extend(hq_capsule(32768, *distance));
+            stream.extend(hq_capsule(49152, 4000));
+            stream.extend(hq_sync_capsule(0, 4000));
+        }
+
+        let mut rplidar = mock_device(&stream);
+
+        let deltas = rplidar.scan_delta_stream(4, 0.1f32).unwrap();
+        assert_eq!(deltas, [(0, 1f32), (1, 1f32), (2, 1f32), (3, 1f32)]);
+
+        let deltas = rplidar.scan_delta_stream(4, 0.1f32).unwrap();
+        assert_eq!(deltas, [(2, 2f32)]);
+    }
+
+    #[test]
+    fn grab_points_available_returns_single_capsule() {
+        let mut stream = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &hq_capsule(0, 4000), true);
+        stream.extend_from_slice(&hq_capsule(256, 4000));
+
+        let mut rplidar = mock_device(&stream);
+
+        let points = rplidar.grab_points_available(Duration::from_millis(10)).unwrap();
+        assert_eq!(points.len(), 16);
+        assert_eq!(points[1].angle_z_q14, 16);
+
+        let points = rplidar.grab_points_available(Duration::from_millis(10)).unwrap();
+        assert_eq!(points.len(), 16);
+        assert_eq!(points[0].angle_z_q14, 256);
+    }
 }
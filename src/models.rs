@@ -0,0 +1,214 @@
+use super::answers::RplidarResponseDeviceInfo;
+use std::fmt;
+
+/// Major model id of C-series
+const RPLIDAR_C_SERIES_MAJOR_ID: u8 = 4;
+
+/// Major model id of the first S-series LIDAR (S1), later S-series LIDARs follow
+const RPLIDAR_S_SERIES_FIRST_MAJOR_ID: u8 = 6;
+
+/// Major model id of the first T-series LIDAR (T1), later T-series LIDARs follow
+const RPLIDAR_T_SERIES_FIRST_MAJOR_ID: u8 = 9;
+
+/// Product family of RPLIDAR (with the series number)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LidarFamily {
+    /// A-series triangulation LIDARs (A1, A2, A3)
+    ASeries(u8),
+
+    /// C-series ToF LIDARs (C1)
+    CSeries(u8),
+
+    /// S-series ToF LIDARs (S1, S2, S3)
+    SSeries(u8),
+
+    /// T-series ToF LIDARs (T1)
+    TSeries(u8),
+}
+
+impl LidarFamily {
+    /// get the family from the model byte in device info
+    pub fn from_model(model: u8) -> LidarFamily {
+        let major = model >> 4;
+
+        if major >= RPLIDAR_T_SERIES_FIRST_MAJOR_ID {
+            return LidarFamily::TSeries(major - RPLIDAR_T_SERIES_FIRST_MAJOR_ID + 1);
+        } else if major >= RPLIDAR_S_SERIES_FIRST_MAJOR_ID {
+            return LidarFamily::SSeries(major - RPLIDAR_S_SERIES_FIRST_MAJOR_ID + 1);
+        } else if major == RPLIDAR_C_SERIES_MAJOR_ID {
+            return LidarFamily::CSeries(1);
+        } else {
+            return LidarFamily::ASeries(major);
+        }
+    }
+
+    /// the default baud rate used by the UART interface of this family
+    pub fn default_baud_rate(&self) -> u32 {
+        match self {
+            LidarFamily::ASeries(series) if *series >= 3 => 256000,
+            LidarFamily::ASeries(_) => 115200,
+            LidarFamily::CSeries(_) => 460800,
+            LidarFamily::SSeries(1) => 256000,
+            LidarFamily::SSeries(_) => 1000000,
+            LidarFamily::TSeries(_) => 1000000,
+        }
+    }
+
+    /// whether the LIDAR measures distance with time of flight
+    pub fn is_tof(&self) -> bool {
+        return !matches!(self, LidarFamily::ASeries(_));
+    }
+}
+
+/// Known RPLIDAR models
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Model {
+    A1,
+    A2,
+    A3,
+    S1,
+    S2,
+    S2E,
+    S3,
+    C1,
+    T1,
+
+    /// Models not known yet, with the raw model byte
+    Unknown(u8),
+}
+
+impl Model {
+    /// get the model from the model byte in device info
+    pub fn from_model(model: u8) -> Model {
+        match model {
+            0x72 => Model::S2E,
+            _ => match model >> 4 {
+                1 => Model::A1,
+                2 => Model::A2,
+                3 => Model::A3,
+                4 => Model::C1,
+                6 => Model::S1,
+                7 => Model::S2,
+                8 => Model::S3,
+                9 => Model::T1,
+                _ => Model::Unknown(model),
+            },
+        }
+    }
+
+    /// whether the model acknowledges `SET_MOTOR_PWM` with an answer
+    pub fn acks_motor_pwm(&self) -> bool {
+        return *self == Model::A3;
+    }
+}
+
+impl RplidarResponseDeviceInfo {
+    /// get the product family of the LIDAR
+    pub fn family(&self) -> LidarFamily {
+        LidarFamily::from_model(self.model)
+    }
+
+    /// get the model of the LIDAR (the raw byte is kept in `model` field)
+    pub fn model(&self) -> Model {
+        Model::from_model(self.model)
+    }
+
+    /// get the serial number of the LIDAR as upper case hex string (bytes in stored order, as the official SDK prints)
+    pub fn serial_number(&self) -> String {
+        { self.serialnum }.iter().map(|byte| format!("{:02X}", byte)).collect()
+    }
+
+    /// get the firmware version as `major.minor` string (e.g. "1.29"), the raw value is kept in `firmware_version` field
+    pub fn firmware_version(&self) -> String {
+        let version = self.firmware_version;
+        format!("{}.{:02}", version >> 8, version & 0xff)
+    }
+
+    /// get the hardware version
+    pub fn hardware_version(&self) -> u8 {
+        self.hardware_version
+    }
+}
+
+impl fmt::Display for RplidarResponseDeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(
+            f,
+            "Model: {:?} (0x{:02X}), Firmware: {}, Hardware: {}, Serial: {}",
+            self.model(),
+            self.model,
+            self.firmware_version(),
+            self.hardware_version(),
+            self.serial_number()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn family_from_model() {
+        assert_eq!(LidarFamily::from_model(0x18), LidarFamily::ASeries(1));
+        assert_eq!(LidarFamily::from_model(0x31), LidarFamily::ASeries(3));
+        assert_eq!(LidarFamily::from_model(0x61), LidarFamily::SSeries(1));
+        assert_eq!(LidarFamily::from_model(0x71), LidarFamily::SSeries(2));
+
+        let t1 = LidarFamily::from_model(0x91);
+        assert_eq!(t1, LidarFamily::TSeries(1));
+        assert_eq!(t1.default_baud_rate(), 1000000);
+        assert!(t1.is_tof());
+
+        let c1 = LidarFamily::from_model(0x41);
+        assert_eq!(c1, LidarFamily::CSeries(1));
+        assert_eq!(c1.default_baud_rate(), 460800);
+        assert!(c1.is_tof());
+        assert_eq!(Model::from_model(0x41), Model::C1);
+
+        assert_eq!(LidarFamily::from_model(0x81), LidarFamily::SSeries(3));
+        assert_eq!(LidarFamily::from_model(0x18).default_baud_rate(), 115200);
+        assert_eq!(LidarFamily::from_model(0x61).default_baud_rate(), 256000);
+    }
+
+    #[test]
+    fn format_device_info() {
+        let data = [
+            0x18, 0x1d, 0x01, 0x07, 0xb5, 0xe3, 0x9a, 0xf0, 0xc3, 0xe3, 0x9e, 0xd4, 0xa0, 0xe5, 0x9f, 0xf6, 0x2a, 0x3d,
+            0x4c, 0x00,
+        ];
+        let mut serialnum = [0u8; 16];
+        serialnum.copy_from_slice(&data[4..20]);
+        let info = RplidarResponseDeviceInfo {
+            model: data[0],
+            firmware_version: u16::from(data[1]) | (u16::from(data[2]) << 8),
+            hardware_version: data[3],
+            serialnum: serialnum,
+        };
+
+        assert_eq!(info.serial_number(), "B5E39AF0C3E39ED4A0E59FF62A3D4C00");
+        assert_eq!(info.firmware_version(), "1.29");
+        assert_eq!(info.hardware_version(), 7);
+        assert_eq!(
+            info.to_string(),
+            "Model: A1 (0x18), Firmware: 1.29, Hardware: 7, Serial: B5E39AF0C3E39ED4A0E59FF62A3D4C00"
+        );
+
+        let info = RplidarResponseDeviceInfo { firmware_version: 0x0105, ..info };
+        assert_eq!(info.firmware_version(), "1.05");
+    }
+
+    #[test]
+    fn model_from_byte() {
+        assert_eq!(Model::from_model(0x18), Model::A1);
+        assert_eq!(Model::from_model(0x28), Model::A2);
+        assert_eq!(Model::from_model(0x31), Model::A3);
+        assert_eq!(Model::from_model(0x41), Model::C1);
+        assert_eq!(Model::from_model(0x61), Model::S1);
+        assert_eq!(Model::from_model(0x71), Model::S2);
+        assert_eq!(Model::from_model(0x72), Model::S2E);
+        assert_eq!(Model::from_model(0x81), Model::S3);
+        assert_eq!(Model::from_model(0x91), Model::T1);
+        assert_eq!(Model::from_model(0xF1), Model::Unknown(0xF1));
+    }
+}
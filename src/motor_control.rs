@@ -0,0 +1,12 @@
+use super::errors::*;
+use std::fmt::Debug;
+
+/// Control of the motor through a line of the transport, for LIDARs without PWM motor control (e.g. A1)
+///
+/// Implement it for serial port wrappers and install it with `RplidarDevice::set_motor_control`
+pub trait MotorControl: Debug {
+    /// set the DTR line of the transport
+    ///
+    /// The adapter boards of A-series LIDARs spin the motor while DTR is cleared
+    fn set_dtr(&mut self, on: bool) -> Result<()>;
+}
@@ -1,9 +1,17 @@
 use std::f32::consts::PI;
-use super::answers::RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
+use super::answers::*;
+use super::cmds::{RPLIDAR_EXPRESS_SCAN_FLAG_BOOST, RPLIDAR_EXPRESS_SCAN_FLAG_SUNLIGHT_REJECTION};
+use super::errors::*;
+use super::internals::RPLIDAR_EXPRESS_SCAN_START_VERSION;
+use super::utils::bin_scan_with;
 use std::cmp::Ordering;
+use std::time::Instant;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Scan point in a particular laser scan
 #[derive(Debug, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScanPoint {
     pub angle_z_q14: u16,
     pub dist_mm_q2: u32,
@@ -16,6 +24,11 @@ impl ScanPoint {
         return (self.angle_z_q14 as f32) / 16384f32 / 2f32 * PI;
     }
 
+    /// angle in degrees, measured the same way as `angle()`
+    pub fn angle_degrees(&self) -> f32 {
+        return (self.angle_z_q14 as f32) * 90f32 / 16384f32;
+    }
+
     pub fn set_angle(&mut self, angle:f32) {
         self.angle_z_q14 = (angle * 16384f32 * 2f32 / PI) as u16;
     }
@@ -24,6 +37,11 @@ impl ScanPoint {
         return (self.dist_mm_q2 as f32) / 4000f32;
     }
 
+    /// distance in `unit`, computed from the raw `dist_mm_q2` (which is always in 1/4 mm)
+    pub fn distance_in(&self, unit: DistanceUnit) -> f32 {
+        return (self.dist_mm_q2 as f32) / 4f32 / unit.mm_per_unit();
+    }
+
     pub fn set_distance(&mut self, dist: f32) {
         self.dist_mm_q2 = (dist * 4000f32) as u32;
     }
@@ -35,6 +53,95 @@ impl ScanPoint {
     pub fn is_valid(&self) -> bool {
         return self.quality != 0 && self.dist_mm_q2 != 0;
     }
+
+    /// convert to `(x, y)` in millimeters, with the angle measured the same way as `angle()`
+    pub fn to_cartesian(&self) -> (f32, f32) {
+        let angle = self.angle();
+        let dist_mm = (self.dist_mm_q2 as f32) / 4f32;
+        return (dist_mm * angle.cos(), dist_mm * angle.sin());
+    }
+
+    /// convert to `(x, y)` in meters, with the angle measured the same way as `angle()`
+    pub fn as_cartesian(&self) -> (f32, f32) {
+        let angle = self.angle();
+        let dist = self.distance();
+        return (dist * angle.cos(), dist * angle.sin());
+    }
+
+    /// distance in fixed point millimeters (q2, 1mm = 4)
+    pub fn distance_mm_q2(&self) -> u32 {
+        return self.dist_mm_q2;
+    }
+
+    /// angle in fixed point (q14, 90 degrees = 16384, so a full rotation wraps around `u16`)
+    pub fn angle_q14(&self) -> u16 {
+        return self.angle_z_q14;
+    }
+
+    /// convert to `(x, y)` in fixed point millimeters (q2, 1mm = 4) without floating point operations
+    ///
+    /// Same as `to_cartesian`, but trigonometric functions are interpolated from a table,
+    /// so the error is within 1/5000 of the distance
+    pub fn to_cartesian_q2(&self) -> (i32, i32) {
+        let dist_q2 = self.dist_mm_q2 as i64;
+        let x = (dist_q2 * (cos_q15(self.angle_z_q14) as i64)) >> 15;
+        let y = (dist_q2 * (sin_q15(self.angle_z_q14) as i64)) >> 15;
+        return (x as i32, y as i32);
+    }
+}
+
+/// sin of `[0, 90]` degrees in 64 steps, in q15
+const SIN_TABLE_Q15: [i32; 65] = [
+    0, 804, 1608, 2411, 3212, 4011, 4808, 5602,
+    6393, 7180, 7962, 8740, 9512, 10279, 11039, 11793,
+    12540, 13279, 14010, 14733, 15447, 16151, 16846, 17531,
+    18205, 18868, 19520, 20160, 20788, 21403, 22006, 22595,
+    23170, 23732, 24279, 24812, 25330, 25833, 26320, 26791,
+    27246, 27684, 28106, 28511, 28899, 29269, 29622, 29957,
+    30274, 30572, 30853, 31114, 31357, 31581, 31786, 31972,
+    32138, 32286, 32413, 32522, 32610, 32679, 32729, 32758,
+    32768,
+];
+
+/// sin of angle within `[0, 16384]` (q14), interpolated from the table
+fn quarter_sin_q15(angle_q14: u32) -> i32 {
+    let index = (angle_q14 >> 8) as usize;
+    if index >= SIN_TABLE_Q15.len() - 1 {
+        return SIN_TABLE_Q15[SIN_TABLE_Q15.len() - 1];
+    }
+
+    let frac = (angle_q14 & 0xff) as i32;
+    return SIN_TABLE_Q15[index] + (((SIN_TABLE_Q15[index + 1] - SIN_TABLE_Q15[index]) * frac) >> 8);
+}
+
+fn sin_q15(angle_z_q14: u16) -> i32 {
+    let angle = (angle_z_q14 & 0x3fff) as u32;
+
+    match angle_z_q14 >> 14 {
+        0 => return quarter_sin_q15(angle),
+        1 => return quarter_sin_q15(16384 - angle),
+        2 => return -quarter_sin_q15(angle),
+        _ => return -quarter_sin_q15(16384 - angle),
+    }
+}
+
+fn cos_q15(angle_z_q14: u16) -> i32 {
+    return sin_q15(angle_z_q14.wrapping_add(16384));
+}
+
+/// Bins of `normalize_scan` giving one distance per integer degree
+pub const RPLIDAR_NORMALIZED_SCAN_DEFAULT_BINS: usize = 360;
+
+/// normalize the scan into `bins` equal angular slots over a full rotation, in meters
+///
+/// Bin `i` covers angles `[i, i + 1) * 360 / bins` degrees, measured the same way as `ScanPoint::angle()`
+/// (clockwise from the front of the LIDAR), so with `RPLIDAR_NORMALIZED_SCAN_DEFAULT_BINS` bin `i` is degree `i`.
+/// The highest-quality valid point of each bin is taken, and bins without valid points are `None`
+pub fn normalize_scan(points: &[ScanPoint], bins: usize) -> Vec<Option<f32>> {
+    return bin_scan_with(points, bins, |current, point| current.quality >= point.quality)
+        .iter()
+        .map(|point| point.map(|point| point.distance()))
+        .collect();
 }
 
 impl Ord for ScanPoint {
@@ -58,8 +165,39 @@ impl PartialEq for ScanPoint {
     }
 }
 
+/// Unit of distances reported by `ScanPoint::distance_in` and `RplidarDevice::scan_distances`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DistanceUnit {
+    /// Millimeters (default)
+    Millimeters,
+
+    /// Centimeters
+    Centimeters,
+
+    /// Meters
+    Meters,
+}
+
+impl Default for DistanceUnit {
+    fn default() -> DistanceUnit {
+        return DistanceUnit::Millimeters;
+    }
+}
+
+impl DistanceUnit {
+    /// millimeters in 1 of the unit
+    pub fn mm_per_unit(&self) -> f32 {
+        match self {
+            DistanceUnit::Millimeters => return 1f32,
+            DistanceUnit::Centimeters => return 10f32,
+            DistanceUnit::Meters => return 1000f32,
+        }
+    }
+}
+
 /// Description of a specific scan mode
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScanMode {
     /// The scan mode id
     pub id: u16,
@@ -79,6 +217,7 @@ pub struct ScanMode {
 
 /// Scan options
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScanOptions {
     /// Specify this field to force use specific scan mode
     pub scan_mode: Option<u16>,
@@ -88,24 +227,33 @@ pub struct ScanOptions {
 
     /// Parameters sent to LIDAR. Please use 0 for now
     pub options: u32,
+
+    /// Rotations discarded after the scan starts, as the first rotations after the motor spins up are
+    /// often distorted (0 by default). Points before the first rotation boundary are discarded as well
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warmup_rotations: usize,
 }
 
-impl ScanOptions {
+impl Default for ScanOptions {
     /// default options
-    pub fn default() -> ScanOptions {
+    fn default() -> ScanOptions {
         ScanOptions {
             scan_mode: None,
             force_scan: false,
             options: 0,
+            warmup_rotations: 0,
         }
     }
+}
 
+impl ScanOptions {
     /// with specific mode
     pub fn with_mode(scan_mode: u16) -> ScanOptions {
         ScanOptions {
             scan_mode: Some(scan_mode),
             force_scan: false,
             options: 0,
+            warmup_rotations: 0,
         }
     }
 
@@ -115,6 +263,7 @@ impl ScanOptions {
             scan_mode: None,
             force_scan: true,
             options: 0,
+            warmup_rotations: 0,
         }
     }
 
@@ -124,14 +273,482 @@ impl ScanOptions {
             scan_mode: Some(scan_mode),
             force_scan: true,
             options: 0,
+            warmup_rotations: 0,
+        }
+    }
+}
+
+/// Known work flags of express scan, set in `ScanOptions::options`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScanOption {
+    /// Boost the sample rate
+    Boost,
+
+    /// Reject the interference of sunlight
+    SunlightRejection,
+}
+
+impl ScanOption {
+    /// the bit of the flag in `ScanOptions::options`
+    pub fn bits(&self) -> u32 {
+        match self {
+            ScanOption::Boost => return RPLIDAR_EXPRESS_SCAN_FLAG_BOOST as u32,
+            ScanOption::SunlightRejection => return RPLIDAR_EXPRESS_SCAN_FLAG_SUNLIGHT_REJECTION as u32,
+        }
+    }
+}
+
+/// Builder of `ScanOptions`, which validates the combination of options
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScanOptionsBuilder {
+    options: ScanOptions,
+}
+
+impl ScanOptionsBuilder {
+    /// start with the default options
+    pub fn new() -> ScanOptionsBuilder {
+        return ScanOptionsBuilder::default();
+    }
+
+    /// force use specific scan mode
+    pub fn scan_mode(mut self, scan_mode: u16) -> ScanOptionsBuilder {
+        self.options.scan_mode = Some(scan_mode);
+        return self;
+    }
+
+    /// scan regardless of the LIDAR is spinning or not
+    pub fn force_scan(mut self, force_scan: bool) -> ScanOptionsBuilder {
+        self.options.force_scan = force_scan;
+        return self;
+    }
+
+    /// set a work flag of express scan
+    pub fn option_flag(mut self, flag: ScanOption) -> ScanOptionsBuilder {
+        self.options.options |= flag.bits();
+        return self;
+    }
+
+    /// rotations discarded after the scan starts
+    pub fn warmup_rotations(mut self, warmup_rotations: usize) -> ScanOptionsBuilder {
+        self.options.warmup_rotations = warmup_rotations;
+        return self;
+    }
+
+    /// build the options
+    ///
+    /// Work flags are only sent with express scans, so they can't be combined with force scan or
+    /// the standard mode (mode 0), which are started with legacy commands
+    pub fn build(self) -> Result<ScanOptions> {
+        if self.options.options != 0 && (self.options.force_scan || self.options.scan_mode == Some(0)) {
+            return Err(RposError::OperationFail {
+                description: "option flags are only supported by express scan".to_owned(),
+            }
+            .into());
+        }
+
+        return Ok(self.options);
+    }
+}
+
+/// Features supported by the accessory board, reported by `GET_ACC_BOARD_FLAG`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AccBoardFlag {
+    /// The raw flags
+    pub flags: u32,
+
+    /// The accessory board controls the motor with PWM
+    pub motor_ctrl: bool,
+}
+
+impl AccBoardFlag {
+    /// decode the raw flags
+    pub fn from_flags(flags: u32) -> AccBoardFlag {
+        AccBoardFlag {
+            flags: flags,
+            motor_ctrl: (flags & RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK) != 0,
+        }
+    }
+}
+
+/// Commands and features supported by the connected LIDAR
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// The LIDAR responds to `GET_LIDAR_CONF` (scan modes can be queried)
+    pub has_get_lidar_conf: bool,
+
+    /// The LIDAR responds to `GET_DEVICE_HEALTH`
+    pub has_health: bool,
+
+    /// An accessory board is attached
+    pub has_accessory_board: bool,
+
+    /// The accessory board supports motor control
+    pub has_motor_ctrl: bool,
+
+    /// The LIDAR supports express scan (`EXPRESS_SCAN`)
+    pub has_express: bool,
+}
+
+/// How to handle answers of unexpected types received during scan
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnexpectedAnswerPolicy {
+    /// Return protocol error (default)
+    Error,
+
+    /// Skip the answer and continue (the skipped answers are counted)
+    Skip,
+}
+
+/// How to handle legacy measurement nodes failing the check bit or sync bit validation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CorruptNodePolicy {
+    /// Return protocol error (default)
+    Error,
+
+    /// Drop the node and continue (the dropped nodes are counted)
+    Skip,
+}
+
+/// Details of a scan message failed to decode, passed to the handler set by `set_decode_error_handler`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    /// Answer type of the message
+    pub ans_type: u8,
+
+    /// Length of the message payload (in bytes)
+    pub message_len: usize,
+
+    /// Why the message failed to decode
+    pub reason: String,
+}
+
+/// Generation of the protocol, which decides the answer layouts the LIDAR may send
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    /// Firmware before 1.17, with legacy measurement nodes only
+    Legacy,
+
+    /// Firmware 1.17 to 1.23, with express scan capsules added
+    Express,
+
+    /// Firmware 1.24 and later, with scan modes in HQ, ultra and dense capsules added (default)
+    Extended,
+}
+
+impl ProtocolVersion {
+    /// the protocol generation of the firmware version (major in high byte, minor in low byte)
+    pub fn from_firmware_version(firmware_version: u16) -> ProtocolVersion {
+        if firmware_version < RPLIDAR_EXPRESS_SCAN_START_VERSION {
+            return ProtocolVersion::Legacy;
+        } else if firmware_version < super::RPLIDAR_GET_LIDAR_CONF_START_VERSION {
+            return ProtocolVersion::Express;
+        } else {
+            return ProtocolVersion::Extended;
+        }
+    }
+
+    /// whether the answers of `ans_type` carrying scan data exist in this protocol generation
+    pub(crate) fn has_measurement_ans_type(&self, ans_type: u8) -> bool {
+        match (self, ans_type) {
+            (_, RPLIDAR_ANS_TYPE_MEASUREMENT) => return true,
+            (ProtocolVersion::Express, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED) => return true,
+            (ProtocolVersion::Extended, _) => return true,
+            _ => return false,
         }
     }
 }
 
+/// How rotation boundaries are detected from decoded points
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SyncMode {
+    /// The sync flag reported by the LIDAR (default)
+    StandardBit,
+
+    /// The angle wrapping around 0 degree, for firmwares reporting misplaced sync flags
+    AngleWrap,
+
+    /// The sync flag if any has been seen since the scan started, otherwise the angle wrapping around
+    Auto,
+}
+
+/// Command used by `start_scan` to start the scan
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScanPath {
+    /// `SCAN` or `FORCE_SCAN` (scan mode 0)
+    Legacy,
+
+    /// `EXPRESS_SCAN` with the scan mode id
+    Express,
+}
+
+/// Kind of the transport connecting to the LIDAR
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransportKind {
+    /// UART or USB serial port (default)
+    Serial,
+
+    /// UDP/TCP on Ethernet models like S2E, where the motor is controlled with motor speed command instead of PWM
+    Ethernet,
+}
+
+/// A full rotation of scan points with metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    /// The scan points of the rotation
+    pub points: Vec<ScanPoint>,
+
+    /// The scan mode in which the scan is measured
+    pub mode: ScanMode,
+
+    /// Rotation frequency estimated from sample count and sample duration
+    pub frequency_hz: Option<f32>,
+
+    /// The time when the scan is grabbed
+    pub timestamp: Instant,
+
+    /// The sequence number of the scan (increases by one for every grabbed scan)
+    pub sequence: u64,
+}
+
+/// A full rotation of scan points with the time span it's measured in, grabbed by `grab_scan_monotonic`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanFrame {
+    /// The scan points of the rotation
+    pub points: Vec<ScanPoint>,
+
+    /// When the first point is measured, estimated from the sample rate (strictly increasing across frames)
+    pub start: Instant,
+
+    /// When the scan is grabbed (not earlier than `start`)
+    pub end: Instant,
+}
+
+/// Running counters of the scan data decoded since the device is created or `reset_stats` is called
+///
+/// Checksum failures are protocol errors too, so they are counted in both
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionStats {
+    /// Count of scan points decoded and cached for grabbing
+    pub points_emitted: u64,
+
+    /// Count of capsules failing the checksum (often caused by a bad cable)
+    pub checksum_failures: u64,
+
+    /// Count of protocol errors while waiting for scan data
+    pub protocol_errors: u64,
+
+    /// Count of capsules passing the checksum and parsed
+    pub capsules_parsed: u64,
+}
+
+/// Statistics of valid points in a scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanStats {
+    /// Count of valid points
+    pub valid_points: usize,
+
+    /// Distance of the nearest valid point in meters
+    pub min_distance: Option<f32>,
+
+    /// Distance of the farthest valid point in meters
+    pub max_distance: Option<f32>,
+
+    /// Mean distance of valid points in meters
+    pub mean_distance: Option<f32>,
+
+    /// Fraction of 1 degree bins with valid points
+    pub coverage: f32,
+}
+
+/// Data integrity report of a scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanValidation {
+    /// Ratio of actual points to expected points (capped at 1)
+    pub completeness: f32,
+
+    /// Points expected in a rotation, from sample rate and rotation frequency
+    pub expected_points: u32,
+
+    /// Points actually in the scan
+    pub actual_points: usize,
+
+    /// The scan starts with a point with sync flag (start of a rotation)
+    pub has_sync_start: bool,
+}
+
+/// Scan frequency measured by counting points of full rotations
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasuredScanFrequency {
+    /// Rotation frequency derived from the sample rate and the average point count per rotation
+    pub hz: f32,
+
+    /// Average point count per rotation (rounded)
+    pub points_per_rev: u32,
+}
+
 /// Health status of device
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Health {
     Healthy,
     Warning(u16),
     Error(u16)
 }
+
+/// Descriptions of known health error codes
+///
+/// The meaning of other error codes is device specific, please contact Slamtec support with the code
+const RPLIDAR_HEALTH_ERROR_DESCRIPTIONS: [(u16, &str); 1] = [
+    (0x0000, "no error"),
+];
+
+impl Health {
+    /// describe an error code reported in health info, "unknown error code" if the code is not described
+    pub fn describe_error(code: u16) -> &'static str {
+        for (known_code, description) in RPLIDAR_HEALTH_ERROR_DESCRIPTIONS.iter() {
+            if *known_code == code {
+                return description;
+            }
+        }
+
+        return "unknown error code";
+    }
+
+    /// format an error code reported in health info together with its description
+    pub fn format_error(code: u16) -> String {
+        return format!("{}: 0x{:04X}", Health::describe_error(code), code);
+    }
+
+    /// describe the error code of this health status, `None` if the device is healthy
+    pub fn error_description(&self) -> Option<&'static str> {
+        match self {
+            Health::Healthy => None,
+            Health::Warning(code) | Health::Error(code) => Some(Health::describe_error(*code)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let point = ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 0xbc, flag: 1 };
+        let json = serde_json::to_string(&point).unwrap();
+        assert_eq!(json, "{\"angle_z_q14\":16384,\"dist_mm_q2\":4000,\"quality\":188,\"flag\":1}");
+        assert_eq!(serde_json::from_str::<ScanPoint>(&json).unwrap(), point);
+
+        let health = Health::Warning(0x8001);
+        assert_eq!(serde_json::from_str::<Health>(&serde_json::to_string(&health).unwrap()).unwrap(), health);
+    }
+
+    #[test]
+    fn build_scan_options() {
+        let options = ScanOptionsBuilder::new()
+            .scan_mode(2)
+            .option_flag(ScanOption::Boost)
+            .build()
+            .unwrap();
+        assert_eq!(options.scan_mode, Some(2));
+        assert!(!options.force_scan);
+        assert_eq!(options.options, 0x0001);
+
+        assert_eq!(ScanOptionsBuilder::new().build().unwrap(), ScanOptions::default());
+        assert_eq!(ScanOptionsBuilder::new().scan_mode(0).force_scan(true).build().unwrap(), ScanOptions::force_scan_with_mode(0));
+
+        assert!(ScanOptionsBuilder::new()
+            .force_scan(true)
+            .option_flag(ScanOption::SunlightRejection)
+            .build()
+            .is_err());
+        assert!(ScanOptionsBuilder::new().scan_mode(0).option_flag(ScanOption::Boost).build().is_err());
+    }
+
+    #[test]
+    fn unit_conversions() {
+        let point = ScanPoint { angle_z_q14: 16384, dist_mm_q2: 1000 * 4, quality: 0xbc, flag: 1 };
+        assert_eq!(point.angle_degrees(), 90f32);
+        assert!((point.angle() - PI / 2f32).abs() < 1e-6);
+        assert_eq!(point.distance(), 1f32);
+        assert!(point.is_valid());
+        assert!(point.is_sync());
+
+        assert_eq!(point.distance_in(DistanceUnit::Millimeters), 1000f32);
+        assert_eq!(point.distance_in(DistanceUnit::Centimeters), 100f32);
+        assert_eq!(point.distance_in(DistanceUnit::Meters), point.distance());
+
+        let (x, y) = point.as_cartesian();
+        assert!(x.abs() < 1e-6);
+        assert!((y - 1f32).abs() < 1e-6);
+
+        let point = ScanPoint { angle_z_q14: 16384, dist_mm_q2: 0, quality: 0xbc, flag: 0 };
+        assert!(!point.is_valid());
+        assert!(!point.is_sync());
+        assert_eq!(point.as_cartesian(), (0f32, 0f32));
+    }
+
+    #[test]
+    fn fixed_point_cartesian() {
+        for angle_z_q14 in (0..=65535u32).step_by(97) {
+            let point = ScanPoint { angle_z_q14: angle_z_q14 as u16, dist_mm_q2: 40000 * 4, quality: 0xbc, flag: 0 };
+            assert_eq!(point.angle_q14(), angle_z_q14 as u16);
+            assert_eq!(point.distance_mm_q2(), 160000);
+
+            let (x, y) = point.to_cartesian();
+            let (x_q2, y_q2) = point.to_cartesian_q2();
+            assert!(((x_q2 as f32) / 4f32 - x).abs() < 8f32);
+            assert!(((y_q2 as f32) / 4f32 - y).abs() < 8f32);
+        }
+    }
+
+    #[test]
+    fn normalize_uniform_scan() {
+        let points: Vec<ScanPoint> = (0..360u32)
+            .map(|deg| ScanPoint {
+                angle_z_q14: ((deg << 16) / 360 + 1) as u16,
+                dist_mm_q2: (1000 + deg) * 4,
+                quality: 0xbc,
+                flag: 0,
+            })
+            .collect();
+
+        let bins = normalize_scan(&points, RPLIDAR_NORMALIZED_SCAN_DEFAULT_BINS);
+        assert_eq!(bins.len(), 360);
+        for (deg, bin) in bins.iter().enumerate() {
+            assert_eq!(*bin, Some(points[deg].distance()));
+        }
+    }
+
+    #[test]
+    fn normalize_sparse_scan() {
+        let point = |angle_z_q14: u16, dist_mm: u32, quality: u8| ScanPoint {
+            angle_z_q14: angle_z_q14,
+            dist_mm_q2: dist_mm * 4,
+            quality: quality,
+            flag: 0,
+        };
+        let points = [
+            point(0, 1000, 10),
+            point(100, 2000, 40),
+            point(16384, 3000, 40),
+            point(16400, 0, 60),
+            point(32768, 4000, 0),
+        ];
+
+        let bins = normalize_scan(&points, 4);
+        assert_eq!(bins, vec![Some(2f32), Some(3f32), None, None]);
+        assert!(normalize_scan(&points, 0).is_empty());
+    }
+
+    #[test]
+    fn describe_health_error() {
+        assert_eq!(Health::describe_error(0), "no error");
+        assert_eq!(Health::describe_error(0x8001), "unknown error code");
+        assert_eq!(Health::format_error(0), "no error: 0x0000");
+        assert_eq!(Health::format_error(0x8001), "unknown error code: 0x8001");
+        assert_eq!(Health::Healthy.error_description(), None);
+        assert_eq!(Health::Error(0x12).error_description(), Some("unknown error code"));
+    }
+}
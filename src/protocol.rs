@@ -139,6 +139,12 @@ impl RplidarHostProtocol {
     }
 }
 
+impl Default for RplidarHostProtocol {
+    fn default() -> RplidarHostProtocol {
+        RplidarHostProtocol::new()
+    }
+}
+
 impl ProtocolDecoder for RplidarHostProtocol {
     /// Decode bytes and return consumed bytes and message
     fn decode(&mut self, buf: &[u8]) -> Result<(usize, Option<Message>)> {
@@ -151,14 +157,14 @@ impl ProtocolDecoder for RplidarHostProtocol {
                 }
                 DecodeStatus::WaitAnsHeader => {
                     let (read, msg) = self.decode_ans_header(&buf[i..buf.len()])?;
-                    if let Some(_) = msg {
+                    if msg.is_some() {
                         return Ok((i + read, msg));
                     }
                     i += read;
                 }
                 DecodeStatus::ReceiveResponse => {
                     let (read, msg) = self.decode_response(&buf[i..buf.len()])?;
-                    if let Some(_) = msg {
+                    if msg.is_some() {
                         return Ok((i + read, msg));
                     }
                     i += read;
@@ -189,7 +195,7 @@ impl ProtocolEncoder for RplidarHostProtocol {
             return Err(RposError::OperationFail { description: "payload too big".to_owned() }.into());
         }
 
-        let cmd = if msg.data.len() != 0 {
+        let cmd = if !msg.data.is_empty() {
             msg.cmd | RPLIDAR_CMDFLAG_HAS_PAYLOAD
         } else {
             msg.cmd
@@ -198,7 +204,7 @@ impl ProtocolEncoder for RplidarHostProtocol {
         bytes[0] = RPLIDAR_CMD_SYNC_BYTE;
         bytes[1] = cmd;
 
-        if msg.data.len() != 0 {
+        if !msg.data.is_empty() {
             let mut checksum = Checksum::new();
 
             checksum.push_slice(&bytes[0..2]);
@@ -221,7 +227,7 @@ impl ProtocolEncoder for RplidarHostProtocol {
             return Err(RposError::OperationFail { description: "payload too big".to_owned() }.into());
         }
 
-        if msg.data.len() > 0 {
+        if !msg.data.is_empty() {
             return Ok(4 + msg.data.len());
         } else {
             return Ok(2);
@@ -249,9 +255,9 @@ mod tests {
     use rpos_drv::{Message, ProtocolEncoder, Result};
 
     fn encode<T: ProtocolEncoder>(protocol: &mut T, msg: &Message) -> Result<Vec<u8>> {
-        let encoded_bytes = protocol.estimate_encoded_size(&msg)?;
+        let encoded_bytes = protocol.estimate_encoded_size(msg)?;
         let mut buf = vec![0; encoded_bytes];
-        let encoded_bytes = protocol.encode(&msg, &mut buf[0..encoded_bytes])?;
+        let encoded_bytes = protocol.encode(msg, &mut buf[0..encoded_bytes])?;
         buf.truncate(encoded_bytes);
         return Ok(buf);
     }
@@ -0,0 +1,340 @@
+use super::answers::*;
+use super::errors::*;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Response decodable from the little-endian wire format, independent of the byte order of the host
+pub trait FromResponse: Sized {
+    /// size of the response on the wire (in bytes)
+    const SIZE: usize;
+
+    /// decode the response from `data`, which must be exactly `SIZE` bytes
+    fn parse(data: &[u8]) -> Result<Self>;
+}
+
+fn check_size<T: FromResponse>(data: &[u8]) -> Result<()> {
+    if data.len() != T::SIZE {
+        return Err(RposError::OperationFail { description: "answer type mismatch".to_owned() }.into());
+    } else {
+        return Ok(());
+    }
+}
+
+/// decode consecutive responses of type `T` from `data` into `output`
+fn parse_array<T: FromResponse>(data: &[u8], output: &mut [T]) -> Result<()> {
+    for (i, item) in output.iter_mut().enumerate() {
+        *item = T::parse(&data[i * T::SIZE..(i + 1) * T::SIZE])?;
+    }
+    return Ok(());
+}
+
+impl FromResponse for u8 {
+    const SIZE: usize = 1;
+
+    fn parse(data: &[u8]) -> Result<u8> {
+        check_size::<u8>(data)?;
+        return Ok(data[0]);
+    }
+}
+
+impl FromResponse for u16 {
+    const SIZE: usize = 2;
+
+    fn parse(data: &[u8]) -> Result<u16> {
+        check_size::<u16>(data)?;
+        return Ok(LittleEndian::read_u16(data));
+    }
+}
+
+impl FromResponse for u32 {
+    const SIZE: usize = 4;
+
+    fn parse(data: &[u8]) -> Result<u32> {
+        check_size::<u32>(data)?;
+        return Ok(LittleEndian::read_u32(data));
+    }
+}
+
+impl FromResponse for RplidarResponseDeviceInfo {
+    const SIZE: usize = 20;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseDeviceInfo> {
+        check_size::<RplidarResponseDeviceInfo>(data)?;
+
+        let mut serialnum = [0u8; 16];
+        serialnum.copy_from_slice(&data[4..20]);
+
+        return Ok(RplidarResponseDeviceInfo {
+            model: data[0],
+            firmware_version: LittleEndian::read_u16(&data[1..3]),
+            hardware_version: data[3],
+            serialnum: serialnum,
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseDeviceHealth {
+    const SIZE: usize = 3;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseDeviceHealth> {
+        check_size::<RplidarResponseDeviceHealth>(data)?;
+
+        return Ok(RplidarResponseDeviceHealth {
+            status: data[0],
+            error_code: LittleEndian::read_u16(&data[1..3]),
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseSampleRate {
+    const SIZE: usize = 4;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseSampleRate> {
+        check_size::<RplidarResponseSampleRate>(data)?;
+
+        return Ok(RplidarResponseSampleRate {
+            std_sample_duration_us: LittleEndian::read_u16(&data[0..2]),
+            express_sample_duration_us: LittleEndian::read_u16(&data[2..4]),
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseMeasurementNode {
+    const SIZE: usize = 5;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseMeasurementNode> {
+        check_size::<RplidarResponseMeasurementNode>(data)?;
+
+        return Ok(RplidarResponseMeasurementNode {
+            sync_quality: data[0],
+            angle_q6_checkbit: LittleEndian::read_u16(&data[1..3]),
+            distance_q2: LittleEndian::read_u16(&data[3..5]),
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseCabinNodes {
+    const SIZE: usize = 5;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseCabinNodes> {
+        check_size::<RplidarResponseCabinNodes>(data)?;
+
+        return Ok(RplidarResponseCabinNodes {
+            distance_angle_1: LittleEndian::read_u16(&data[0..2]),
+            distance_angle_2: LittleEndian::read_u16(&data[2..4]),
+            offset_angles_q3: data[4],
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseCapsuleMeasurementNodes {
+    const SIZE: usize = 4 + 16 * RplidarResponseCabinNodes::SIZE;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseCapsuleMeasurementNodes> {
+        check_size::<RplidarResponseCapsuleMeasurementNodes>(data)?;
+
+        let mut cabins = [RplidarResponseCabinNodes {
+            distance_angle_1: 0,
+            distance_angle_2: 0,
+            offset_angles_q3: 0,
+        }; 16];
+        parse_array(&data[4..], &mut cabins)?;
+
+        return Ok(RplidarResponseCapsuleMeasurementNodes {
+            s_checksum_1: data[0],
+            s_checksum_2: data[1],
+            start_angle_sync_q6: LittleEndian::read_u16(&data[2..4]),
+            cabins: cabins,
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseMeasurementNodeHq {
+    const SIZE: usize = 8;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseMeasurementNodeHq> {
+        check_size::<RplidarResponseMeasurementNodeHq>(data)?;
+
+        return Ok(RplidarResponseMeasurementNodeHq {
+            angle_z_q14: LittleEndian::read_u16(&data[0..2]),
+            dist_mm_q2: LittleEndian::read_u32(&data[2..6]),
+            quality: data[6],
+            flag: data[7],
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseHqCapsuledMeasurementNodes {
+    const SIZE: usize = 9 + 16 * RplidarResponseMeasurementNodeHq::SIZE + 4;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseHqCapsuledMeasurementNodes> {
+        check_size::<RplidarResponseHqCapsuledMeasurementNodes>(data)?;
+
+        let mut nodes = [RplidarResponseMeasurementNodeHq {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality: 0,
+            flag: 0,
+        }; 16];
+        parse_array(&data[9..data.len() - 4], &mut nodes)?;
+
+        return Ok(RplidarResponseHqCapsuledMeasurementNodes {
+            sync_byte: data[0],
+            timestamp: LittleEndian::read_u64(&data[1..9]),
+            nodes: nodes,
+            crc32: LittleEndian::read_u32(&data[data.len() - 4..]),
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseUltraCapsuleMeasurementNodes {
+    const SIZE: usize = 4 + 32 * 4;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseUltraCapsuleMeasurementNodes> {
+        check_size::<RplidarResponseUltraCapsuleMeasurementNodes>(data)?;
+
+        let mut ultra_cabins = [0u32; 32];
+        LittleEndian::read_u32_into(&data[4..], &mut ultra_cabins);
+
+        return Ok(RplidarResponseUltraCapsuleMeasurementNodes {
+            s_checksum_1: data[0],
+            s_checksum_2: data[1],
+            start_angle_sync_q6: LittleEndian::read_u16(&data[2..4]),
+            ultra_cabins: ultra_cabins,
+        });
+    }
+}
+
+impl FromResponse for RplidarResponseDenseCapsuleMeasurementNodes {
+    const SIZE: usize = 4 + 40 * 2;
+
+    fn parse(data: &[u8]) -> Result<RplidarResponseDenseCapsuleMeasurementNodes> {
+        check_size::<RplidarResponseDenseCapsuleMeasurementNodes>(data)?;
+
+        let mut dense_cabins = [0u16; 40];
+        LittleEndian::read_u16_into(&data[4..], &mut dense_cabins);
+
+        return Ok(RplidarResponseDenseCapsuleMeasurementNodes {
+            s_checksum_1: data[0],
+            s_checksum_2: data[1],
+            start_angle_sync_q6: LittleEndian::read_u16(&data[2..4]),
+            dense_cabins: dense_cabins,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// decode little-endian bytes by shifting, which doesn't depend on the byte order of the host
+    fn le(bytes: &[u8]) -> u64 {
+        return bytes.iter().rev().fold(0u64, |value, byte| (value << 8) | (*byte as u64));
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        return (0..len).map(|i| (i * 37 + 11) as u8).collect();
+    }
+
+    #[test]
+    fn wire_sizes_match_packed_layouts() {
+        assert_eq!(RplidarResponseDeviceInfo::SIZE, std::mem::size_of::<RplidarResponseDeviceInfo>());
+        assert_eq!(RplidarResponseDeviceHealth::SIZE, std::mem::size_of::<RplidarResponseDeviceHealth>());
+        assert_eq!(RplidarResponseSampleRate::SIZE, std::mem::size_of::<RplidarResponseSampleRate>());
+        assert_eq!(RplidarResponseMeasurementNode::SIZE, std::mem::size_of::<RplidarResponseMeasurementNode>());
+        assert_eq!(
+            RplidarResponseCapsuleMeasurementNodes::SIZE,
+            std::mem::size_of::<RplidarResponseCapsuleMeasurementNodes>()
+        );
+        assert_eq!(
+            RplidarResponseHqCapsuledMeasurementNodes::SIZE,
+            std::mem::size_of::<RplidarResponseHqCapsuledMeasurementNodes>()
+        );
+        assert_eq!(
+            RplidarResponseUltraCapsuleMeasurementNodes::SIZE,
+            std::mem::size_of::<RplidarResponseUltraCapsuleMeasurementNodes>()
+        );
+        assert_eq!(
+            RplidarResponseDenseCapsuleMeasurementNodes::SIZE,
+            std::mem::size_of::<RplidarResponseDenseCapsuleMeasurementNodes>()
+        );
+    }
+
+    #[test]
+    fn parse_integers_little_endian() {
+        assert_eq!(u8::parse(&[0x12]).unwrap(), 0x12);
+        assert_eq!(u16::parse(&[0x34, 0x12]).unwrap(), 0x1234);
+        assert_eq!(u32::parse(&[0x78, 0x56, 0x34, 0x12]).unwrap(), 0x12345678);
+        assert!(u32::parse(&[0x78, 0x56, 0x34]).is_err());
+        assert!(u16::parse(&[0x78, 0x56, 0x34]).is_err());
+    }
+
+    #[test]
+    fn parse_device_info_and_health() {
+        let data = pattern(RplidarResponseDeviceInfo::SIZE);
+        let info = RplidarResponseDeviceInfo::parse(&data).unwrap();
+        assert_eq!(info.model, data[0]);
+        assert_eq!({ info.firmware_version } as u64, le(&data[1..3]));
+        assert_eq!(info.hardware_version, data[3]);
+        assert_eq!(info.serialnum[..], data[4..20]);
+
+        let health = RplidarResponseDeviceHealth::parse(&[2, 0x34, 0x12]).unwrap();
+        assert_eq!(health.status, 2);
+        assert_eq!({ health.error_code }, 0x1234);
+
+        assert!(RplidarResponseDeviceInfo::parse(&data[1..]).is_err());
+    }
+
+    #[test]
+    fn parse_measurement_nodes() {
+        let data = pattern(RplidarResponseMeasurementNode::SIZE);
+        let node = RplidarResponseMeasurementNode::parse(&data).unwrap();
+        assert_eq!(node.sync_quality, data[0]);
+        assert_eq!({ node.angle_q6_checkbit } as u64, le(&data[1..3]));
+        assert_eq!({ node.distance_q2 } as u64, le(&data[3..5]));
+
+        let data = pattern(RplidarResponseCapsuleMeasurementNodes::SIZE);
+        let nodes = RplidarResponseCapsuleMeasurementNodes::parse(&data).unwrap();
+        assert_eq!({ nodes.start_angle_sync_q6 } as u64, le(&data[2..4]));
+        for (i, cabin) in { nodes.cabins }.iter().enumerate() {
+            let offset = 4 + i * 5;
+            assert_eq!({ cabin.distance_angle_1 } as u64, le(&data[offset..offset + 2]));
+            assert_eq!({ cabin.distance_angle_2 } as u64, le(&data[offset + 2..offset + 4]));
+            assert_eq!(cabin.offset_angles_q3, data[offset + 4]);
+        }
+    }
+
+    #[test]
+    fn parse_hq_capsule() {
+        let data = pattern(RplidarResponseHqCapsuledMeasurementNodes::SIZE);
+        let nodes = RplidarResponseHqCapsuledMeasurementNodes::parse(&data).unwrap();
+        assert_eq!(nodes.sync_byte, data[0]);
+        assert_eq!({ nodes.timestamp }, le(&data[1..9]));
+        assert_eq!({ nodes.crc32 } as u64, le(&data[data.len() - 4..]));
+
+        for (i, node) in { nodes.nodes }.iter().enumerate() {
+            let offset = 9 + i * 8;
+            assert_eq!({ node.angle_z_q14 } as u64, le(&data[offset..offset + 2]));
+            assert_eq!({ node.dist_mm_q2 } as u64, le(&data[offset + 2..offset + 6]));
+            assert_eq!(node.quality, data[offset + 6]);
+            assert_eq!(node.flag, data[offset + 7]);
+        }
+    }
+
+    #[test]
+    fn parse_ultra_and_dense_capsules() {
+        let data = pattern(RplidarResponseUltraCapsuleMeasurementNodes::SIZE);
+        let nodes = RplidarResponseUltraCapsuleMeasurementNodes::parse(&data).unwrap();
+        assert_eq!({ nodes.start_angle_sync_q6 } as u64, le(&data[2..4]));
+        for (i, cabin) in { nodes.ultra_cabins }.iter().enumerate() {
+            assert_eq!(*cabin as u64, le(&data[4 + i * 4..8 + i * 4]));
+        }
+
+        let data = pattern(RplidarResponseDenseCapsuleMeasurementNodes::SIZE);
+        let nodes = RplidarResponseDenseCapsuleMeasurementNodes::parse(&data).unwrap();
+        assert_eq!(nodes.s_checksum_1, data[0]);
+        assert_eq!(nodes.s_checksum_2, data[1]);
+        for (i, cabin) in { nodes.dense_cabins }.iter().enumerate() {
+            assert_eq!(*cabin as u64, le(&data[4 + i * 2..6 + i * 2]));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use super::internals::*;
+use super::{MotorControl, RplidarDevice, RposError, Result};
+use std::fmt;
+use serialport::prelude::*;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// Baud rates tried by `open_port_autobaud`, in order
+pub const RPLIDAR_AUTOBAUD_CANDIDATES: [u32; 4] = [115200, 256000, 460800, 1000000];
+
+/// Open RPLIDAR on serial port without knowing the baud rate
+///
+/// Each baud rate in `RPLIDAR_AUTOBAUD_CANDIDATES` is tried with `GET_DEVICE_INFO`,
+/// the device is returned with the first baud rate the LIDAR responds at
+///
+/// # Example
+/// ```ignore
+/// let (mut rplidar, baud_rate) = rplidar_drv::open_port_autobaud("/dev/ttyUSB0")?;
+/// ```
+pub fn open_port_autobaud(path: &str) -> Result<(RplidarDevice<dyn SerialPort>, u32)> {
+    return probe_baud_rates(&RPLIDAR_AUTOBAUD_CANDIDATES, |baud_rate| open_serial_port(path, baud_rate));
+}
+
+/// open serial port with the settings of RPLIDAR (8N1 without flow control)
+fn open_serial_port(path: &str, baud_rate: u32) -> Result<Box<dyn SerialPort>> {
+    let settings = SerialPortSettings {
+        baud_rate: baud_rate,
+        data_bits: DataBits::Eight,
+        flow_control: FlowControl::None,
+        parity: Parity::None,
+        stop_bits: StopBits::One,
+        timeout: Duration::from_millis(1),
+    };
+
+    return Ok(serialport::open_with_settings(path, &settings)?);
+}
+
+impl RplidarDevice<dyn SerialPort> {
+    /// Open RPLIDAR on serial port at `baud_rate` (8N1 without flow control)
+    ///
+    /// The default baud rates are 115200 for A1/A2, 256000 for A3/S1, 460800 for C1, and 1000000 for S2/S3 and T-series
+    /// (see `LidarFamily::default_baud_rate`). Use `open_port_autobaud` if the baud rate is unknown
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut rplidar = RplidarDevice::open_port("/dev/ttyUSB0", 115200)?;
+    /// ```
+    pub fn open_port(path: &str, baud_rate: u32) -> Result<RplidarDevice<dyn SerialPort>> {
+        return Ok(RplidarDevice::with_stream(open_serial_port(path, baud_rate)?));
+    }
+
+    /// Get bytes not decoded yet, including the bytes queued in the OS serial driver
+    pub fn pending_serial_bytes(&self) -> Result<usize> {
+        return Ok(self.pending_bytes() + self.transport().bytes_to_read()? as usize);
+    }
+
+    /// Control the motor with the DTR line of the serial port, for LIDARs without PWM motor control (e.g. A1)
+    pub fn enable_dtr_motor_control(&mut self) -> Result<()> {
+        let port = self.transport().try_clone()?;
+        self.set_motor_control(Box::new(SerialDtrMotorControl { port: port }));
+        return Ok(());
+    }
+}
+
+/// Motor control through the DTR line of a serial port
+pub struct SerialDtrMotorControl {
+    port: Box<dyn SerialPort>,
+}
+
+impl SerialDtrMotorControl {
+    /// control the motor through the DTR line of `port` (usually a clone of the port of the LIDAR)
+    pub fn new(port: Box<dyn SerialPort>) -> SerialDtrMotorControl {
+        SerialDtrMotorControl { port: port }
+    }
+}
+
+impl fmt::Debug for SerialDtrMotorControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "SerialDtrMotorControl({:?})", self.port.name());
+    }
+}
+
+impl MotorControl for SerialDtrMotorControl {
+    fn set_dtr(&mut self, on: bool) -> Result<()> {
+        return Ok(self.port.write_data_terminal_ready(on)?);
+    }
+}
+
+/// LIDARs of a multi-LIDAR rig, addressed by serial numbers
+///
+/// Unlike the port names, serial numbers are stable across reboots
+pub struct RplidarRegistry<T: ?Sized = dyn SerialPort> {
+    devices: Vec<(String, RplidarDevice<T>)>,
+}
+
+impl RplidarRegistry<dyn SerialPort> {
+    /// open LIDARs on `ports` (pairs of port name and baud rate), and register them by serial numbers
+    pub fn open(ports: &[(&str, u32)]) -> Result<RplidarRegistry> {
+        let mut registry = RplidarRegistry::new();
+
+        for (path, baud_rate) in ports {
+            registry.register(RplidarDevice::open_port(path, *baud_rate)?)?;
+        }
+
+        return Ok(registry);
+    }
+}
+
+impl<T: ?Sized> RplidarRegistry<T>
+where
+    T: Read + Write,
+{
+    /// create an empty registry
+    pub fn new() -> RplidarRegistry<T> {
+        RplidarRegistry { devices: Vec::new() }
+    }
+
+    /// read the serial number of the device and register it, returns the serial number
+    ///
+    /// Returns error if a device with the same serial number is already registered
+    pub fn register(&mut self, mut device: RplidarDevice<T>) -> Result<String> {
+        let serial = device.get_device_info()?.serial_number();
+
+        if self.devices.iter().any(|(registered, _)| *registered == serial) {
+            return Err(RposError::OperationFail {
+                description: format!("LIDAR {} is already registered", serial),
+            }
+            .into());
+        }
+
+        self.devices.push((serial.clone(), device));
+        return Ok(serial);
+    }
+
+    /// get the device by serial number (hex string, case insensitive)
+    pub fn get_by_serial(&mut self, serial: &str) -> Option<&mut RplidarDevice<T>> {
+        return self
+            .devices
+            .iter_mut()
+            .find(|(registered, _)| registered.eq_ignore_ascii_case(serial))
+            .map(|(_, device)| device);
+    }
+
+    /// serial numbers of the registered devices, in the order of registration
+    pub fn serials(&self) -> impl Iterator<Item = &str> {
+        return self.devices.iter().map(|(serial, _)| serial.as_str());
+    }
+}
+
+impl<T: ?Sized> Default for RplidarRegistry<T>
+where
+    T: Read + Write,
+{
+    fn default() -> RplidarRegistry<T> {
+        RplidarRegistry::new()
+    }
+}
+
+/// open stream with `open` at each baud rate in `candidates` until the LIDAR responds
+fn probe_baud_rates<T, F>(candidates: &[u32], mut open: F) -> Result<(RplidarDevice<T>, u32)>
+where
+    T: ?Sized + Read + Write,
+    F: FnMut(u32) -> Result<Box<T>>,
+{
+    for baud_rate in candidates {
+        let mut rplidar = RplidarDevice::with_stream(open(*baud_rate)?);
+
+        if rplidar.get_device_info_with_timeout(RPLIDAR_AUTOBAUD_PROBE_TIMEOUT).is_ok() {
+            return Ok((rplidar, *baud_rate));
+        }
+    }
+
+    return Err(RposError::OperationFail {
+        description: "LIDAR does not respond at any candidate baud rate".to_owned(),
+    }
+    .into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answers::RPLIDAR_ANS_TYPE_DEVINFO;
+    use crate::tests::{answer, device_info_payload, MockStream};
+
+    #[test]
+    fn probe_responding_baud_rate() {
+        let mut tried = Vec::new();
+        let (_, baud_rate) = probe_baud_rates(&RPLIDAR_AUTOBAUD_CANDIDATES, |baud_rate| {
+            tried.push(baud_rate);
+            if baud_rate == 460800 {
+                return Ok(Box::new(MockStream::new(&answer(
+                    RPLIDAR_ANS_TYPE_DEVINFO,
+                    &device_info_payload(0x61, 0x0118),
+                    false,
+                ))));
+            }
+
+            // garbage received at mismatched baud rate
+            return Ok(Box::new(MockStream::new(&[0x3c, 0xff, 0x00, 0xa5, 0x81])));
+        })
+        .unwrap();
+
+        assert_eq!(baud_rate, 460800);
+        assert_eq!(tried, [115200, 256000, 460800]);
+    }
+
+    #[test]
+    fn registry_by_serial() {
+        let mock = |model: u8, serial_offset: u8| {
+            let mut payload = device_info_payload(model, 0x0118);
+            for byte in payload[4..].iter_mut() {
+                *byte += serial_offset;
+            }
+            RplidarDevice::with_stream(Box::new(MockStream::new(&answer(RPLIDAR_ANS_TYPE_DEVINFO, &payload, false))))
+        };
+
+        let mut registry = RplidarRegistry::<MockStream>::new();
+        assert_eq!(registry.register(mock(0x61, 0)).unwrap(), "000102030405060708090A0B0C0D0E0F");
+        assert_eq!(registry.register(mock(0x18, 0xa0)).unwrap(), "A0A1A2A3A4A5A6A7A8A9AAABACADAEAF");
+        assert!(registry.register(mock(0x18, 0)).is_err());
+
+        let serials: Vec<&str> = registry.serials().collect();
+        assert_eq!(serials.len(), 2);
+
+        assert!(registry.get_by_serial("a0a1a2a3a4a5a6a7a8a9aaabacadaeaf").is_some());
+        assert!(registry.get_by_serial("000102030405060708090A0B0C0D0E0F").is_some());
+        assert!(registry.get_by_serial("FFFF").is_none());
+    }
+
+    #[test]
+    fn probe_without_response() {
+        let result = probe_baud_rates(&RPLIDAR_AUTOBAUD_CANDIDATES, |_| {
+            return Ok(Box::new(MockStream::new(&[])));
+        });
+
+        assert!(result.is_err());
+    }
+}
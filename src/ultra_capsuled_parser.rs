@@ -1,8 +1,7 @@
 use super::CachedPrevCapsule;
 use super::answers::{RplidarResponseUltraCapsuleMeasurementNodes, RplidarResponseMeasurementNodeHq};
 use super::capsuled_parser::{ angle_diff_q8, check_sync, generate_quality, generate_flag };
-
-const PI:f64 = 3.1415926535;
+use std::f64::consts::PI;
 
 struct ParsedNode {
     pub dist_q2: u32,
@@ -22,7 +21,8 @@ fn calc_angle_offset_q16(dist:u32) -> i32 {
         const K1:i32 = 98361;
         let k2 = K1 / (dist as i32);
 
-        return (deg_to_rad_q16(8f64) - ((k2 << 6) as f64) - (((k2 * k2 * k2) / 98304) as f64)) as i32;
+        // truncate the base offset before subtracting, as the reference SDK does in integer arithmetic
+        return (deg_to_rad_q16(8f64) as i32) - (k2 << 6) - ((k2 * k2 * k2) / 98304);
     } else {
         return deg_to_rad_q16(7.5f64) as i32;
     }
@@ -116,7 +116,7 @@ fn to_hq(node: &ParsedNode, cur_angle_raw_q16: u32, angle_inc_q16: u32) -> Rplid
 
     RplidarResponseMeasurementNodeHq {
         angle_z_q14: angle_q16_to_angle_z_q14(angle_q16 as u32),
-        dist_mm_q2: node.dist_q2 as u32,
+        dist_mm_q2: node.dist_q2,
         quality: generate_quality(node.dist_q2),
         flag: generate_flag(sync)
     }
@@ -127,7 +127,7 @@ pub fn parse_ultra_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarRespo
         let mut output_nodes : Vec<RplidarResponseMeasurementNodeHq> = Vec::with_capacity(32*3);
 
         let cur_start_angle_q8 = get_start_angle_q8(&nodes);
-        let prev_start_angle_q8 = get_start_angle_q8(&prev_capsule);
+        let prev_start_angle_q8 = get_start_angle_q8(prev_capsule);
 
         let diff_angle_q8 = angle_diff_q8(prev_start_angle_q8, cur_start_angle_q8);
 
@@ -135,7 +135,7 @@ pub fn parse_ultra_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarRespo
         let mut cur_angle_raw_q16 = prev_start_angle_q8 << 8;
 
         let (mut cur_major, mut cur_predict1, mut cur_predict2) = parse_cabin(prev_capsule.ultra_cabins[0]);
-        let cabin_count = unsafe { prev_capsule.ultra_cabins.len() };
+        let cabin_count = { prev_capsule.ultra_cabins }.len();
 
         for i in 0..cabin_count {
             let next_cabin = if i == cabin_count-1 {
@@ -149,7 +149,7 @@ pub fn parse_ultra_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarRespo
             let parsed_nodes = generate_nodes(cur_major, next_major, cur_predict1, cur_predict2);
 
             for node in parsed_nodes.iter() {
-                output_nodes.push(to_hq(&node, cur_angle_raw_q16, angle_inc_q16));
+                output_nodes.push(to_hq(node, cur_angle_raw_q16, angle_inc_q16));
                 cur_angle_raw_q16 += angle_inc_q16;
             }
 
@@ -163,3 +163,85 @@ pub fn parse_ultra_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarRespo
         return (Vec::new(), CachedPrevCapsule::UltraCapsuled(nodes));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScanPoint;
+
+    fn ultra_capsule(start_angle_q6: u16, major: u32) -> RplidarResponseUltraCapsuleMeasurementNodes {
+        RplidarResponseUltraCapsuleMeasurementNodes {
+            s_checksum_1: 0,
+            s_checksum_2: 0,
+            start_angle_sync_q6: start_angle_q6,
+            ultra_cabins: [major; 32],
+        }
+    }
+
+    /// encode a cabin with major distance and two predict deltas
+    fn cabin(major: u32, predict1: i32, predict2: i32) -> u32 {
+        return (major & 0xfff) | (((predict1 as u32) & 0x3ff) << 12) | ((predict2 as u32) << 22);
+    }
+
+    #[test]
+    fn parse_long_range_predicts() {
+        let mut prev = ultra_capsule(0, 0);
+        prev.ultra_cabins = [cabin(3866, 3, -2); 32];
+        prev.ultra_cabins[1] = cabin(1800, 5, 0x1ff);
+
+        let (_, cached) = parse_ultra_capsuled(&CachedPrevCapsule::None, prev);
+        let (nodes, _) = parse_ultra_capsuled(&cached, ultra_capsule(10 << 6, 3866));
+        let distances: Vec<u32> = nodes.iter().map(|node| node.dist_mm_q2 >> 2).collect();
+
+        // 3866 decodes to 24992mm (scale level 4), and 1800 decodes to 4160mm (scale level 3)
+        assert_eq!(distances[0..3], [24992, 24992 + (3 << 4), 4160 - (2 << 3)]);
+        assert_eq!(distances[3..6], [4160, 4160 + (5 << 3), 0]);
+        assert_eq!(distances[6..9], [24992, 24992 + (3 << 4), 24992 - (2 << 4)]);
+    }
+
+    #[test]
+    fn angle_offset_matches_reference() {
+        assert_eq!(calc_angle_offset_q16(0), 8578);
+        assert_eq!(calc_angle_offset_q16(200), 9150 - (491 << 6) - 1204);
+        assert_eq!(calc_angle_offset_q16(24992 << 2), 9150);
+    }
+
+    #[test]
+    fn parse_consecutive_capsules() {
+        let mut prev = ultra_capsule(30 << 6, 0);
+        let mut next = ultra_capsule(33 << 6, 0);
+        for i in 0..32 {
+            prev.ultra_cabins[i] = cabin(1200 + (i as u32), 1, 1);
+            next.ultra_cabins[i] = cabin(1232 + (i as u32), 1, 1);
+        }
+
+        let (_, cached) = parse_ultra_capsuled(&CachedPrevCapsule::None, prev);
+        let (nodes, _) = parse_ultra_capsuled(&cached, next);
+        assert_eq!(nodes.len(), 96);
+
+        let points: Vec<ScanPoint> = nodes.iter().map(|node| ScanPoint::from(*node)).collect();
+        // angle offsets depend on distances, so neighbor nodes with different distances may be slightly out of order
+        assert!(crate::utils::angle_monotonicity(&points) > 0.95f32);
+
+        for point in points.iter() {
+            let angle = point.angle().to_degrees();
+            // the nodes are between the start angles of the capsules (30 to 33 degrees), shifted by the angle offsets
+            assert!(angle > 20f32 && angle < 33f32, "angle {} out of range", angle);
+            assert!(point.distance() > 1.8f32 && point.distance() < 2f32);
+        }
+    }
+
+    #[test]
+    fn parse_long_range_distances() {
+        // 3866 is varbit encoded 24992mm, which is beyond the range of triangulation LIDARs
+        let (nodes, cached) = parse_ultra_capsuled(&CachedPrevCapsule::None, ultra_capsule(0, 3866));
+        assert!(nodes.is_empty());
+
+        let (nodes, _) = parse_ultra_capsuled(&cached, ultra_capsule(10 << 6, 3866));
+        assert_eq!(nodes.len(), 96);
+
+        for node in nodes.iter() {
+            assert_eq!({ node.dist_mm_q2 }, 24992 << 2);
+        }
+    }
+}
@@ -1,32 +1,46 @@
 use super::prelude::*;
 use super::errors::*;
+use byteorder::{ByteOrder, LittleEndian};
+use std::cmp::Ordering;
 use std::f32::consts::PI;
+use std::io::{Read, Write};
 
 const PI2:f32 = PI * 2f32;
 
-fn find_first_valid_index(scan: &Vec<ScanPoint>) -> Option<usize> {
-    for i in 0..scan.len() {
-        if scan[i].is_valid() {
-            return Some(i);
-        }
-    }
+/// 360 degrees in the unit of `ScanPoint::angle_z_q14`
+const ANGLE_360_Z_Q14: i32 = 65536;
 
-    return None;
+/// iterate over valid points of the scan without collecting them
+pub fn valid_points(scan: &[ScanPoint]) -> impl Iterator<Item = &ScanPoint> {
+    return scan.iter().filter(|point| point.is_valid());
 }
 
-fn find_last_valid_index(scan: &Vec<ScanPoint>) -> Option<usize> {
-    for i in 0..scan.len() {
-        let id = scan.len() - i - 1;
+/// check if the point has at least `min_quality`, and its distance is within `[min_distance_mm, max_distance_mm]`
+pub fn passes_filter(point: &ScanPoint, min_quality: u8, min_distance_mm: u32, max_distance_mm: u32) -> bool {
+    let dist_mm_q2 = point.dist_mm_q2 as u64;
+    return point.quality >= min_quality
+        && dist_mm_q2 >= (min_distance_mm as u64) * 4
+        && dist_mm_q2 <= (max_distance_mm as u64) * 4;
+}
 
-        if scan[i].is_valid() {
-            return Some(id);
-        }
-    }
+/// keep the points passing `passes_filter`, in their original order
+pub fn filter_scan(scan: &[ScanPoint], min_quality: u8, min_distance_mm: u32, max_distance_mm: u32) -> Vec<ScanPoint> {
+    return scan
+        .iter()
+        .filter(|point| passes_filter(point, min_quality, min_distance_mm, max_distance_mm))
+        .cloned()
+        .collect();
+}
 
-    return None;
+fn find_first_valid_index(scan: &[ScanPoint]) -> Option<usize> {
+    return scan.iter().position(|point| point.is_valid());
 }
 
-fn tune_head(scan: &mut Vec<ScanPoint>, inc_origin_angle: f32) -> Result<()> {
+fn find_last_valid_index(scan: &[ScanPoint]) -> Option<usize> {
+    return scan.iter().rposition(|point| point.is_valid());
+}
+
+fn tune_head(scan: &mut [ScanPoint], inc_origin_angle: f32) -> Result<()> {
     if let Some(head_index) = find_first_valid_index(scan) {
         let mut i = head_index;
 
@@ -47,7 +61,7 @@ fn tune_head(scan: &mut Vec<ScanPoint>, inc_origin_angle: f32) -> Result<()> {
     }
 }
 
-fn tune_tail(scan: &mut Vec<ScanPoint>, inc_origin_angle: f32) -> Result<()> {
+fn tune_tail(scan: &mut [ScanPoint], inc_origin_angle: f32) -> Result<()> {
     if let Some(tail_index) = find_last_valid_index(scan) {
         for i in tail_index+1..scan.len() {
             let mut expect_angle = scan[i - 1].angle() + inc_origin_angle;
@@ -66,8 +80,8 @@ fn tune_tail(scan: &mut Vec<ScanPoint>, inc_origin_angle: f32) -> Result<()> {
 }
 
 /// sort scan points
-pub fn sort_scan(scan: &mut Vec<ScanPoint>) -> Result<()> {
-    if scan.len() == 0 {
+pub fn sort_scan(scan: &mut [ScanPoint]) -> Result<()> {
+    if scan.is_empty() {
         return Ok(());
     }
 
@@ -77,13 +91,13 @@ pub fn sort_scan(scan: &mut Vec<ScanPoint>) -> Result<()> {
     tune_tail(scan, inc_origin_angle)?;
 
     let front_angle = scan[0].angle();
-    for i in 1..scan.len() {
-        if !scan[i].is_valid() {
+    for (i, point) in scan.iter_mut().enumerate().skip(1) {
+        if !point.is_valid() {
             let mut expect_angle = front_angle + (i as f32) * inc_origin_angle;
             if expect_angle > PI2 {
                 expect_angle -= PI2;
             }
-            scan[i].set_angle(expect_angle);
+            point.set_angle(expect_angle);
         }
     }
 
@@ -91,3 +105,1359 @@ pub fn sort_scan(scan: &mut Vec<ScanPoint>) -> Result<()> {
     
     return Ok(());
 }
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a_push(hash: u64, bytes: &[u8]) -> u64 {
+    let mut hash = hash;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    return hash;
+}
+
+/// compute a stable fingerprint of scan points
+///
+/// The fingerprint is calculated with FNV-1a over the angle and the distance (quantized to millimeters)
+/// of each point, so it is identical across runs and platforms. It could be used to detect duplicated scans.
+pub fn scan_fingerprint(scan: &[ScanPoint]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+
+    for point in scan {
+        hash = fnv1a_push(hash, &point.angle_z_q14.to_le_bytes());
+        hash = fnv1a_push(hash, &(point.dist_mm_q2 >> 2).to_le_bytes());
+    }
+
+    return hash;
+}
+
+/// get the bin index of an angle (in radians) when 360 degrees are split into `bins` bins
+fn angle_to_bin(angle: f32, bins: usize) -> usize {
+    let mut angle = angle % PI2;
+    if angle < 0f32 {
+        angle += PI2;
+    }
+    return ((angle / PI2 * (bins as f32)) as usize) % bins;
+}
+
+/// split the scan into evenly spaced angular bins, and keep the valid point of each bin chosen by `keep_current`
+///
+/// `keep_current(current, point)` returns whether the point already kept in the bin is preferred over `point`
+pub(crate) fn bin_scan_with<F>(scan: &[ScanPoint], bins: usize, keep_current: F) -> Vec<Option<&ScanPoint>>
+where
+    F: Fn(&ScanPoint, &ScanPoint) -> bool,
+{
+    if bins == 0 {
+        return Vec::new();
+    }
+
+    let mut output: Vec<Option<&ScanPoint>> = vec![None; bins];
+
+    for point in scan.iter().filter(|point| point.is_valid()) {
+        let bin = &mut output[angle_to_bin(point.angle(), bins)];
+
+        *bin = match *bin {
+            Some(current) if keep_current(current, point) => Some(current),
+            _ => Some(point),
+        };
+    }
+
+    return output;
+}
+
+/// split the scan into evenly spaced angular bins, and keep the nearest valid distance (in meters) of each bin
+pub(crate) fn bin_scan(scan: &[ScanPoint], bins: usize) -> Vec<Option<f32>> {
+    return bin_scan_with(scan, bins, |current, point| current.dist_mm_q2 <= point.dist_mm_q2)
+        .iter()
+        .map(|point| point.map(|point| point.distance()))
+        .collect();
+}
+
+/// compress the scan into run-length encoded `(run_length, range_mm)` pairs
+///
+/// The scan is split into `bins` angular bins (keeping the nearest return of each bin),
+/// and the ranges are quantized to multiples of `quantum_mm`. Bins without valid returns have range 0.
+pub fn scan_to_rle(scan: &[ScanPoint], bins: usize, quantum_mm: f32) -> Vec<(u16, f32)> {
+    let mut output: Vec<(u16, f32)> = Vec::new();
+
+    for bin in bin_scan(scan, bins) {
+        let range = match bin {
+            Some(distance) if quantum_mm > 0f32 => (distance * 1000f32 / quantum_mm).round() * quantum_mm,
+            Some(distance) => distance * 1000f32,
+            None => 0f32,
+        };
+
+        match output.last_mut() {
+            Some((run_length, last_range)) if *last_range == range && *run_length < u16::MAX => {
+                *run_length += 1;
+            }
+            _ => output.push((1, range)),
+        }
+    }
+
+    return output;
+}
+
+/// decode run-length encoded ranges generated by `scan_to_rle` into per-bin ranges (in millimeters)
+pub fn rle_to_ranges(rle: &[(u16, f32)]) -> Vec<f32> {
+    let mut output = Vec::with_capacity(rle.iter().map(|(run_length, _)| *run_length as usize).sum());
+
+    for (run_length, range) in rle {
+        output.extend(std::iter::repeat_n(*range, *run_length as usize));
+    }
+
+    return output;
+}
+
+/// calculate per-bin range differences (in meters) from `prev` to `curr`
+///
+/// Both scans are split into `bins` angular bins first. The value of a bin is `NaN` if either scan
+/// has no valid return in it
+pub fn scan_difference(prev: &[ScanPoint], curr: &[ScanPoint], bins: usize) -> Vec<f32> {
+    return bin_scan(prev, bins)
+        .iter()
+        .zip(bin_scan(curr, bins).iter())
+        .map(|bins| match bins {
+            (Some(prev), Some(curr)) => curr - prev,
+            _ => f32::NAN,
+        })
+        .collect();
+}
+
+/// average several scans of a static scene into `bins` ranges (in meters) to reduce noise
+///
+/// Each scan is binned (keeping the nearest return of each bin), and each range is the mean of the bins with
+/// valid returns, ignoring no-returns. Bins without valid returns in any scan are `f32::INFINITY`
+pub fn average_scans(scans: &[Vec<ScanPoint>], bins: usize) -> Vec<f32> {
+    let mut sums = vec![0f32; bins];
+    let mut counts = vec![0u32; bins];
+
+    for scan in scans {
+        for (i, bin) in bin_scan(scan, bins).iter().enumerate() {
+            if let Some(distance) = bin {
+                sums[i] += distance;
+                counts[i] += 1;
+            }
+        }
+    }
+
+    return sums
+        .iter()
+        .zip(counts.iter())
+        .map(|(sum, count)| if *count == 0 { f32::INFINITY } else { sum / (*count as f32) })
+        .collect();
+}
+
+/// Scan split into evenly spaced angular bins, for constant time lookup of ranges by angle
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinnedScan {
+    ranges: Vec<Option<f32>>,
+}
+
+impl BinnedScan {
+    /// split the points into `bins` bins, keeping the nearest valid distance of each bin
+    pub fn from_points(scan: &[ScanPoint], bins: usize) -> BinnedScan {
+        BinnedScan { ranges: bin_scan(scan, bins) }
+    }
+
+    /// range (in meters) of the bin containing `deg` (in degrees), `None` if the bin has no valid return
+    pub fn range_at(&self, deg: f32) -> Option<f32> {
+        if self.ranges.is_empty() {
+            return None;
+        }
+
+        return self.ranges[angle_to_bin(deg.to_radians(), self.ranges.len())];
+    }
+
+    /// angular width of each bin (in degrees)
+    pub fn bin_width_deg(&self) -> f32 {
+        return 360f32 / (self.ranges.len() as f32);
+    }
+
+    /// ranges (in meters) of all bins, starting from 0 degree
+    pub fn ranges(&self) -> &[Option<f32>] {
+        return &self.ranges;
+    }
+}
+
+/// Ranges evenly ordered by angle, in the shape expected by scan matchers (e.g. ROS `LaserScan`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderedScan {
+    /// Angle of the first range (in radians)
+    pub angle_min: f32,
+
+    /// Angle of the last range (in radians)
+    pub angle_max: f32,
+
+    /// Angle between neighbor ranges (in radians)
+    pub angle_increment: f32,
+
+    /// Min range (in meters)
+    pub range_min: f32,
+
+    /// Max range (in meters)
+    pub range_max: f32,
+
+    /// Ranges (in meters) from `angle_min` to `angle_max`, `INFINITY` for angles without returns
+    pub ranges: Vec<f32>,
+}
+
+/// order the scan into `bins` evenly spaced ranges
+///
+/// Each range is the nearest return within its bin, clamped to `[range_min_m, range_max_m]`.
+/// Bins without valid returns are `f32::INFINITY`
+pub fn to_ordered_ranges(scan: &[ScanPoint], bins: usize, range_min_m: f32, range_max_m: f32) -> OrderedScan {
+    let angle_increment = if bins == 0 { 0f32 } else { PI2 / (bins as f32) };
+
+    return OrderedScan {
+        angle_min: 0f32,
+        angle_max: angle_increment * (std::cmp::max(bins, 1) - 1) as f32,
+        angle_increment: angle_increment,
+        range_min: range_min_m,
+        range_max: range_max_m,
+        ranges: bin_scan(scan, bins)
+            .iter()
+            .map(|bin| match bin {
+                Some(distance) => distance.clamp(range_min_m, range_max_m),
+                None => f32::INFINITY,
+            })
+            .collect(),
+    };
+}
+
+/// remove trailing points of the scan which have swept past the angle of the first point
+///
+/// Points should be in the order they were measured (e.g. grabbed by `grab_scan`). The result is a sweep
+/// no wider than 360 degrees, so no angle is counted twice.
+pub fn trim_scan_wrap(scan: &mut Vec<ScanPoint>) {
+    if scan.is_empty() {
+        return;
+    }
+
+    // a full turn is exactly 65536 in q14 angles, so the wrapping difference is the signed angle difference
+    let mut prev_angle_q14 = scan[0].angle_z_q14;
+    let mut swept_q14 = 0i32;
+
+    for i in 1..scan.len() {
+        let angle_q14 = scan[i].angle_z_q14;
+        swept_q14 += angle_q14.wrapping_sub(prev_angle_q14) as i16 as i32;
+        prev_angle_q14 = angle_q14;
+
+        if swept_q14 >= ANGLE_360_Z_Q14 {
+            scan.truncate(i);
+            return;
+        }
+    }
+}
+
+/// Min points of a segment to fit a wall
+const WALL_MIN_POINTS: usize = 5;
+
+/// Min length (in meters) of a segment to be considered as a wall
+const WALL_MIN_LENGTH: f32 = 0.5f32;
+
+/// Max gap (in meters) between neighbor points of the same wall
+const WALL_MAX_GAP: f32 = 0.2f32;
+
+fn to_cartesian_meters(point: &ScanPoint) -> (f32, f32) {
+    let (x, y) = point.to_cartesian();
+    return (x / 1000f32, y / 1000f32);
+}
+
+fn cartesian_distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    return ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+}
+
+/// split valid points of the scan into segments of neighbor points
+///
+/// Points are ordered by angle first, and a new segment starts wherever the gap between neighbor points
+/// exceeds `max_gap_m` meters. The first and last segments are merged if they are connected across 0 degree.
+pub fn segment_scan(scan: &[ScanPoint], max_gap_m: f32) -> Vec<Vec<ScanPoint>> {
+    let mut points: Vec<ScanPoint> = scan.iter().filter(|point| point.is_valid()).cloned().collect();
+    points.sort();
+
+    let mut segments: Vec<Vec<ScanPoint>> = Vec::new();
+
+    for point in points {
+        let connected = match segments.last().and_then(|segment| segment.last()) {
+            Some(last) => cartesian_distance(to_cartesian_meters(last), to_cartesian_meters(&point)) <= max_gap_m,
+            None => false,
+        };
+
+        if connected {
+            segments.last_mut().unwrap().push(point);
+        } else {
+            segments.push(vec![point]);
+        }
+    }
+
+    if segments.len() > 1 {
+        let first = to_cartesian_meters(&segments[0][0]);
+        let last = to_cartesian_meters(segments.last().unwrap().last().unwrap());
+
+        if cartesian_distance(first, last) <= max_gap_m {
+            let mut tail = segments.pop().unwrap();
+            tail.append(&mut segments[0]);
+            segments[0] = tail;
+        }
+    }
+
+    return segments;
+}
+
+/// fit a line to the points, and return the direction (in radians, within `[0, PI)`) and the length of the line
+fn fit_line(points: &[ScanPoint]) -> (f32, f32) {
+    let xy: Vec<(f32, f32)> = points.iter().map(to_cartesian_meters).collect();
+    let count = xy.len() as f32;
+    let mean_x = xy.iter().map(|p| p.0).sum::<f32>() / count;
+    let mean_y = xy.iter().map(|p| p.1).sum::<f32>() / count;
+
+    let (mut sxx, mut syy, mut sxy) = (0f32, 0f32, 0f32);
+    for (x, y) in xy.iter() {
+        sxx += (x - mean_x) * (x - mean_x);
+        syy += (y - mean_y) * (y - mean_y);
+        sxy += (x - mean_x) * (y - mean_y);
+    }
+
+    let mut angle = 0.5f32 * (2f32 * sxy).atan2(sxx - syy);
+    if angle < 0f32 {
+        angle += PI;
+    }
+    if angle >= PI {
+        angle -= PI;
+    }
+
+    let (dir_x, dir_y) = (angle.cos(), angle.sin());
+    let projections = xy.iter().map(|(x, y)| (x - mean_x) * dir_x + (y - mean_y) * dir_y);
+    let (min, max) = projections.fold((f32::MAX, f32::MIN), |(min, max), t| (min.min(t), max.max(t)));
+
+    return (angle, max - min);
+}
+
+/// find the direction (in radians, within `[0, PI)`) of the longest wall in the scan
+///
+/// Returns `None` if there is no segment long enough (0.5 meters) to be a wall
+pub fn dominant_wall_angle(scan: &[ScanPoint]) -> Option<f32> {
+    return segment_scan(scan, WALL_MAX_GAP)
+        .iter()
+        .filter(|segment| segment.len() >= WALL_MIN_POINTS)
+        .map(|segment| fit_line(segment))
+        .filter(|(_, length)| *length >= WALL_MIN_LENGTH)
+        .fold(None, |longest: Option<(f32, f32)>, line| match longest {
+            Some(longest) if longest.1 >= line.1 => Some(longest),
+            _ => Some(line),
+        })
+        .map(|(angle, _)| angle);
+}
+
+/// estimate the incidence angle (in radians, within `[0, PI/2]`) of each point
+///
+/// The incidence angle is the angle between the beam and the normal of the surface, where the surface is
+/// approximated by the line through the previous and next valid points, so the scan is expected to be ordered.
+/// It is only an estimate, and it is `NaN` for invalid points or if there are not enough valid points.
+pub fn incidence_angles(scan: &[ScanPoint]) -> Vec<f32> {
+    let valid: Vec<usize> = (0..scan.len()).filter(|i| scan[*i].is_valid()).collect();
+    let mut angles = vec![f32::NAN; scan.len()];
+
+    if valid.len() < 2 {
+        return angles;
+    }
+
+    for (k, index) in valid.iter().enumerate() {
+        let prev = to_cartesian_meters(&scan[valid[k.saturating_sub(1)]]);
+        let next = to_cartesian_meters(&scan[valid[(k + 1).min(valid.len() - 1)]]);
+        let (tangent_x, tangent_y) = (next.0 - prev.0, next.1 - prev.1);
+        let tangent_length = (tangent_x * tangent_x + tangent_y * tangent_y).sqrt();
+
+        if tangent_length <= 0f32 {
+            continue;
+        }
+
+        let beam_angle = scan[*index].angle();
+        let cos_to_tangent = (beam_angle.cos() * tangent_x + beam_angle.sin() * tangent_y) / tangent_length;
+        angles[*index] = cos_to_tangent.abs().min(1f32).asin();
+    }
+
+    return angles;
+}
+
+/// find the nearest valid point of the scan, and return its distance (in meters) with the point
+pub fn min_clearance(scan: &[ScanPoint]) -> Option<(f32, ScanPoint)> {
+    return scan
+        .iter()
+        .filter(|point| point.is_valid())
+        .fold(None, |nearest: Option<&ScanPoint>, point| match nearest {
+            Some(nearest) if nearest.distance() <= point.distance() => Some(nearest),
+            _ => Some(point),
+        })
+        .map(|point| (point.distance(), point.clone()));
+}
+
+/// find the nearest valid range (in meters) within the cone of `cone_deg` wide centered at `heading_deg`
+///
+/// The cone could span across 0 degree. Returns `None` if there is no valid return in the cone
+pub fn forward_clearance(scan: &[ScanPoint], heading_deg: f32, cone_deg: f32) -> Option<f32> {
+    let half_cone_deg = cone_deg / 2f32;
+
+    return valid_points(scan)
+        .filter(|point| {
+            let offset_deg = normalize_degrees(point.angle().to_degrees() - heading_deg + 180f32) - 180f32;
+            offset_deg.abs() <= half_cone_deg
+        })
+        .map(|point| point.distance())
+        .fold(None, |nearest: Option<f32>, distance| match nearest {
+            Some(nearest) if nearest <= distance => Some(nearest),
+            _ => Some(distance),
+        });
+}
+
+/// find the `k` nearest valid points of the scan, sorted by distance ascending
+///
+/// Only the nearest `k` points are sorted, so it's cheaper than sorting the whole scan
+pub fn nearest_k(scan: &[ScanPoint], k: usize) -> Vec<ScanPoint> {
+    let mut points: Vec<ScanPoint> = valid_points(scan).cloned().collect();
+
+    if k == 0 {
+        return Vec::new();
+    }
+
+    if k < points.len() {
+        points.select_nth_unstable_by_key(k - 1, |point| point.dist_mm_q2);
+        points.truncate(k);
+    }
+
+    points.sort_unstable_by_key(|point| point.dist_mm_q2);
+    return points;
+}
+
+/// front sector (in degrees) of the LIDAR, where angles increase clockwise from the front
+pub const SECTOR_FRONT: (f32, f32) = (315f32, 45f32);
+
+/// right sector (in degrees) of the LIDAR
+pub const SECTOR_RIGHT: (f32, f32) = (45f32, 135f32);
+
+/// rear sector (in degrees) of the LIDAR
+pub const SECTOR_BACK: (f32, f32) = (135f32, 225f32);
+
+/// left sector (in degrees) of the LIDAR
+pub const SECTOR_LEFT: (f32, f32) = (225f32, 315f32);
+
+/// front, left, back and right sectors, for `split_into_sectors`
+pub const QUADRANT_SECTORS: [(f32, f32); 4] = [SECTOR_FRONT, SECTOR_LEFT, SECTOR_BACK, SECTOR_RIGHT];
+
+fn normalize_degrees(angle: f32) -> f32 {
+    let angle = angle % 360f32;
+    return if angle < 0f32 { angle + 360f32 } else { angle };
+}
+
+fn in_sector(angle_deg: f32, sector: (f32, f32)) -> bool {
+    let (start, end) = (normalize_degrees(sector.0), normalize_degrees(sector.1));
+
+    if start <= end {
+        return angle_deg >= start && angle_deg < end;
+    } else {
+        return angle_deg >= start || angle_deg < end;
+    }
+}
+
+/// split valid points of the scan into angular sectors
+///
+/// Each sector is `(start_deg, end_deg)` with start inclusive and end exclusive, and a sector wraps across
+/// 0 degree if start is greater than end (e.g. `(315, 45)`). Points in overlapped sectors are put into each of them.
+pub fn split_into_sectors(scan: &[ScanPoint], sectors: &[(f32, f32)]) -> Vec<Vec<ScanPoint>> {
+    let mut result: Vec<Vec<ScanPoint>> = vec![Vec::new(); sectors.len()];
+
+    for point in scan.iter().filter(|point| point.is_valid()) {
+        let angle_deg = normalize_degrees(point.angle().to_degrees());
+
+        for (sector, points) in sectors.iter().zip(result.iter_mut()) {
+            if in_sector(angle_deg, *sector) {
+                points.push(point.clone());
+            }
+        }
+    }
+
+    return result;
+}
+
+/// find the widest arc without obstacle within `clear_range_m` meters, and return its `(center_deg, width_deg)`
+///
+/// The arc spans between the neighbor obstacle points, and no-returns are considered to be clear.
+/// Arcs across 0 degree are handled, and the whole circle `(0, 360)` is returned if there is no obstacle.
+/// Returns `None` if the scan is empty.
+pub fn widest_free_arc(scan: &[ScanPoint], clear_range_m: f32) -> Option<(f32, f32)> {
+    if scan.is_empty() {
+        return None;
+    }
+
+    let mut obstacles: Vec<f32> = scan
+        .iter()
+        .filter(|point| point.is_valid() && point.distance() <= clear_range_m)
+        .map(|point| normalize_degrees(point.angle().to_degrees()))
+        .collect();
+    obstacles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    if obstacles.is_empty() {
+        return Some((0f32, 360f32));
+    }
+
+    let mut widest = (0f32, 0f32);
+
+    for (i, start) in obstacles.iter().enumerate() {
+        let end = obstacles[(i + 1) % obstacles.len()];
+        let mut width = end - start;
+        if width <= 0f32 {
+            width += 360f32;
+        }
+
+        if width > widest.1 {
+            widest = (normalize_degrees(start + width / 2f32), width);
+        }
+    }
+
+    return Some(widest);
+}
+
+/// score (within `[0, 1]`) how symmetric the left and right sides of the scan are
+///
+/// This is a heuristic: ranges of the right sector are compared with ranges of the mirrored angles on the left,
+/// in 1 degree bins. A score close to 1 suggests the LIDAR is centered between parallel walls.
+/// Returns 0 if no bin has valid returns on both sides.
+pub fn lateral_symmetry(scan: &[ScanPoint]) -> f32 {
+    let bins = bin_scan(scan, 360);
+    let (start, end) = (SECTOR_RIGHT.0 as usize, SECTOR_RIGHT.1 as usize);
+
+    let differences: Vec<f32> = (start..end)
+        .filter_map(|bin| match (bins[bin], bins[359 - bin]) {
+            (Some(right), Some(left)) => Some((right - left).abs() / right.max(left)),
+            _ => None,
+        })
+        .collect();
+
+    if differences.is_empty() {
+        return 0f32;
+    }
+
+    return 1f32 - differences.iter().sum::<f32>() / (differences.len() as f32);
+}
+
+/// Magic bytes of each scan record in the binary scan format
+const SCAN_BIN_MAGIC: &[u8; 4] = b"RPSC";
+
+/// Version of the binary scan format
+const SCAN_BIN_VERSION: u8 = 1;
+
+/// magic(4) + version(1) + sequence(8) + mode id(2) + point count(4)
+const SCAN_BIN_HEADER_SIZE: usize = 19;
+
+/// angle_z_q14(2) + dist_mm_q2(4) + quality(1) + flag(1)
+const SCAN_BIN_POINT_SIZE: usize = 8;
+
+/// Max count of points in a scan record, so corrupted point counts can't exhaust memory
+const SCAN_BIN_MAX_POINTS: usize = 65536;
+
+/// A scan read back from the binary scan format
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedScan {
+    /// The sequence number of the scan when it's recorded
+    pub sequence: u64,
+
+    /// The id of the scan mode in which the scan is measured
+    pub mode_id: u16,
+
+    /// The scan points
+    pub points: Vec<ScanPoint>,
+}
+
+/// append the scan to the writer in the compact binary scan format
+///
+/// Each scan is a self-contained record: a header with magic `RPSC`, format version, sequence number,
+/// scan mode id and point count, followed by 8 bytes per point (all little endian).
+/// Scans of more than 65536 points are rejected
+pub fn write_scan_bin<W: Write>(writer: &mut W, scan: &Scan) -> Result<()> {
+    if scan.points.len() > SCAN_BIN_MAX_POINTS {
+        return Err(RposError::OperationFail { description: "too many points for a scan record".to_owned() }.into());
+    }
+
+    let mut bytes = vec![0u8; SCAN_BIN_HEADER_SIZE + scan.points.len() * SCAN_BIN_POINT_SIZE];
+
+    bytes[0..4].copy_from_slice(SCAN_BIN_MAGIC);
+    bytes[4] = SCAN_BIN_VERSION;
+    LittleEndian::write_u64(&mut bytes[5..13], scan.sequence);
+    LittleEndian::write_u16(&mut bytes[13..15], scan.mode.id);
+    LittleEndian::write_u32(&mut bytes[15..19], scan.points.len() as u32);
+
+    for (point, buf) in scan.points.iter().zip(bytes[SCAN_BIN_HEADER_SIZE..].chunks_mut(SCAN_BIN_POINT_SIZE)) {
+        LittleEndian::write_u16(&mut buf[0..2], point.angle_z_q14);
+        LittleEndian::write_u32(&mut buf[2..6], point.dist_mm_q2);
+        buf[6] = point.quality;
+        buf[7] = point.flag;
+    }
+
+    writer.write_all(&bytes)?;
+    return Ok(());
+}
+
+/// read as many bytes as possible into the buffer, and return the count of bytes read
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+
+    while read < buf.len() {
+        match reader.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    return Ok(read);
+}
+
+fn invalid_scan_record() -> Error {
+    return RposError::ProtocolError { description: "invalid scan record".to_owned() }.into();
+}
+
+/// read a scan written by `write_scan_bin`, or `None` if the reader is at the end
+pub fn read_scan_bin<R: Read>(reader: &mut R) -> Result<Option<RecordedScan>> {
+    let mut header = [0u8; SCAN_BIN_HEADER_SIZE];
+
+    match read_fully(reader, &mut header)? {
+        0 => return Ok(None),
+        SCAN_BIN_HEADER_SIZE => {}
+        _ => return Err(invalid_scan_record()),
+    }
+
+    if &header[0..4] != SCAN_BIN_MAGIC || header[4] != SCAN_BIN_VERSION {
+        return Err(invalid_scan_record());
+    }
+
+    let count = LittleEndian::read_u32(&header[15..19]) as usize;
+    if count > SCAN_BIN_MAX_POINTS {
+        return Err(invalid_scan_record());
+    }
+
+    let mut bytes = vec![0u8; count * SCAN_BIN_POINT_SIZE];
+    if read_fully(reader, &mut bytes)? != bytes.len() {
+        return Err(invalid_scan_record());
+    }
+
+    let points = bytes
+        .chunks(SCAN_BIN_POINT_SIZE)
+        .map(|buf| ScanPoint {
+            angle_z_q14: LittleEndian::read_u16(&buf[0..2]),
+            dist_mm_q2: LittleEndian::read_u32(&buf[2..6]),
+            quality: buf[6],
+            flag: buf[7],
+        })
+        .collect();
+
+    return Ok(Some(RecordedScan {
+        sequence: LittleEndian::read_u64(&header[5..13]),
+        mode_id: LittleEndian::read_u16(&header[13..15]),
+        points: points,
+    }));
+}
+
+/// Iterator over the scan points of scans read by `read_scan_bin`, created by `replay_from`
+///
+/// It ends at the end of the reader, or after the first error
+#[derive(Debug)]
+pub struct ScanReplay<'a, R: Read> {
+    reader: &'a mut R,
+    finished: bool,
+}
+
+impl<'a, R: Read> Iterator for ScanReplay<'a, R> {
+    type Item = Result<Vec<ScanPoint>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match read_scan_bin(self.reader) {
+            Ok(Some(scan)) => return Some(Ok(scan.points)),
+            Ok(None) => {
+                self.finished = true;
+                return None;
+            }
+            Err(err) => {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// replay the scans written by `write_scan_bin` (or `RplidarDevice::record_to`) from the reader, without a device
+pub fn replay_from<R: Read>(reader: &mut R) -> ScanReplay<'_, R> {
+    return ScanReplay {
+        reader: reader,
+        finished: false,
+    };
+}
+
+/// convert the scan into `bins` ranges normalized by `max_range_m` into `[0, 1]`, as the input of ML models
+///
+/// Each entry is the nearest valid range of the bin divided by `max_range_m` (clamped to 1),
+/// and bins without valid returns are 1
+pub fn to_range_image(scan: &[ScanPoint], bins: usize, max_range_m: f32) -> Vec<f32> {
+    return bin_scan(scan, bins)
+        .iter()
+        .map(|bin| match bin {
+            Some(distance) if max_range_m > 0f32 => (distance / max_range_m).min(1f32),
+            _ => 1f32,
+        })
+        .collect();
+}
+
+/// convert the scan into `bins` normalized ranges like `to_range_image`, rotated so bin 0 is centered on `heading_deg`
+///
+/// Following bins go clockwise from the heading and wrap around, so the output does not depend on the heading.
+/// Bins without valid returns are 1
+pub fn to_heading_centered_bins(scan: &[ScanPoint], bins: usize, heading_deg: f32, max_range_m: f32) -> Vec<f32> {
+    let mut output = vec![1f32; bins];
+
+    if bins == 0 {
+        return output;
+    }
+
+    let half_bin = PI2 / (bins as f32) / 2f32;
+    let heading = heading_deg.to_radians();
+
+    for point in valid_points(scan) {
+        let bin = &mut output[angle_to_bin(point.angle() - heading + half_bin, bins)];
+        let range = if max_range_m > 0f32 { (point.distance() / max_range_m).min(1f32) } else { 1f32 };
+        *bin = bin.min(range);
+    }
+
+    return output;
+}
+
+/// Count of bins `ScanStats::coverage` is measured in
+const SCAN_STATS_COVERAGE_BINS: usize = 360;
+
+/// Accumulator of `ScanStats`, which takes points one by one
+#[derive(Debug, Clone)]
+pub struct ScanStatsAccumulator {
+    valid_points: usize,
+    min_distance: f32,
+    max_distance: f32,
+    sum_distance: f64,
+    covered: Vec<bool>,
+}
+
+impl Default for ScanStatsAccumulator {
+    fn default() -> ScanStatsAccumulator {
+        return ScanStatsAccumulator::new();
+    }
+}
+
+impl ScanStatsAccumulator {
+    /// accumulator without points
+    pub fn new() -> ScanStatsAccumulator {
+        return ScanStatsAccumulator {
+            valid_points: 0,
+            min_distance: f32::INFINITY,
+            max_distance: 0f32,
+            sum_distance: 0f64,
+            covered: vec![false; SCAN_STATS_COVERAGE_BINS],
+        };
+    }
+
+    /// accumulate the point, invalid points are ignored
+    pub fn push(&mut self, point: &ScanPoint) {
+        if !point.is_valid() {
+            return;
+        }
+
+        let distance = point.distance();
+        self.valid_points += 1;
+        self.min_distance = self.min_distance.min(distance);
+        self.max_distance = self.max_distance.max(distance);
+        self.sum_distance += distance as f64;
+        self.covered[angle_to_bin(point.angle(), SCAN_STATS_COVERAGE_BINS)] = true;
+    }
+
+    /// statistics of the points accumulated
+    pub fn stats(&self) -> ScanStats {
+        let has_points = self.valid_points > 0;
+        let covered = self.covered.iter().filter(|covered| **covered).count();
+
+        return ScanStats {
+            valid_points: self.valid_points,
+            min_distance: if has_points { Some(self.min_distance) } else { None },
+            max_distance: if has_points { Some(self.max_distance) } else { None },
+            mean_distance: if has_points { Some((self.sum_distance / (self.valid_points as f64)) as f32) } else { None },
+            coverage: (covered as f32) / (SCAN_STATS_COVERAGE_BINS as f32),
+        };
+    }
+}
+
+/// compute the statistics of valid points in the scan
+pub fn scan_stats(scan: &[ScanPoint]) -> ScanStats {
+    let mut accumulator = ScanStatsAccumulator::new();
+    for point in scan.iter() {
+        accumulator.push(point);
+    }
+    return accumulator.stats();
+}
+
+/// Max exponent of bin counts accepted by `to_pow2_bins` (65536 bins, finer than any LIDAR resolves)
+pub const MAX_POW2_BINS_EXP: u8 = 16;
+
+/// resample the scan into `2^exp` evenly spaced ranges (in meters), as the input of FFT-based processing
+///
+/// Each range is the nearest return within its bin, and bins without valid returns are `f32::INFINITY`.
+/// `exp` is clamped to `MAX_POW2_BINS_EXP`
+pub fn to_pow2_bins(scan: &[ScanPoint], exp: u8) -> Vec<f32> {
+    let bins = 1usize << std::cmp::min(exp, MAX_POW2_BINS_EXP);
+
+    return bin_scan(scan, bins)
+        .iter()
+        .map(|bin| bin.unwrap_or(f32::INFINITY))
+        .collect();
+}
+
+/// fraction of consecutive point pairs whose angle increases (the wrap around 360 degrees counts as increasing)
+///
+/// A correctly decoded rotation scores close to 1, low values mean bugs in capsule decoding or corrupted data.
+/// Scans with less than two points score 1
+pub fn angle_monotonicity(scan: &[ScanPoint]) -> f32 {
+    if scan.len() < 2 {
+        return 1f32;
+    }
+
+    let increasing = scan
+        .windows(2)
+        .filter(|pair| (pair[1].angle_z_q14.wrapping_sub(pair[0].angle_z_q14) as i16) > 0)
+        .count();
+
+    return (increasing as f32) / ((scan.len() - 1) as f32);
+}
+
+/// angular coverage weighted by quality: the best quality (normalized into `[0, 1]`) of valid points in each bin,
+/// summed and divided by `bins`
+///
+/// Unlike binary coverage, weak returns (dirty optics or distant targets) contribute less than strong ones
+pub fn quality_weighted_coverage(scan: &[ScanPoint], bins: usize) -> f32 {
+    if bins == 0 {
+        return 0f32;
+    }
+
+    let mut best_quality = vec![0u8; bins];
+    for point in valid_points(scan) {
+        let bin = &mut best_quality[angle_to_bin(point.angle(), bins)];
+        *bin = (*bin).max(point.quality);
+    }
+
+    let sum: f32 = best_quality.iter().map(|quality| (*quality as f32) / 255f32).sum();
+    return sum / (bins as f32);
+}
+
+/// Angular bins (one per degree) compared by `estimate_scan_delta`
+const SCAN_DELTA_BINS: usize = 360;
+
+/// Largest rotation (in degrees) searched by `estimate_scan_delta`
+const SCAN_DELTA_MAX_SHIFT: i32 = 45;
+
+/// Least bins with valid returns in both scans for a shift to be taken into account
+const SCAN_DELTA_MIN_OVERLAP: usize = 36;
+
+/// mean absolute difference of ranges between `curr` and `prev` shifted by `shift` bins
+fn shifted_range_difference(prev: &[Option<f32>], curr: &[Option<f32>], shift: i32) -> Option<f32> {
+    let bins = prev.len() as i32;
+    let mut difference = 0f32;
+    let mut overlap = 0usize;
+
+    for (i, curr_range) in curr.iter().enumerate() {
+        if let (Some(curr_range), Some(prev_range)) = (curr_range, prev[(i as i32 + shift).rem_euclid(bins) as usize]) {
+            difference += (curr_range - prev_range).abs();
+            overlap += 1;
+        }
+    }
+
+    if overlap < SCAN_DELTA_MIN_OVERLAP {
+        return None;
+    }
+
+    return Some(difference / (overlap as f32));
+}
+
+/// estimate the rotation of the sensor (in degrees, in the direction of scan angles) from `prev` to `curr`
+///
+/// The scans are registered by matching ranges of 1 degree bins, at shifts up to 45 degrees,
+/// and refined to sub-degree by parabolic interpolation. Only pure rotation is modeled, so translation
+/// of the sensor or moving objects bias the estimation. Returns `None` if the scans hardly overlap
+pub fn estimate_scan_delta(prev: &[ScanPoint], curr: &[ScanPoint]) -> Option<f32> {
+    let prev = bin_scan(prev, SCAN_DELTA_BINS);
+    let curr = bin_scan(curr, SCAN_DELTA_BINS);
+
+    let costs: Vec<Option<f32>> = (-SCAN_DELTA_MAX_SHIFT..=SCAN_DELTA_MAX_SHIFT)
+        .map(|shift| shifted_range_difference(&prev, &curr, shift))
+        .collect();
+
+    let (best, best_cost) = costs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, cost)| cost.map(|cost| (i, cost)))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))?;
+
+    let mut refinement = 0f32;
+    if best > 0 && best < costs.len() - 1 {
+        if let (Some(before), Some(after)) = (costs[best - 1], costs[best + 1]) {
+            let curvature = before - 2f32 * best_cost + after;
+            if curvature > 0f32 {
+                refinement = 0.5f32 * (before - after) / curvature;
+            }
+        }
+    }
+
+    let shift = (best as i32 - SCAN_DELTA_MAX_SHIFT) as f32 + refinement;
+    return Some(shift * 360f32 / (SCAN_DELTA_BINS as f32));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_scan_record_with_huge_count() {
+        let mut record = Vec::new();
+        record.extend_from_slice(SCAN_BIN_MAGIC);
+        record.push(SCAN_BIN_VERSION);
+        record.extend_from_slice(&[0u8; 10]);
+        record.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        record.extend_from_slice(&[0u8; SCAN_BIN_POINT_SIZE]);
+
+        let err = read_scan_bin(&mut &record[..]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RposError>(),
+            Some(RposError::ProtocolError { description }) if description == "invalid scan record"
+        ));
+        assert!(replay_from(&mut &record[..]).next().unwrap().is_err());
+    }
+
+    pub fn scan_point(angle: f32, distance: f32) -> ScanPoint {
+        let mut point = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality: 0xbc,
+            flag: 0,
+        };
+        point.set_angle(angle);
+        point.set_distance(distance);
+        return point;
+    }
+
+    pub fn uniform_scan(count: usize, distance: f32) -> Vec<ScanPoint> {
+        (0..count)
+            .map(|i| scan_point(PI2 * (i as f32 + 0.5f32) / (count as f32), distance))
+            .collect()
+    }
+
+    #[test]
+    fn forward_clearance_in_cone() {
+        let mut scan = uniform_scan(360, 5f32);
+        scan.push(scan_point(1f32.to_radians(), 0.8f32));
+        scan.push(scan_point(20f32.to_radians(), 0.5f32));
+
+        assert_eq!(forward_clearance(&scan, 0f32, 10f32), Some(0.8f32));
+        assert_eq!(forward_clearance(&scan, 358f32, 10f32), Some(0.8f32));
+        assert_eq!(forward_clearance(&scan, 180f32, 10f32), Some(5f32));
+        assert_eq!(forward_clearance(&scan, 0f32, 60f32), Some(0.5f32));
+        assert_eq!(forward_clearance(&scan[..10], 180f32, 10f32), None);
+    }
+
+    #[test]
+    fn average_noisy_scans() {
+        // deterministic noise within +-3cm, different in each copy
+        let noisy = |seed: usize| -> Vec<ScanPoint> {
+            let mut scan = uniform_scan(90, 2f32);
+            for (i, point) in scan.iter_mut().enumerate() {
+                let noise = ((i * 3 + seed * 5) % 7) as f32 / 100f32 - 0.03f32;
+                point.set_distance(2f32 + noise);
+            }
+            scan
+        };
+
+        let scans = vec![noisy(0), noisy(1), noisy(2)];
+        let variance = |ranges: &[f32]| ranges.iter().map(|range| (range - 2f32).powi(2)).sum::<f32>() / (ranges.len() as f32);
+
+        let averaged = average_scans(&scans, 90);
+        assert_eq!(averaged.len(), 90);
+        for scan in scans.iter() {
+            let ranges: Vec<f32> = scan.iter().map(|point| point.distance()).collect();
+            assert!(variance(&averaged) < variance(&ranges) / 2f32);
+        }
+
+        // bins without valid returns in any scan
+        let averaged = average_scans(&[uniform_scan(2, 1f32), Vec::new()], 4);
+        assert_eq!(averaged.iter().filter(|range| range.is_infinite()).count(), 2);
+        assert!(average_scans(&[], 4).iter().all(|range| range.is_infinite()));
+    }
+
+    #[test]
+    fn pow2_bins() {
+        let ranges = to_pow2_bins(&uniform_scan(128, 2f32), 8);
+        assert_eq!(ranges.len(), 256);
+        assert_eq!(ranges.iter().filter(|range| **range == 2f32).count(), 128);
+        assert_eq!(ranges.iter().filter(|range| range.is_infinite()).count(), 128);
+
+        assert_eq!(to_pow2_bins(&[], 0), vec![f32::INFINITY]);
+        assert_eq!(to_pow2_bins(&[], 255).len(), 1 << MAX_POW2_BINS_EXP);
+    }
+
+    #[test]
+    fn iterate_valid_points() {
+        let mut scan = uniform_scan(10, 2f32);
+        scan[2].dist_mm_q2 = 0;
+        scan[7].quality = 0;
+
+        assert_eq!(valid_points(&scan).count(), 8);
+        assert!(valid_points(&scan).all(|point| point.is_valid()));
+        assert_eq!(valid_points(&[]).count(), 0);
+    }
+
+    #[test]
+    fn fingerprint_of_identical_scans() {
+        let scan = uniform_scan(360, 2f32);
+        let mut perturbed = scan.clone();
+        perturbed[100].set_distance(2.5f32);
+
+        assert_eq!(scan_fingerprint(&scan), scan_fingerprint(&scan.clone()));
+        assert_ne!(scan_fingerprint(&scan), scan_fingerprint(&perturbed));
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        let mut scan = uniform_scan(720, 2f32);
+        for point in scan[100..140].iter_mut() {
+            point.set_distance(0.8f32);
+        }
+        scan[500].dist_mm_q2 = 0;
+
+        let rle = scan_to_rle(&scan, 360, 10f32);
+        assert!(rle.len() < 10);
+
+        let ranges = rle_to_ranges(&rle);
+        let binned = bin_scan(&scan, 360);
+        assert_eq!(ranges.len(), binned.len());
+
+        for (range, bin) in ranges.iter().zip(binned.iter()) {
+            let expected = bin.map(|distance| distance * 1000f32).unwrap_or(0f32);
+            assert!((range - expected).abs() <= 5f32);
+        }
+    }
+
+    #[test]
+    fn difference_of_scans() {
+        let prev = uniform_scan(360, 2f32);
+        let mut curr = prev.clone();
+        curr[90].set_distance(1.5f32);
+        curr[180].dist_mm_q2 = 0;
+
+        let diff = scan_difference(&prev, &curr, 360);
+        assert_eq!(diff.len(), 360);
+
+        for (i, delta) in diff.iter().enumerate() {
+            match i {
+                90 => assert!((delta + 0.5f32).abs() < 1e-3),
+                180 => assert!(delta.is_nan()),
+                _ => assert_eq!(*delta, 0f32),
+            }
+        }
+    }
+
+    fn cartesian_point(x: f32, y: f32) -> ScanPoint {
+        let mut angle = y.atan2(x);
+        if angle < 0f32 {
+            angle += PI2;
+        }
+        return scan_point(angle, (x * x + y * y).sqrt());
+    }
+
+    #[test]
+    fn dominant_wall() {
+        let wall_angle = PI / 6f32;
+        let (dir_x, dir_y) = (wall_angle.cos(), wall_angle.sin());
+
+        // a 3 meters long wall passing (0.5, 1.5), and a short wall
+        let mut scan: Vec<ScanPoint> = (0..60)
+            .map(|i| {
+                let t = (i as f32) * 0.05f32 - 1.5f32;
+                cartesian_point(0.5f32 + t * dir_x, 1.5f32 + t * dir_y)
+            })
+            .collect();
+        scan.extend((0..10).map(|i| cartesian_point(-2f32, -1f32 - (i as f32) * 0.05f32)));
+
+        let angle = dominant_wall_angle(&scan).unwrap();
+        assert!((angle - wall_angle).abs() < 0.01f32);
+
+        assert_eq!(dominant_wall_angle(&scan[60..]), None);
+    }
+
+    #[test]
+    fn incidence_on_flat_wall() {
+        // a wall at x = 1m, so the incidence angle equals to the beam angle from x axis
+        let mut scan: Vec<ScanPoint> = (0..41).map(|i| cartesian_point(1f32, (i as f32) * 0.05f32 - 1f32)).collect();
+        scan[5].dist_mm_q2 = 0;
+
+        let angles = incidence_angles(&scan);
+        assert_eq!(angles.len(), scan.len());
+        assert!(angles[5].is_nan());
+
+        for (point, angle) in scan.iter().zip(angles.iter()).filter(|(point, _)| point.is_valid()) {
+            let (x, y) = to_cartesian_meters(point);
+            assert!((angle - (y / x).atan().abs()).abs() < 0.01f32);
+        }
+
+        assert!(angles[20] < 0.01f32);
+        assert!(angles[40] > angles[30]);
+    }
+
+    #[test]
+    fn nearest_point() {
+        let mut scan = uniform_scan(360, 2f32);
+        scan[30].set_distance(1.2f32);
+        scan[200].set_distance(0.6f32);
+        scan[300].set_distance(0.8f32);
+        scan[100].dist_mm_q2 = 0;
+
+        let (distance, point) = min_clearance(&scan).unwrap();
+        assert!((distance - 0.6f32).abs() < 1e-3);
+        assert_eq!(point.angle_z_q14, scan[200].angle_z_q14);
+
+        let invalid: Vec<ScanPoint> = scan.iter().cloned().map(|mut point| { point.dist_mm_q2 = 0; point }).collect();
+        assert!(min_clearance(&invalid).is_none());
+    }
+
+    #[test]
+    fn nearest_k_points() {
+        let mut scan = uniform_scan(720, 5f32);
+        for (i, distance) in [(600, 0.9f32), (30, 1.2f32), (400, 0.3f32), (100, 2.5f32), (250, 0.6f32)].iter() {
+            scan[*i].set_distance(*distance);
+        }
+        scan[10].dist_mm_q2 = 0;
+
+        let nearest = nearest_k(&scan, 4);
+        let angles: Vec<u16> = nearest.iter().map(|point| point.angle_z_q14).collect();
+        assert_eq!(angles, [scan[400].angle_z_q14, scan[250].angle_z_q14, scan[600].angle_z_q14, scan[30].angle_z_q14]);
+
+        assert_eq!(nearest_k(&scan, 0), []);
+        assert_eq!(nearest_k(&scan, 1000).len(), 719);
+        assert!(nearest_k(&scan, 1000).iter().all(|point| point.is_valid()));
+    }
+
+    #[test]
+    fn free_arc() {
+        let mut scan = uniform_scan(360, 0.5f32);
+        for point in scan[80..120].iter_mut() {
+            point.set_distance(3f32);
+        }
+        scan[90].dist_mm_q2 = 0;
+
+        let (center, width) = widest_free_arc(&scan, 1f32).unwrap();
+        assert!((center - 100f32).abs() < 0.1f32);
+        assert!((width - 41f32).abs() < 0.1f32);
+
+        let mut wrapped = uniform_scan(360, 0.5f32);
+        for point in wrapped.iter_mut().filter(|point| point.angle() < 11f32.to_radians() || point.angle() > 349f32.to_radians()) {
+            point.set_distance(3f32);
+        }
+
+        let (center, width) = widest_free_arc(&wrapped, 1f32).unwrap();
+        assert!(center.min(360f32 - center) < 0.1f32);
+        assert!((width - 23f32).abs() < 0.1f32);
+
+        assert_eq!(widest_free_arc(&wrapped, 0.1f32), Some((0f32, 360f32)));
+        assert_eq!(widest_free_arc(&[], 1f32), None);
+    }
+
+    #[test]
+    fn symmetry_of_sides() {
+        let mut symmetric = uniform_scan(360, 1f32);
+        let mut asymmetric = uniform_scan(360, 1f32);
+        for (a, b) in symmetric.iter_mut().zip(asymmetric.iter_mut()) {
+            let angle = a.angle();
+            a.set_distance(2f32 + angle.cos());
+            b.set_distance(2f32 + angle.sin());
+        }
+
+        assert!(lateral_symmetry(&symmetric) > 0.99f32);
+        assert!(lateral_symmetry(&asymmetric) < 0.7f32);
+        assert_eq!(lateral_symmetry(&[]), 0f32);
+    }
+
+    #[test]
+    fn quadrant_sectors() {
+        let mut scan = uniform_scan(360, 2f32);
+        scan[0].dist_mm_q2 = 0;
+
+        let sectors = split_into_sectors(&scan, &QUADRANT_SECTORS);
+        assert_eq!(sectors.len(), 4);
+        assert_eq!(sectors[0].len(), 89);
+        assert!(sectors[1..].iter().all(|points| points.len() == 90));
+
+        let degree = PI / 180f32;
+        assert!(sectors[0].iter().all(|point| point.angle() < 45f32 * degree || point.angle() >= 315f32 * degree));
+        assert!(sectors[1].iter().all(|point| point.angle() >= 225f32 * degree && point.angle() < 315f32 * degree));
+
+        let overlapped = split_into_sectors(&scan, &[(0f32, 180f32), (90f32, 270f32)]);
+        assert_eq!(overlapped[0].len(), 179);
+        assert_eq!(overlapped[1].len(), 180);
+    }
+
+    #[test]
+    fn trim_overlapped_rotation() {
+        let degree = PI / 180f32;
+        let mut scan: Vec<ScanPoint> = (10..376).map(|i| scan_point(((i % 360) as f32 + 0.5f32) * degree, 1f32)).collect();
+
+        trim_scan_wrap(&mut scan);
+        assert_eq!(scan.len(), 360);
+        assert!((scan.last().unwrap().angle() - 9.5f32 * degree).abs() < 1e-3);
+
+        let mut partial = scan[0..100].to_vec();
+        trim_scan_wrap(&mut partial);
+        assert_eq!(partial.len(), 100);
+    }
+
+    #[test]
+    fn range_image() {
+        let mut scan = uniform_scan(720, 2f32);
+        scan[0].set_distance(1f32);
+        scan[1].set_distance(1.5f32);
+        scan[100].set_distance(30f32);
+        scan[101].set_distance(30f32);
+        scan[200].dist_mm_q2 = 0;
+        scan[201].quality = 0;
+
+        let image = to_range_image(&scan, 360, 10f32);
+        assert_eq!(image.len(), 360);
+        assert!((image[0] - 0.1f32).abs() < 1e-4);
+        assert_eq!(image[50], 1f32);
+        assert_eq!(image[100], 1f32);
+        assert!((image[101] - 0.2f32).abs() < 1e-4);
+        assert!(image.iter().all(|range| (0f32..=1f32).contains(range)));
+    }
+
+    #[test]
+    fn filter_by_quality_and_distance() {
+        let mut scan = uniform_scan(6, 1f32);
+        scan[1].quality = 5;
+        scan[2].dist_mm_q2 = 0;
+        scan[3].set_distance(0.05f32);
+        scan[4].set_distance(13f32);
+
+        let filtered = filter_scan(&scan, 10, 100, 12000);
+        assert_eq!(filtered, vec![scan[0].clone(), scan[5].clone()]);
+        assert!(passes_filter(&scan[4], 10, 100, u32::MAX));
+        assert!(filter_scan(&scan, 0xff, 0, u32::MAX).is_empty());
+    }
+
+    #[test]
+    fn heading_centered_bins() {
+        let mut scan = uniform_scan(720, 8f32);
+        // 90 degrees, and slightly before it, which is still in the bin centered on the heading
+        scan[180].set_distance(1f32);
+        scan[179].set_distance(2f32);
+        scan[0].set_distance(4f32);
+
+        let bins = to_heading_centered_bins(&scan, 36, 90f32, 10f32);
+        assert_eq!(bins.len(), 36);
+        assert!((bins[0] - 0.1f32).abs() < 1e-4);
+        assert!((bins[27] - 0.4f32).abs() < 1e-4);
+        assert!((bins[1] - 0.8f32).abs() < 1e-4);
+
+        let unrotated = to_heading_centered_bins(&scan, 36, 0f32, 10f32);
+        assert!((unrotated[0] - 0.4f32).abs() < 1e-4);
+        assert!((unrotated[9] - 0.1f32).abs() < 1e-4);
+        assert!(to_heading_centered_bins(&scan, 0, 90f32, 10f32).is_empty());
+    }
+
+    #[test]
+    fn scan_delta_of_rotated_room() {
+        let prev = crate::tests::room_scan(0f32);
+
+        assert!(estimate_scan_delta(&prev, &prev).unwrap().abs() < 0.5f32);
+        assert!((estimate_scan_delta(&prev, &crate::tests::room_scan(7f32)).unwrap() - 7f32).abs() < 0.5f32);
+        assert!((estimate_scan_delta(&prev, &crate::tests::room_scan(-12.5f32)).unwrap() + 12.5f32).abs() < 0.5f32);
+        assert_eq!(estimate_scan_delta(&prev, &[]), None);
+    }
+
+    #[test]
+    fn monotonic_angles() {
+        let scan = uniform_scan(360, 2f32);
+        assert_eq!(angle_monotonicity(&scan), 1f32);
+
+        // rotations starting anywhere wrap around
+        let mut rotated = scan[100..].to_vec();
+        rotated.extend_from_slice(&scan[..100]);
+        assert_eq!(angle_monotonicity(&rotated), 1f32);
+
+        let mut shuffled = scan.clone();
+        shuffled.swap(10, 20);
+        shuffled.swap(200, 300);
+        assert!(angle_monotonicity(&shuffled) < 1f32);
+        assert!(angle_monotonicity(&shuffled) > 0.9f32);
+
+        let mut reversed = scan.clone();
+        reversed.reverse();
+        assert!(angle_monotonicity(&reversed) < 0.01f32);
+        assert_eq!(angle_monotonicity(&[]), 1f32);
+    }
+
+    #[test]
+    fn quality_weighted() {
+        let strong = uniform_scan(360, 2f32);
+        let weak: Vec<ScanPoint> = strong.iter().cloned().map(|mut point| { point.quality = 0x20; point }).collect();
+
+        assert!((quality_weighted_coverage(&strong, 360) - (0xbc as f32) / 255f32).abs() < 1e-4);
+        assert!(quality_weighted_coverage(&weak, 360) < quality_weighted_coverage(&strong, 360));
+
+        // half of the bins are empty
+        assert!((quality_weighted_coverage(&strong[..180], 360) - (0xbc as f32) / 510f32).abs() < 1e-4);
+        assert_eq!(quality_weighted_coverage(&strong, 0), 0f32);
+    }
+
+    #[test]
+    fn binned_scan_lookup() {
+        let mut scan = uniform_scan(720, 2f32);
+        scan[90].set_distance(1f32);
+        scan[91].set_distance(1.5f32);
+        scan[400].dist_mm_q2 = 0;
+        scan[401].dist_mm_q2 = 0;
+
+        let binned = BinnedScan::from_points(&scan, 360);
+        assert_eq!(binned.bin_width_deg(), 1f32);
+        assert_eq!(binned.ranges().len(), 360);
+
+        assert!((binned.range_at(10f32).unwrap() - 2f32).abs() < 1e-3);
+        assert!((binned.range_at(45.5f32).unwrap() - 1f32).abs() < 1e-3);
+        assert!((binned.range_at(-314.5f32).unwrap() - 1f32).abs() < 1e-3);
+        assert_eq!(binned.range_at(200.2f32), None);
+
+        assert_eq!(BinnedScan::from_points(&scan, 0).range_at(10f32), None);
+    }
+
+    #[test]
+    fn ordered_ranges() {
+        let mut scan = uniform_scan(360, 2f32);
+        scan[10].set_distance(0.05f32);
+        scan[20].set_distance(20f32);
+        scan[30].dist_mm_q2 = 0;
+        scan.reverse();
+
+        let ordered = to_ordered_ranges(&scan, 360, 0.15f32, 12f32);
+        assert_eq!(ordered.ranges.len(), 360);
+        assert_eq!(ordered.angle_min, 0f32);
+        assert!((ordered.angle_increment - PI / 180f32).abs() < 1e-6);
+        assert!((ordered.angle_max - PI2 * 359f32 / 360f32).abs() < 1e-5);
+
+        assert_eq!(ordered.ranges[10], 0.15f32);
+        assert_eq!(ordered.ranges[20], 12f32);
+        assert_eq!(ordered.ranges[30], f32::INFINITY);
+        assert!((ordered.ranges[40] - 2f32).abs() < 1e-3);
+    }
+}